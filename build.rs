@@ -12,7 +12,7 @@ fn main() {
 
     let mut file = File::create(&dest.join("gl_bindings.rs")).unwrap();
 
-    Registry::new(Api::Gl, (2, 1), Profile::Core, Fallbacks::All, [])
+    Registry::new(Api::Gl, (3, 3), Profile::Compatibility, Fallbacks::All, [])
         .write_bindings(gl_generator::GlobalGenerator, &mut file)
         .unwrap();
 }
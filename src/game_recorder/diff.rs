@@ -0,0 +1,98 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use super::RecordedEvent;
+use crate::tt::errors::GameError;
+
+// Events are compared without their frame, so that a frame shift does not hide the events which
+// actually differ.
+struct Line {
+    frame: u32,
+    rng_id: Option<usize>,
+    event: String,
+}
+
+impl PartialEq for Line {
+    fn eq(&self, other: &Self) -> bool {
+        self.event == other.event
+    }
+}
+
+impl Line {
+    fn new(recorded: &RecordedEvent) -> Self {
+        Line {
+            frame: recorded.frame,
+            rng_id: recorded.event.rng_id(),
+            event: serde_json::to_string(&recorded.event).unwrap(),
+        }
+    }
+
+    fn print(&self, prefix: char) {
+        match self.rng_id {
+            Some(rng_id) => println!(
+                "{}{:>7} rng {:<4} {}",
+                prefix, self.frame, rng_id, self.event
+            ),
+            None => println!("{}{:>7}          {}", prefix, self.frame, self.event),
+        }
+    }
+}
+
+pub fn print_diff<'a, 'e: 'a, L, R>(left: L, right: R, with_common: bool) -> Option<u32>
+where
+    L: Iterator<Item = &'a RecordedEvent<'e>>,
+    R: Iterator<Item = &'a RecordedEvent<'e>>,
+{
+    let left = left.map(Line::new).collect::<Vec<Line>>();
+    let right = right.map(Line::new).collect::<Vec<Line>>();
+    let mut first_difference = None;
+    for res in diff::slice(&left, &right) {
+        match res {
+            diff::Result::Both(l, _r) => {
+                if with_common {
+                    l.print(' ');
+                }
+            }
+            diff::Result::Left(l) => {
+                first_difference.get_or_insert(l.frame);
+                l.print('-');
+            }
+            diff::Result::Right(r) => {
+                first_difference.get_or_insert(r.frame);
+                r.print('+');
+            }
+        }
+    }
+    first_difference
+}
+
+fn load(path: &Path) -> Result<Vec<RecordedEvent<'static>>, GameError> {
+    let file = File::open(path)
+        .map_err(Box::from)
+        .map_err(GameError::new_game_recorder)?;
+    let mut events = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line
+            .map_err(Box::from)
+            .map_err(GameError::new_game_recorder)?;
+        events.push(
+            serde_json::from_str(&line)
+                .map_err(Box::from)
+                .map_err(GameError::new_game_recorder)?,
+        );
+    }
+    Ok(events)
+}
+
+// Prints the events which differ between two recording files, each line being annotated with
+// the frame and the RNG id of the event.
+pub fn diff_files(left: &Path, right: &Path) -> Result<(), GameError> {
+    let left = load(left)?;
+    let right = load(right)?;
+    match print_diff(left.iter(), right.iter(), false) {
+        Some(frame) => println!("FIRST DIFFERENCE AT FRAME: {}", frame),
+        None => println!("NO DIFFERENCE"),
+    }
+    Ok(())
+}
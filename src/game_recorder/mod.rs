@@ -1,15 +1,29 @@
+mod diff;
+
+pub use self::diff::diff_files;
+
 use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
 use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use self::diff::print_diff;
+
+const RECORDING_DIR_NAME: &str = "recording";
 
 #[derive(Default)]
 pub struct GameRecorder<'a> {
     recording: bool,
-    saved: Vec<GameEvent<'a>>,
-    running: Vec<GameEvent<'a>>,
+    saved: Vec<RecordedEvent<'a>>,
+    running: Vec<RecordedEvent<'a>>,
     next_id: usize,
+    frame: u32,
+    log: Option<BufWriter<File>>,
 }
 
-#[derive(PartialEq, Serialize, Debug)]
+#[derive(PartialEq, Serialize, Deserialize, Debug)]
 pub enum GameEvent<'a> {
     Start,
     End {
@@ -39,6 +53,56 @@ pub enum GameEvent<'a> {
         value: usize,
     },
     Custom(Cow<'a, str>),
+    Frame,
+    EnemySpawned {
+        spec: Cow<'a, str>,
+        x: f32,
+        y: f32,
+    },
+    ShotFired {
+        x: f32,
+        y: f32,
+        charge: bool,
+    },
+    ShotHit {
+        spec: Cow<'a, str>,
+        damage: i32,
+        destroyed: bool,
+    },
+    BulletsCreated {
+        count: u32,
+    },
+    ShipDestroyed,
+    ZoneChanged {
+        level: f32,
+    },
+    Extend {
+        next_extend: u32,
+    },
+    Score {
+        delta: u32,
+        score: u32,
+    },
+}
+
+impl<'a> GameEvent<'a> {
+    pub fn rng_id(&self) -> Option<usize> {
+        match self {
+            GameEvent::NewRand { rng_id, .. }
+            | GameEvent::SetRandSeed { rng_id, .. }
+            | GameEvent::RandF32 { rng_id, .. }
+            | GameEvent::RandSignedF32 { rng_id, .. }
+            | GameEvent::RandUsize { rng_id, .. } => Some(*rng_id),
+            _ => None,
+        }
+    }
+}
+
+// One line of a recording file.
+#[derive(PartialEq, Serialize, Deserialize, Debug)]
+pub struct RecordedEvent<'a> {
+    pub frame: u32,
+    pub event: GameEvent<'a>,
 }
 
 impl<'a> GameRecorder<'a> {
@@ -47,71 +111,93 @@ impl<'a> GameRecorder<'a> {
         self.saved.clear();
         self.running.clear();
         self.next_id = next_id;
+        self.frame = 0;
+        self.open_log("record");
     }
     pub fn save_record(&mut self) {
         self.recording = false;
+        self.close_log();
         std::mem::swap(&mut self.saved, &mut self.running);
     }
     pub fn start_replay(&mut self, next_id: usize) {
         self.recording = true;
         self.running.clear();
         self.next_id = next_id;
+        self.frame = 0;
+        self.open_log("replay");
     }
     pub fn compare_replay(&mut self) {
         let until_end = || {
             let mut reached_end = false;
-            move |e: &&GameEvent| {
+            move |e: &&RecordedEvent| {
                 if reached_end {
                     false
                 } else {
-                    if let GameEvent::End { .. } = e {
+                    if let GameEvent::End { .. } = e.event {
                         reached_end = true;
                     }
                     true
                 }
             }
         };
-        let left = &self
-            .saved
-            .iter()
-            .take_while(until_end())
-            .map(|event| serde_json::to_string(event).unwrap())
-            .collect::<Vec<String>>();
-        let right = &self
-            .running
-            .iter()
-            .take_while(until_end())
-            .map(|event| serde_json::to_string(event).unwrap())
-            .collect::<Vec<String>>();
-        let diff = diff::slice(left, right);
+        let left = self.saved.iter().take_while(until_end());
+        let right = self.running.iter().take_while(until_end());
         println!("COMPARE !!!!!!!!!!!!!!!!!!!!!!!!");
-        for res in diff {
-            match res {
-                diff::Result::Both(l, _r) => {
-                    println!(" {}", l);
-                }
-                diff::Result::Left(l) => {
-                    println!("-{}", l);
-                }
-                diff::Result::Right(r) => {
-                    println!("+{}", r);
-                }
-            }
-        }
+        print_diff(left, right, true);
         println!("END COMPARE !!!!!!!!!!!!!!!!!!!!!!!!");
         self.recording = false;
+        self.close_log();
         self.running.clear();
     }
     pub fn record_event(&mut self, event: GameEvent<'a>) {
         if self.recording {
+            let event = RecordedEvent {
+                frame: self.frame,
+                event,
+            };
+            if let Some(log) = &mut self.log {
+                let written = serde_json::to_writer(&mut *log, &event)
+                    .map_err(std::io::Error::from)
+                    .and_then(|_| writeln!(log));
+                if let Err(err) = written {
+                    eprintln!("{}", err);
+                    self.log = None;
+                }
+            }
             self.running.push(event);
         }
     }
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+        self.record_event(GameEvent::Frame);
+    }
     pub fn next_id(&mut self) -> usize {
         let id = self.next_id;
         self.next_id += 1;
         id
     }
+
+    // Each run is written to its own file, named after the time it started.
+    fn open_log(&mut self, kind: &str) {
+        self.close_log();
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() * 1000 + u64::from(d.subsec_millis()))
+            .unwrap_or(0);
+        let path = Path::new(RECORDING_DIR_NAME).join(format!("{}-{}.jsonl", millis, kind));
+        match fs::create_dir_all(RECORDING_DIR_NAME).and_then(|_| File::create(&path)) {
+            Ok(file) => self.log = Some(BufWriter::new(file)),
+            Err(err) => eprintln!("{}: {}", path.display(), err),
+        }
+    }
+
+    fn close_log(&mut self) {
+        if let Some(mut log) = self.log.take() {
+            if let Err(err) = log.flush() {
+                eprintln!("{}", err);
+            }
+        }
+    }
 }
 
 lazy_static! {
@@ -1,5 +1,7 @@
 include!(concat!(env!("OUT_DIR"), "/gl_bindings.rs"));
 
+pub const GL_FALSE: u8 = 0;
+pub const GL_TRUE: u8 = 1;
 pub const GL_ONE: u32 = 1;
 
 pub const GL_LINES: u32 = 0x0001;
@@ -19,11 +21,23 @@ pub const GL_BLEND: u32 = 0x0BE2;
 pub const GL_TEXTURE_2D: u32 = 0x0DE1;
 pub const GL_COMPILE: u32 = 0x1300;
 pub const GL_UNSIGNED_BYTE: u32 = 0x1401;
+pub const GL_FLOAT: u32 = 0x1406;
 pub const GL_MODELVIEW: u32 = 0x1700;
 pub const GL_PROJECTION: u32 = 0x1701;
-pub const GL_RGB: u32 = 0x1907;
 pub const GL_RGBA: u32 = 0x1908;
 pub const GL_LINEAR: u32 = 0x2601;
 pub const GL_TEXTURE_MAG_FILTER: u32 = 0x2801;
 pub const GL_TEXTURE_MIN_FILTER: u32 = 0x2801;
 pub const GL_COLOR_BUFFER_BIT: u32 = 0x4000;
+pub const GL_CONSTANT_ALPHA: u32 = 0x8003;
+pub const GL_ARRAY_BUFFER: u32 = 0x8892;
+pub const GL_STREAM_DRAW: u32 = 0x88E0;
+pub const GL_STATIC_DRAW: u32 = 0x88E4;
+pub const GL_FRAGMENT_SHADER: u32 = 0x8B30;
+pub const GL_VERTEX_SHADER: u32 = 0x8B31;
+pub const GL_COMPILE_STATUS: u32 = 0x8B81;
+pub const GL_LINK_STATUS: u32 = 0x8B82;
+pub const GL_INFO_LOG_LENGTH: u32 = 0x8B84;
+pub const GL_COLOR_ATTACHMENT0: u32 = 0x8CE0;
+pub const GL_FRAMEBUFFER: u32 = 0x8D40;
+pub const GL_GEOMETRY_SHADER: u32 = 0x8DD9;
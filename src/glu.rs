@@ -1,4 +1,4 @@
-use crate::gl;
+use crate::util::renderer::Renderer;
 
 fn make_identityf(m: &mut [f32; 16]) {
     *m = [
@@ -24,6 +24,7 @@ fn cross(v1: [f32; 3], v2: [f32; 3], result: &mut [f32; 3]) {
 
 #[allow(clippy::too_many_arguments)]
 pub fn look_at(
+    renderer: &dyn Renderer,
     eyex: f64,
     eyey: f64,
     eyez: f64,
@@ -58,8 +59,6 @@ pub fn look_at(
     m[2] = -forward[0];
     m[6] = -forward[1];
     m[10] = -forward[2];
-    unsafe {
-        gl::MultMatrixf(m.as_ptr());
-        gl::Translated(-eyex, -eyey, -eyez);
-    }
+    renderer.mult_matrix(&m);
+    renderer.translate(-eyex as f32, -eyey as f32, -eyez as f32);
 }
@@ -42,6 +42,17 @@ macro_rules! record_compare_replay {
     };
 }
 
+macro_rules! record_next_frame {
+    () => {
+        #[cfg(feature = "game_recorder")]
+        {
+            use crate::game_recorder::GAME_RECORDER;
+            let mut guard = GAME_RECORDER.lock().unwrap();
+            guard.next_frame();
+        }
+    };
+}
+
 macro_rules! record_event_start {
     () => {
         #[cfg(feature = "game_recorder")]
@@ -147,6 +158,18 @@ macro_rules! record_event_rand_usize {
     };
 }
 
+// Records any game event, the event variants being in scope.
+macro_rules! record_event {
+    ($event:expr) => {
+        #[cfg(feature = "game_recorder")]
+        {
+            use crate::game_recorder::{GameEvent::*, GAME_RECORDER};
+            let mut guard = GAME_RECORDER.lock().unwrap();
+            guard.record_event($event);
+        }
+    };
+}
+
 #[cfg(feature = "game_recorder")]
 macro_rules! record_next_id {
     () => {{
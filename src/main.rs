@@ -27,29 +27,25 @@ mod game_recorder;
 use piston::event_loop::*;
 use piston::input::*;
 use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process;
 use std::str::FromStr;
-use std::time::Instant;
 use structopt::StructOpt;
 
-use crate::tt::actor::bullet::BulletPool;
-use crate::tt::actor::enemy::EnemyPool;
-use crate::tt::actor::float_letter::FloatLetterPool;
-use crate::tt::actor::particle::ParticlePool;
-use crate::tt::actor::shot::ShotPool;
-use crate::tt::barrage::BarrageManager;
-use crate::tt::camera::Camera;
+use crate::tt::autopilot::{self, AutopilotPad};
+use crate::tt::capture::{self, FrameDump};
+use crate::tt::controller::{self, Controller};
 use crate::tt::errors::GameError;
-use crate::tt::letter::Letter;
-use crate::tt::manager::stage::StageManager;
+use crate::tt::ghost::Ghost;
 use crate::tt::manager::{GameManager, Manager, MoveAction};
-use crate::tt::pad::GamePad;
+use crate::tt::pad::{GamePad, Pad, VirtualPad};
 use crate::tt::prefs::PrefManager;
+use crate::tt::replay::{self, GameplayOptions, ReplayFile};
 use crate::tt::screen::Screen;
-use crate::tt::ship::Ship;
+use crate::tt::ship;
+use crate::tt::simulation::{FixedTimestep, World};
 use crate::tt::sound::SoundManager;
-use crate::tt::state::shared::SharedState;
-use crate::tt::tunnel::{Torus, Tunnel};
-use crate::tt::{GeneralParams, MoreParams};
+use crate::tt::theme::Theme;
 use crate::util::rand::Rand;
 
 struct MainLoop {
@@ -63,12 +59,34 @@ impl MainLoop {
 
     fn main(&mut self) -> Result<(), GameError> {
         let sdl = sdl2::init().map_err(GameError::new_sdl2_init)?;
-        let sdl_joystick = sdl.joystick().map(Some).unwrap_or_else(|err| {
-            eprintln!("{}", err);
-            None
-        });
 
         let mut pref_manager = PrefManager::new();
+        if let Some(deadzone) = self.options.deadzone {
+            pref_manager.set_deadzone(deadzone);
+        }
+        let controller = match open_sdl_pads(&sdl) {
+            Some((controller_subsystem, joystick_subsystem)) => Some(Controller::new(
+                controller_subsystem,
+                joystick_subsystem,
+                self.options.pad,
+                pref_manager.deadzone(),
+            )?),
+            None => None,
+        };
+
+        let themes = Theme::available()?;
+        let theme = match &self.options.theme {
+            Some(name) => {
+                let theme = Theme::find(themes, name)?;
+                pref_manager.set_theme(name);
+                theme
+            }
+            // The theme file may have changed since the theme was selected.
+            None => Theme::find(themes, pref_manager.theme()).unwrap_or_else(|err| {
+                eprintln!("{:?}", err);
+                Theme::default()
+            }),
+        };
 
         let mut screen = Screen::new(
             self.options.brightness as f32 / 100.,
@@ -76,66 +94,66 @@ impl MainLoop {
             self.options.fullscreen,
             [self.options.width as u32, self.options.height as u32].into(),
         );
+        screen.set_theme(theme);
+        if self.options.core_profile {
+            screen.set_core_profile();
+        }
         #[cfg(not(feature = "sdl_backend"))]
         screen.init_opengl()?;
         #[cfg(feature = "sdl_backend")]
         screen.init_opengl_sdl(sdl.video().map_err(GameError::new_sdl2_video_init)?)?;
 
-        let mut pad = GamePad::new(self.options.reverse, sdl_joystick)?;
-
-        let letter = Letter::new(&screen);
-
-        let initial_seed = Rand::rand_seed();
-
-        let mut tunnel = Tunnel::new(Torus::new(initial_seed));
-
-        let mut camera = Camera::new();
-        let mut ship = Ship::new(&screen, initial_seed);
-
-        let mut barrage_manager = BarrageManager::load(&screen)?;
-        let mut shots = ShotPool::new(64, &screen);
-        let mut bullets = BulletPool::new(512, initial_seed);
-        let mut enemies = EnemyPool::new(64, initial_seed, &screen);
-        let mut particles = ParticlePool::new(1024, initial_seed);
-        let mut float_letters = FloatLetterPool::new(16);
-
-        let mut stage_manager = StageManager::new(initial_seed);
+        let pad = GamePad::new(
+            self.options.reverse,
+            self.options.analog,
+            controller,
+            pref_manager.bindings().clone(),
+        );
 
         let mut sound_manager = SoundManager::new(self.options.no_sound);
         sound_manager.init()?;
 
-        let mut manager = GameManager::new(&screen)?;
-        let mut shared_state = SharedState::new();
+        let seed = Rand::rand_seed();
+        if self.options.autopilot {
+            let pad = AutopilotPad::new(pad);
+            self.run(World::new(pref_manager, screen, pad, sound_manager, seed)?)
+        } else {
+            self.run(World::new(pref_manager, screen, pad, sound_manager, seed)?)
+        }
+    }
+
+    fn run<P: Pad>(&mut self, mut world: World<P>) -> Result<(), GameError> {
+        let mut manager = GameManager::new(
+            &world.screen,
+            GameplayOptions {
+                reverse: self.options.reverse,
+            },
+        )?;
+        manager.set_record_path(self.options.record.clone());
+        if let Some(ghost) = &self.options.ghost {
+            manager.set_ghost(Some(Ghost::new(ReplayFile::load(ghost)?.replay_data)?));
+        }
 
         let mut events = Events::new(EventSettings::new().swap_buffers(true));
 
-        let mut params = GeneralParams {
-            pref_manager: &mut pref_manager,
-            screen: &mut screen,
-            letter: &letter,
-            pad: &mut pad,
-            shared_state: &mut shared_state,
-            stage_manager: &mut stage_manager,
-            sound_manager: &mut sound_manager,
-            camera: &mut camera,
-            tunnel: &mut tunnel,
-            barrage_manager: &mut barrage_manager,
-            #[cfg(feature = "game_recorder")]
-            next_recorder_id: record_next_id!(),
-        };
-        let mut more_params = MoreParams {
-            ship: &mut ship,
-            shots: &mut shots,
-            bullets: &mut bullets,
-            enemies: &mut enemies,
-            particles: &mut particles,
-            float_letters: &mut float_letters,
-        };
+        let (mut params, mut more_params) = world.params();
 
-        manager.start(&mut params, &mut more_params)?;
+        if let Some(play) = &self.options.play {
+            manager.start_playback(play, &mut params, &mut more_params)?;
+        } else {
+            manager.start(&mut params, &mut more_params)?;
+            if self.options.autopilot {
+                manager.start_in_game(Rand::rand_seed(), &mut params, &mut more_params)?;
+            }
+        }
 
-        let start_time = Instant::now();
-        let mut prev_millis = 0;
+        let mut timestep = FixedTimestep::new();
+        // Dumping frames plays one tick per frame, however long saving takes.
+        let mut frame_dump = match &self.options.dump_frames {
+            Some(dir) => Some(FrameDump::new(dir)?),
+            None => None,
+        };
+        let mut screenshot_requested = false;
 
         let mut done = false;
 
@@ -145,23 +163,16 @@ impl MainLoop {
                 .window_mut()
                 .ok_or_else(|| GameError::new_missing_window())?,
         ) {
-            let now_millis = {
-                let duration = Instant::now().duration_since(start_time);
-                duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
-            };
-            let mut frame = (now_millis - prev_millis) / 16;
-            /*if frame <= 0 {
-                frame = 1;
-                prev_millis = now_millis;
-            } else */
-            if frame > 5 {
-                frame = 5;
-                prev_millis = now_millis
-            } else {
-                prev_millis += frame * 16;
-            }
+            params.pad.poll_devices();
 
-            for _i in 0..frame {
+            let ticks = if frame_dump.is_none() {
+                timestep.due_ticks()
+            } else if e.render_args().is_some() {
+                1
+            } else {
+                0
+            };
+            for _i in 0..ticks {
                 let action = manager.mov(&mut params, &mut more_params);
                 match action {
                     MoveAction::StartTitle(from_game_over) => {
@@ -173,6 +184,14 @@ impl MainLoop {
                             false,
                             from_game_over,
                         )?;
+                        // The autopilot plays one game after the other, until escape.
+                        if self.options.autopilot && from_game_over {
+                            manager.start_in_game(
+                                Rand::rand_seed(),
+                                &mut params,
+                                &mut more_params,
+                            )?;
+                        }
                     }
                     MoveAction::StartInGame => {
                         let new_seed = Rand::rand_seed();
@@ -182,20 +201,54 @@ impl MainLoop {
                     MoveAction::None => (),
                 }
             }
+            params.perf.add_ticks(ticks);
 
             if let Some(r) = e.resize_args() {
                 params.screen.resized(r.window_size);
             }
 
             if let Some(r) = e.render_args() {
-                Screen::clear();
+                params.screen.clear();
                 manager.draw(&mut params, &mut more_params, &r);
+                params.perf.end_frame();
+                if let Some(frame_dump) = &mut frame_dump {
+                    if manager.replay_ended() {
+                        done = true;
+                    } else {
+                        frame_dump.save_frame(params.screen)?;
+                    }
+                }
+                if screenshot_requested {
+                    screenshot_requested = false;
+                    match capture::save_screenshot(params.screen) {
+                        Ok(path) => println!("Screenshot saved to {}", path.display()),
+                        Err(err) => eprintln!("{}", err),
+                    }
+                }
             }
 
+            // While rebinding, every key goes to the pad.
+            let rebinding = params.pad.rebinding_action().is_some();
+
             if let Some(b) = e.button_args() {
                 params.pad.handle_button_event(&b);
             }
 
+            if !rebinding {
+                match e.press_args() {
+                    Some(Button::Keyboard(Key::F1)) if manager.in_title() => {
+                        params.pad.start_rebinding()
+                    }
+                    Some(Button::Keyboard(Key::F2)) => params.perf.toggle(),
+                    Some(Button::Keyboard(Key::F3)) => manager.toggle_hitboxes(),
+                    Some(Button::Keyboard(Key::F4)) => manager.toggle_input_display(),
+                    Some(Button::Keyboard(Key::F12)) => screenshot_requested = true,
+                    _ => (),
+                }
+            } else if let Some(bindings) = params.pad.update_rebinding() {
+                params.pref_manager.set_bindings(bindings);
+            }
+
             if let Some(f) = e.focus_args() {
                 params.pad.handle_focus_event(f);
             }
@@ -205,10 +258,30 @@ impl MainLoop {
             }
         }
 
-        manager.quit_last(params.pref_manager)
+        // The games of the autopilot do not count in the preferences.
+        if self.options.autopilot {
+            Ok(())
+        } else {
+            manager.quit_last(params.pref_manager)
+        }
     }
 }
 
+fn open_sdl_pads(
+    sdl: &sdl2::Sdl,
+) -> Option<(sdl2::GameControllerSubsystem, sdl2::JoystickSubsystem)> {
+    sdl.game_controller()
+        .and_then(|controller_subsystem| {
+            sdl.joystick()
+                .map(|joystick_subsystem| (controller_subsystem, joystick_subsystem))
+        })
+        .map(Some)
+        .unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            None
+        })
+}
+
 fn parse_brightness(s: &str) -> Result<usize, Box<dyn Error>> {
     let val = usize::from_str(s)?;
     if val > 100 {
@@ -225,6 +298,25 @@ fn parse_luminosity(s: &str) -> Result<usize, Box<dyn Error>> {
     Ok(val)
 }
 
+fn parse_grade(s: &str) -> Result<u32, Box<dyn Error>> {
+    let val = u32::from_str(s)?;
+    if val as usize >= ship::GRADE_NUM {
+        Err(format!(
+            "grade must be in the range [0-{}]",
+            ship::GRADE_NUM - 1
+        ))?;
+    }
+    Ok(val)
+}
+
+fn parse_deadzone(s: &str) -> Result<u32, Box<dyn Error>> {
+    let val = u32::from_str(s)?;
+    if val > 100 {
+        Err("deadzone must be in the range [0-100]")?;
+    }
+    Ok(val)
+}
+
 #[derive(StructOpt, Debug)]
 struct Options {
     #[structopt(long, default_value = "100", parse(try_from_str = "parse_brightness"))]
@@ -241,9 +333,214 @@ struct Options {
     no_sound: bool,
     #[structopt(long)]
     reverse: bool,
+    /// Plays with the game pad of this index, as printed by list-pads, instead of the first one
+    #[structopt(long)]
+    pad: Option<u32>,
+    /// Lets the autopilot play one game after the other, escape getting back to the title
+    #[structopt(long, conflicts_with = "play")]
+    autopilot: bool,
+    /// Steers and moves forward in proportion to how far the stick is pushed
+    #[structopt(long)]
+    analog: bool,
+    /// Selects and saves the stick deadzone, in percent of its course [0-100]
+    #[structopt(long, parse(try_from_str = "parse_deadzone"))]
+    deadzone: Option<u32>,
+    /// Selects and saves the colour theme: classic, red-green, blue-yellow, high-contrast, or
+    /// one of the themes.json file
+    #[structopt(long)]
+    theme: Option<String>,
+    /// Draws with OpenGL 3.3 shaders, for the drivers which only expose a core profile
+    #[structopt(long)]
+    core_profile: bool,
+    /// Saves the replay of each finished game to this file, or in this directory
+    #[structopt(long, parse(from_os_str))]
+    record: Option<PathBuf>,
+    /// Starts straight into the playback of this replay file
+    #[structopt(long, parse(from_os_str))]
+    play: Option<PathBuf>,
+    /// Races against the ghost of this replay file, on its seed, grade and level
+    #[structopt(long, parse(from_os_str))]
+    ghost: Option<PathBuf>,
+    /// Plays the replay given with --play once, one tick per frame, saving every frame as a PNG
+    /// in this directory
+    #[structopt(long, parse(from_os_str), requires = "play")]
+    dump_frames: Option<PathBuf>,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Lets the autopilot play games without rendering, printing the seed and result of each one
+    #[structopt(name = "autopilot")]
+    Autopilot {
+        /// Number of games to play, no limit if not given
+        #[structopt(long)]
+        games: Option<u32>,
+        /// Seed of the first game, the next ones being played on the following seeds
+        #[structopt(long)]
+        seed: Option<u64>,
+        #[structopt(long, default_value = "0", parse(try_from_str = "parse_grade"))]
+        grade: u32,
+        /// Saves the replay of each game in this directory, named after its seed
+        #[structopt(long, parse(from_os_str))]
+        record: Option<PathBuf>,
+    },
+    /// Prints the connected game pads with their index
+    #[structopt(name = "list-pads")]
+    ListPads,
+    /// Replays a recorded game without rendering and prints its result
+    #[structopt(name = "verify-replay")]
+    VerifyReplay {
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Plays a replay without any window nor GPU, saving its frames as PNG files in a directory
+    #[structopt(name = "render-replay")]
+    RenderReplay {
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        #[structopt(parse(from_os_str))]
+        dir: PathBuf,
+        /// Saves one frame out of this many, e.g. 600 for a thumbnail every ten seconds
+        #[structopt(long, default_value = "1")]
+        every: u32,
+        #[structopt(long, default_value = "640")]
+        width: u32,
+        #[structopt(long, default_value = "480")]
+        height: u32,
+    },
+    /// Prints the differences between two event logs written by the game recorder
+    #[structopt(name = "diff-recordings")]
+    DiffRecordings {
+        #[structopt(parse(from_os_str))]
+        left: PathBuf,
+        #[structopt(parse(from_os_str))]
+        right: PathBuf,
+    },
+}
+
+fn run_autopilot(
+    games: Option<u32>,
+    seed: Option<u64>,
+    grade: u32,
+    record: Option<PathBuf>,
+) -> Result<(), GameError> {
+    let seed = seed.unwrap_or_else(Rand::rand_seed);
+    let mut game = 0;
+    while games.map_or(true, |games| game < games) {
+        let game_seed = seed.wrapping_add(u64::from(game));
+        // Printed first, so that a crash can be played again with --seed.
+        println!("SEED: {}", game_seed);
+        let (outcome, replay_data) = autopilot::play(grade, game_seed, None)?;
+        print!("{}", outcome);
+        if let Some(dir) = &record {
+            ReplayFile::new(replay_data, GameplayOptions::default())
+                .save(dir.join(format!("{}.rpl", game_seed)))?;
+        }
+        game += 1;
+    }
+    Ok(())
+}
+
+fn list_pads() -> Result<(), GameError> {
+    let sdl = sdl2::init().map_err(GameError::new_sdl2_init)?;
+    if let Some((controller_subsystem, joystick_subsystem)) = open_sdl_pads(&sdl) {
+        controller::load_mappings(&controller_subsystem)?;
+        for (index, name) in controller::list(&controller_subsystem, &joystick_subsystem)
+            .iter()
+            .enumerate()
+        {
+            println!("{}: {}", index, name);
+        }
+    }
+    Ok(())
+}
+
+fn verify_replay(file: &Path) -> Result<(), GameError> {
+    let replay_file = ReplayFile::load(file)?;
+    let outcome = replay::verify_replay(&replay_file.replay_data)?;
+    print!("{}", outcome);
+    if let Some(desync) = outcome.first_desync {
+        eprintln!("{}", desync);
+        process::exit(1);
+    }
+    Ok(())
+}
+
+// Draws the replay with the software rasterizer, the same way the title screen plays it.
+fn render_replay(
+    file: &Path,
+    dir: &Path,
+    every: u32,
+    width: u32,
+    height: u32,
+) -> Result<(), GameError> {
+    let options = ReplayFile::load(file)?.header.options;
+    let mut screen = Screen::new(1., 0., false, [width, height].into());
+    screen.init_cpu()?;
+    let mut world = World::new(
+        PrefManager::new_transient(),
+        screen,
+        VirtualPad::new(),
+        SoundManager::new(true),
+        0,
+    )?;
+    let mut manager = GameManager::new(&world.screen, options)?;
+    let (mut params, mut more_params) = world.params();
+    manager.start_playback(file, &mut params, &mut more_params)?;
+
+    let render_args = RenderArgs {
+        ext_dt: 0.,
+        window_size: [f64::from(width), f64::from(height)],
+        draw_size: [width, height],
+    };
+    let mut frame_dump = FrameDump::new(dir)?;
+    let mut frame = 0;
+    while !manager.replay_ended() {
+        if frame % every.max(1) == 0 {
+            params.screen.clear();
+            manager.draw(&mut params, &mut more_params, &render_args);
+            frame_dump.save_frame(params.screen)?;
+        }
+        manager.mov(&mut params, &mut more_params);
+        frame += 1;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "game_recorder")]
+fn diff_recordings(left: &Path, right: &Path) -> Result<(), GameError> {
+    game_recorder::diff_files(left, right)
+}
+
+#[cfg(not(feature = "game_recorder"))]
+fn diff_recordings(_left: &Path, _right: &Path) -> Result<(), GameError> {
+    Err(GameError::new_game_recorder(Box::from(
+        "the game was built without the game_recorder feature",
+    )))
 }
 
 fn main() {
-    let options = Options::from_args();
-    MainLoop::new(options).main().unwrap();
+    let mut options = Options::from_args();
+    match options.command.take() {
+        Some(Command::Autopilot {
+            games,
+            seed,
+            grade,
+            record,
+        }) => run_autopilot(games, seed, grade, record),
+        Some(Command::ListPads) => list_pads(),
+        Some(Command::VerifyReplay { file }) => verify_replay(&file),
+        Some(Command::RenderReplay {
+            file,
+            dir,
+            every,
+            width,
+            height,
+        }) => render_replay(&file, &dir, every, width, height),
+        Some(Command::DiffRecordings { left, right }) => diff_recordings(&left, &right),
+        None => MainLoop::new(options).main(),
+    }
+    .unwrap();
 }
@@ -1,10 +1,10 @@
+use std::hash::Hasher;
 use std::ops::{Index, IndexMut};
 use std::rc::Rc;
 
 use bulletml::{AppRunner, BulletML, Runner, RunnerData, State};
 
-use crate::gl;
-
+use crate::util::math;
 use crate::util::rand::Rand;
 use crate::util::vector::Vector;
 
@@ -12,12 +12,15 @@ use crate::tt::actor::float_letter::FloatLetterPool;
 use crate::tt::actor::particle::ParticlePool;
 use crate::tt::actor::pool::{Pool, PoolActorRef, PoolGetInstanceArea};
 use crate::tt::actor::shot::{Shot, ShotPool};
-use crate::tt::shape::{Collidable, Drawable};
+use crate::tt::barrage::{BarrageManager, BulletMLId, BulletShapeRef};
+use crate::tt::hitbox;
+use crate::tt::screen::Screen;
+use crate::tt::shape::Collidable;
 use crate::tt::ship::Ship;
 use crate::tt::tunnel::{self, Tunnel};
 use crate::tt::GeneralParams;
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct Bullet {
     bml_params: Rc<Vec<BMLParam>>,
     bml_idx: usize,
@@ -119,7 +122,7 @@ impl Bullet {
                 } else if ox < -std::f32::consts::PI {
                     ox += std::f32::consts::PI * 2.;
                 }
-                bullet.deg = (f32::atan2(ox, tpos.y - bullet.pos.y) * bullet.x_reverse
+                bullet.deg = (math::atan2(ox, tpos.y - bullet.pos.y) * bullet.x_reverse
                     + std::f32::consts::PI / 2.)
                     * bullet.y_reverse
                     - std::f32::consts::PI / 2.;
@@ -130,7 +133,8 @@ impl Bullet {
             }
             if !self.is_simple {
                 let bml_param = &self.bml_params[self.bml_idx];
-                bullet.mov(&mut self.runner, manager, bml_param, tpos, rand);
+                let bml = params.barrage_manager.bml(bml_param.bml);
+                bullet.mov(&mut self.runner, manager, bml, bml_param, tpos, rand);
                 if manager.bullet_should_be_released {
                     self.should_be_released = true;
                 }
@@ -138,7 +142,7 @@ impl Bullet {
                     if self.is_top {
                         self.bml_idx = 0;
                         let bml_param = &self.bml_params[self.bml_idx];
-                        self.runner.init(&bml_param.bml);
+                        self.runner.init(params.barrage_manager.bml(bml_param.bml));
                         if self.is_wait {
                             self.wait_cnt = self.post_wait;
                             return (false, false);
@@ -152,14 +156,14 @@ impl Bullet {
                 return (true, false);
             }
             let speed_rank = self.bml_params[self.bml_idx].speed;
-            let mx = (f32::sin(bullet.deg) * bullet.speed + bullet.acc.x)
+            let mx = (math::sin(bullet.deg) * bullet.speed + bullet.acc.x)
                 * speed_rank
                 * bullet.x_reverse;
-            let my = (f32::cos(bullet.deg) * bullet.speed - bullet.acc.y)
+            let my = (math::cos(bullet.deg) * bullet.speed - bullet.acc.y)
                 * speed_rank
                 * bullet.y_reverse;
-            let d = f32::atan2(mx, my);
-            let r = (1. - f32::abs(f32::sin(d)) * 0.999) * (ship.speed() * 5.);
+            let d = math::atan2(mx, my);
+            let r = (1. - f32::abs(math::sin(d)) * 0.999) * (ship.speed() * 5.);
             bullet.pos.x += mx * r;
             bullet.pos.y += my * r;
 
@@ -223,39 +227,52 @@ impl Bullet {
         }
     }
 
-    fn draw(&self, tunnel: &Tunnel) {
+    fn draw(&self, tunnel: &Tunnel, barrage_manager: &BarrageManager, screen: &Screen) {
+        let renderer = screen.renderer();
         if self.is_visible {
             let bullet = self.bullet.as_ref().unwrap();
             let d = (bullet.deg * bullet.x_reverse + std::f32::consts::PI / 2.) * bullet.y_reverse
                 - std::f32::consts::PI / 2.;
             let sp = tunnel.get_pos_v(bullet.pos);
-            unsafe {
-                gl::PushMatrix();
-                gl::Translatef(sp.x, sp.y, sp.z);
-                gl::Rotatef(d * 180. / std::f32::consts::PI, 0., 1., 0.);
-                gl::Rotatef((self.cnt * 6) as f32, 0., 0., 1.);
-            }
+            renderer.push_matrix();
+            renderer.translate(sp.x, sp.y, sp.z);
+            renderer.rotate(d * 180. / std::f32::consts::PI, 0., 1., 0.);
+            renderer.rotate((self.cnt * 6) as f32, 0., 0., 1.);
             if self.disap_cnt <= 0 {
-                bullet.shape.draw();
+                barrage_manager.draw_shape(bullet.shape, false, screen);
             } else {
                 let s = 1. - self.disap_cnt as f32 / DISAP_CNT as f32;
-                unsafe {
-                    gl::Scalef(s, s, s);
-                }
-                bullet.disap_shape.draw();
-            }
-            unsafe {
-                gl::PopMatrix();
+                renderer.scale(s, s, s);
+                barrage_manager.draw_shape(bullet.shape, true, screen);
             }
+            renderer.pop_matrix();
+        }
+    }
+
+    fn draw_hitbox(&self, tunnel: &Tunnel, screen: &Screen) {
+        if let Some((pos, ppos)) = self.hit_path() {
+            hitbox::draw_path(pos, ppos, tunnel, screen.renderer());
+        }
+    }
+
+    // The path covered on the last tick, when the bullet can hit the ship.
+    fn hit_path(&self) -> Option<(Vector, Vector)> {
+        if self.is_visible && self.disap_cnt <= 0 {
+            Some((self.bullet.as_ref().unwrap().pos, self.ppos))
+        } else {
+            None
         }
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct BulletPool {
     pool: Pool<Bullet>,
     cnt: u32,
     barrage_rand: Rand,
     bullet_rand: Rand,
+    #[cfg(feature = "game_recorder")]
+    created_count: u32,
 }
 
 impl BulletPool {
@@ -265,6 +282,8 @@ impl BulletPool {
             cnt: 0,
             barrage_rand: Rand::new(seed),
             bullet_rand: Rand::new(seed),
+            #[cfg(feature = "game_recorder")]
+            created_count: 0,
         }
     }
 
@@ -279,23 +298,22 @@ impl BulletPool {
         pos: Vector,
         deg: f32,
         speed: f32,
-        shape: &Rc<dyn Drawable>,
-        disap_shape: &Rc<dyn Drawable>,
+        shape: BulletShapeRef,
         x_reverse: f32,
         y_reverse: f32,
         long_range: bool,
         prev_wait: u32,
         post_wait: u32,
+        barrage_manager: &BarrageManager,
     ) -> Option<PoolActorRef> {
         let inst = self.pool.get_instance();
         if let Some((bullet, bullet_ref)) = inst {
             bullet.bml_params = bml_params.clone();
             bullet.bml_idx = 0;
             let bml_param = &bml_params[0];
-            bullet.runner.init(&bml_param.bml);
+            bullet.runner.init(barrage_manager.bml(bml_param.bml));
             bullet.bullet = Some(BulletImpl::new_param_first(
                 shape,
-                disap_shape,
                 x_reverse,
                 y_reverse,
                 long_range,
@@ -304,6 +322,10 @@ impl BulletPool {
             bullet.set(pos, deg, speed);
             bullet.set_wait(prev_wait, post_wait);
             bullet.set_top();
+            #[cfg(feature = "game_recorder")]
+            {
+                self.created_count += 1;
+            }
             Some(bullet_ref)
         } else {
             None
@@ -322,16 +344,24 @@ impl BulletPool {
         let (mut current_pool, mut new_pool) = self.pool.split();
         let mut iter = current_pool.into_iter();
         while let Some((bullet, bullet_ref)) = iter.next() {
+            #[cfg(feature = "game_recorder")]
+            let manager_created_count;
             let (release, destroy) = {
-                let mut manager = BulletsManager::new(&mut new_pool, bullet_ref, turn);
-                bullet.mov(
+                let mut manager =
+                    BulletsManager::new(&mut new_pool, params.barrage_manager, bullet_ref, turn);
+                let res = bullet.mov(
                     &mut manager,
                     params,
                     ship,
                     shots,
                     particles,
                     &mut self.bullet_rand,
-                )
+                );
+                #[cfg(feature = "game_recorder")]
+                {
+                    manager_created_count = manager.created_count;
+                }
+                res
             };
             if release {
                 iter.release();
@@ -339,6 +369,10 @@ impl BulletPool {
             if destroy {
                 ship_destroyed = true;
             }
+            #[cfg(feature = "game_recorder")]
+            {
+                self.created_count += manager_created_count;
+            }
         }
         if ship_destroyed {
             self.clear_visible();
@@ -347,16 +381,34 @@ impl BulletPool {
         self.cnt += 1;
     }
 
-    pub fn draw(&self, tunnel: &Tunnel) {
+    pub fn draw(&self, tunnel: &Tunnel, barrage_manager: &BarrageManager, screen: &Screen) {
         for bullet in &self.pool {
-            bullet.draw(tunnel);
+            bullet.draw(tunnel, barrage_manager, screen);
         }
     }
 
+    pub fn draw_hitboxes(&self, tunnel: &Tunnel, screen: &Screen) {
+        for bullet in &self.pool {
+            bullet.draw_hitbox(tunnel, screen);
+        }
+    }
+
+    pub fn hit_paths(&self) -> impl Iterator<Item = (Vector, Vector)> + '_ {
+        self.pool.into_iter().filter_map(Bullet::hit_path)
+    }
+
     pub fn release(&mut self, bullet_ref: PoolActorRef) {
         self.pool.release(bullet_ref);
     }
 
+    pub fn get_num(&self) -> usize {
+        self.pool.get_num()
+    }
+
+    pub fn get_capacity(&self) -> usize {
+        self.pool.get_capacity()
+    }
+
     pub fn clear(&mut self) {
         self.pool.clear();
         self.cnt = 0;
@@ -396,6 +448,23 @@ impl BulletPool {
     pub fn barrage_rand(&mut self) -> &mut Rand {
         &mut self.barrage_rand
     }
+
+    // Records how many bullets were created since the last call.
+    #[cfg(feature = "game_recorder")]
+    pub fn record_created_count(&mut self) {
+        if self.created_count > 0 {
+            record_event!(BulletsCreated {
+                count: self.created_count,
+            });
+            self.created_count = 0;
+        }
+    }
+
+    pub fn hash_state<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.pool.get_num());
+        self.barrage_rand.hash_state(state);
+        self.bullet_rand.hash_state(state);
+    }
 }
 
 impl Index<PoolActorRef> for BulletPool {
@@ -413,22 +482,29 @@ impl IndexMut<PoolActorRef> for BulletPool {
 
 struct BulletsManager<'a, 'p> {
     pool: &'a mut PoolGetInstanceArea<'p, Bullet>,
+    barrage_manager: &'a BarrageManager,
     src_bullet_ref: PoolActorRef,
     bullet_should_be_released: bool,
     turn: u32,
+    #[cfg(feature = "game_recorder")]
+    created_count: u32,
 }
 
 impl<'a, 'p> BulletsManager<'a, 'p> {
     fn new(
         pool: &'a mut PoolGetInstanceArea<'p, Bullet>,
+        barrage_manager: &'a BarrageManager,
         src_bullet_ref: PoolActorRef,
         turn: u32,
     ) -> Self {
         BulletsManager {
             pool,
+            barrage_manager,
             src_bullet_ref,
             bullet_should_be_released: false,
             turn,
+            #[cfg(feature = "game_recorder")]
+            created_count: 0,
         }
     }
 
@@ -446,8 +522,7 @@ impl<'a, 'p> BulletsManager<'a, 'p> {
                 src_bullet.bml_idx,
                 src_bullet.bml_params.clone(),
                 BulletImpl::new_param(
-                    &src_bullet_impl.shape,
-                    &src_bullet_impl.disap_shape,
+                    src_bullet_impl.shape,
                     src_bullet_impl.x_reverse,
                     src_bullet_impl.y_reverse,
                     src_bullet_impl.long_range,
@@ -466,8 +541,12 @@ impl<'a, 'p> BulletsManager<'a, 'p> {
             };
             bullet.bml_params = bml_params.clone();
             bullet.bml_idx = bml_idx;
+            #[cfg(feature = "game_recorder")]
+            {
+                self.created_count += 1;
+            }
             let bml_param = &bml_params[bml_idx];
-            bullet.runner.init(&bml_param.bml);
+            bullet.runner.init(self.barrage_manager.bml(bml_param.bml));
             bullet.bullet = Some(bullet_impl);
             if goto_next_parser {
                 bullet.set(src_bullet_impl_pos, deg, speed);
@@ -492,8 +571,7 @@ impl<'a, 'p> BulletsManager<'a, 'p> {
                 src_bullet.bml_idx,
                 src_bullet.bml_params.clone(),
                 BulletImpl::new_param(
-                    &src_bullet_impl.shape,
-                    &src_bullet_impl.disap_shape,
+                    src_bullet_impl.shape,
                     src_bullet_impl.x_reverse,
                     src_bullet_impl.y_reverse,
                     src_bullet_impl.long_range,
@@ -505,6 +583,10 @@ impl<'a, 'p> BulletsManager<'a, 'p> {
         if let Some((bullet, _)) = inst {
             bullet.bml_params = bml_params.clone();
             bullet.bml_idx = bml_idx;
+            #[cfg(feature = "game_recorder")]
+            {
+                self.created_count += 1;
+            }
             bullet.runner.init_from_state(state);
             bullet.bullet = Some(bullet_impl);
             bullet.set(src_bullet_impl_pos, deg, speed);
@@ -520,13 +602,13 @@ impl<'a, 'p> BulletsManager<'a, 'p> {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct BulletImpl {
     pub pos: Vector,
     acc: Vector,
     pub deg: f32,
     speed: f32,
-    shape: Rc<dyn Drawable>,
-    disap_shape: Rc<dyn Drawable>,
+    shape: BulletShapeRef,
     x_reverse: f32,
     y_reverse: f32,
     long_range: bool,
@@ -535,8 +617,7 @@ pub struct BulletImpl {
 
 impl BulletImpl {
     fn new_param_first(
-        shape: &Rc<dyn Drawable>,
-        disap_shape: &Rc<dyn Drawable>,
+        shape: BulletShapeRef,
         x_reverse: f32,
         y_reverse: f32,
         long_range: bool,
@@ -547,8 +628,7 @@ impl BulletImpl {
             acc: Vector::default(),
             deg: 0.,
             speed: 0.,
-            shape: shape.clone(),
-            disap_shape: disap_shape.clone(),
+            shape,
             x_reverse,
             y_reverse,
             long_range,
@@ -556,14 +636,8 @@ impl BulletImpl {
         }
     }
 
-    fn new_param(
-        shape: &Rc<dyn Drawable>,
-        disap_shape: &Rc<dyn Drawable>,
-        x_reverse: f32,
-        y_reverse: f32,
-        long_range: bool,
-    ) -> Self {
-        BulletImpl::new_param_first(shape, disap_shape, x_reverse, y_reverse, long_range, None)
+    fn new_param(shape: BulletShapeRef, x_reverse: f32, y_reverse: f32, long_range: bool) -> Self {
+        BulletImpl::new_param_first(shape, x_reverse, y_reverse, long_range, None)
     }
 
     fn set(&mut self, pos: Vector, deg: f32, speed: f32) {
@@ -577,13 +651,14 @@ impl BulletImpl {
         &mut self,
         runner: &mut Runner<TTRunner>,
         manager: &mut BulletsManager,
+        bml: &BulletML,
         bml_param: &BMLParam,
         target: Vector,
         rand: &mut Rand,
     ) {
         if !runner.is_end() {
             runner.run(&mut RunnerData {
-                bml: &bml_param.bml,
+                bml,
                 data: &mut TTRunnerData {
                     manager,
                     rand,
@@ -596,6 +671,7 @@ impl BulletImpl {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 struct TTRunner {}
 
 impl TTRunner {
@@ -632,7 +708,7 @@ impl<'a, 'm, 'p> AppRunner<TTRunnerData<'a, 'm, 'p>> for TTRunner {
     fn get_aim_direction(&self, data: &TTRunnerData) -> f64 {
         let b = data.bullet.pos;
         let t = data.target;
-        f64::from(rtod(f32::atan2(t.x - b.x, t.y - b.y)))
+        f64::from(rtod(math::atan2(t.x - b.x, t.y - b.y)))
     }
 
     fn get_bullet_speed(&self, data: &TTRunnerData) -> f64 {
@@ -700,8 +776,9 @@ impl<'a, 'm, 'p> AppRunner<TTRunnerData<'a, 'm, 'p>> for TTRunner {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct BMLParam {
-    pub bml: Rc<BulletML>,
+    pub bml: BulletMLId,
     pub rank: f32,
     pub root_rank_effect: f32,
     pub speed: f32,
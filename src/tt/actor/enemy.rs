@@ -1,26 +1,28 @@
+use std::hash::Hasher;
+
 use crate::tt::actor::bullet::{Bullet, BulletPool};
 use crate::tt::actor::float_letter::FloatLetterPool;
 use crate::tt::actor::particle::{ParticlePool, ParticleSpec};
 use crate::tt::actor::pool::{Pool, PoolActorRef};
 use crate::tt::actor::shot::Shot;
 use crate::tt::barrage::BarrageManager;
+use crate::tt::hitbox;
 use crate::tt::screen::Screen;
 use crate::tt::shape::bit_shape::BitShape;
 use crate::tt::shape::{Collidable, Drawable};
 use crate::tt::ship::{self, Ship};
 use crate::tt::tunnel::{self, InCourseSliceCheck, InsideSliceCheck, Tunnel};
 use crate::tt::GeneralParams;
+use crate::util::math;
 use crate::util::rand::Rand;
 use crate::util::vector::Vector;
 
-use crate::gl;
-
 use self::ship_spec::ShipSpec;
 
 const OUT_OF_COURSE_BANK: f32 = 1.0;
 const DISAP_DEPTH: f32 = -5.0;
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct Enemy {
     spec: EnemySpec,
     pos: Vector,
@@ -42,7 +44,7 @@ pub struct Enemy {
     bit_cnt: u32,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 enum EnemySpec {
     Small(usize),
     Medium(usize),
@@ -79,6 +81,11 @@ impl Enemy {
             EnemySetOption::New { spec, rand } => {
                 self.shield = spec.shield();
                 self.base_bank = spec.create_base_bank(rand);
+                record_event!(EnemySpawned {
+                    spec: format!("{:?}", self.spec).into(),
+                    x,
+                    y,
+                });
             }
             EnemySetOption::Passed { shield, base_bank } => {
                 self.shield = shield;
@@ -103,6 +110,7 @@ impl Enemy {
         bullets: &mut BulletPool,
         particles: &mut ParticlePool,
         passed_pool: Option<&mut Pool<Enemy>>,
+        barrage_manager: &BarrageManager,
     ) -> (bool, bool) {
         let mut goto_next_zone = false;
         if !passed {
@@ -200,11 +208,11 @@ impl Enemy {
                     ox += std::f32::consts::PI * 2.;
                 }
                 let oy = self.ppos.y;
-                let od = f32::atan2(ox, oy);
+                let od = math::atan2(ox, oy);
                 self.flip_mv_cnt = 48;
                 self.flip_mv = Vector::new_at(
-                    f32::sin(od) * ship.speed() * 0.4,
-                    f32::cos(od) * ship.speed() * 7.,
+                    math::sin(od) * ship.speed() * 0.4,
+                    math::cos(od) * ship.speed() * 7.,
                 );
             }
         }
@@ -234,10 +242,12 @@ impl Enemy {
         self.d1 += (sl.d1() - self.d1) * 0.1;
         self.d2 += (sl.d2() - self.d2) * 0.1;
         if !passed && self.top_bullet.is_none() {
-            self.top_bullet = spec.barrage().add_top_bullet(bullets);
+            self.top_bullet = spec.barrage().add_top_bullet(bullets, barrage_manager);
             if let Some(bit_spec) = &mut spec.bit_spec {
                 for _ in 0..bit_spec.bit_num {
-                    let ba = bit_spec.bit_barrage.add_top_bullet(bullets);
+                    let ba = bit_spec
+                        .bit_barrage
+                        .add_top_bullet(bullets, barrage_manager);
                     if let Some(ba) = ba {
                         let ba_inst = &mut bullets[ba];
                         ba_inst.unset_aim_top();
@@ -350,6 +360,11 @@ impl Enemy {
             .check_collision_shape(ox, oy, shot.shape.as_ref().unwrap(), 1.)
         {
             self.shield -= shot.damage();
+            record_event!(ShotHit {
+                spec: format!("{:?}", self.spec).into(),
+                damage: shot.damage(),
+                destroyed: self.shield <= 0,
+            });
             let release_enemy = if self.shield <= 0 {
                 self.destroyed(spec, params, ship, particles, rand);
                 true
@@ -465,53 +480,39 @@ impl Enemy {
         tunnel: &Tunnel,
         bullets: &BulletPool,
         bit_shape: &BitShape,
+        screen: &Screen,
     ) {
+        let renderer = screen.renderer();
         let sp = tunnel.get_pos_v(self.pos);
-        unsafe {
-            gl::PushMatrix();
-        }
-        sp.gl_translate();
-        unsafe {
-            gl::Rotatef(
-                (self.pos.x - self.bank) * 180. / std::f32::consts::PI,
-                0.,
-                0.,
-                1.,
-            );
-        }
+        renderer.push_matrix();
+        sp.translate(renderer);
+        renderer.rotate(
+            (self.pos.x - self.bank) * 180. / std::f32::consts::PI,
+            0.,
+            0.,
+            1.,
+        );
         if sp.z > 200. {
             let sz = 1. - (sp.z - 200.) * 0.0025;
-            unsafe {
-                gl::Scalef(sz, sz, sz);
-            }
-        }
-        unsafe {
-            gl::Rotatef(self.d1 * 180. / std::f32::consts::PI, 0., 1., 0.);
-            gl::Rotatef(self.d2 * 180. / std::f32::consts::PI, 1., 0., 0.);
+            renderer.scale(sz, sz, sz);
         }
+        renderer.rotate(self.d1 * 180. / std::f32::consts::PI, 0., 1., 0.);
+        renderer.rotate(self.d2 * 180. / std::f32::consts::PI, 1., 0., 0.);
         if !self.damaged {
-            spec.shape().draw();
+            spec.shape().draw(screen);
         } else {
-            spec.damaged_shape().draw();
-        }
-        unsafe {
-            gl::PopMatrix();
+            spec.damaged_shape().draw(screen);
         }
+        renderer.pop_matrix();
         for bb in &self.bit_bullet {
             let bb_inst = &bullets[*bb];
             let sp = tunnel.get_pos_v(bb_inst.bullet.as_ref().unwrap().pos);
-            unsafe {
-                gl::PushMatrix();
-            }
-            sp.gl_translate();
-            unsafe {
-                gl::Rotatef((self.bit_cnt * 7) as f32, 0., 1., 0.);
-                gl::Rotatef(self.pos.x * 180. / std::f32::consts::PI, 0., 0., 1.);
-            }
-            bit_shape.draw();
-            unsafe {
-                gl::PopMatrix();
-            }
+            renderer.push_matrix();
+            sp.translate(renderer);
+            renderer.rotate((self.bit_cnt * 7) as f32, 0., 1., 0.);
+            renderer.rotate(self.pos.x * 180. / std::f32::consts::PI, 0., 0., 1.);
+            bit_shape.draw(screen);
+            renderer.pop_matrix();
         }
     }
 
@@ -525,6 +526,7 @@ impl Enemy {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct EnemyPool {
     pool: Pool<Enemy>,
     passed_pool: Pool<Enemy>,
@@ -537,7 +539,7 @@ pub struct EnemyPool {
 }
 
 impl EnemyPool {
-    pub fn new(n: usize, seed: u64, screen: &Screen) -> Self {
+    pub fn new(n: usize, seed: u64) -> Self {
         EnemyPool {
             pool: Pool::new(n),
             passed_pool: Pool::new(n),
@@ -546,7 +548,7 @@ impl EnemyPool {
             small_ship_specs: Vec::new(),
             medium_ship_specs: Vec::new(),
             boss_ship_specs: Vec::new(),
-            bit_shape: BitShape::new(screen),
+            bit_shape: BitShape::new(),
         }
     }
 
@@ -556,19 +558,17 @@ impl EnemyPool {
         grade: u32,
         medium_boss_zone: bool,
         boss_num: u32,
-        screen: &Screen,
         barrage_manager: &mut BarrageManager,
     ) {
         self.small_ship_specs.clear();
         self.medium_ship_specs.clear();
         self.boss_ship_specs.clear();
         for _ in 0..(2 + self.rand.gen_usize(2)) {
-            let ss =
-                ShipSpec::new_small(&mut self.rand, level * 1.8, grade, screen, barrage_manager);
+            let ss = ShipSpec::new_small(&mut self.rand, level * 1.8, grade, barrage_manager);
             self.small_ship_specs.push(ss);
         }
         for _ in 0..(2 + self.rand.gen_usize(2)) {
-            let ss = ShipSpec::new_medium(&mut self.rand, level * 1.9, screen, barrage_manager);
+            let ss = ShipSpec::new_medium(&mut self.rand, level * 1.9, barrage_manager);
             self.medium_ship_specs.push(ss);
         }
         for _ in 0..boss_num {
@@ -582,7 +582,6 @@ impl EnemyPool {
                 lv,
                 boss_speed,
                 medium_boss_zone,
-                screen,
                 barrage_manager,
             );
             self.boss_ship_specs.push(ss);
@@ -662,6 +661,7 @@ impl EnemyPool {
         ship: &mut Ship,
         bullets: &mut BulletPool,
         particles: &mut ParticlePool,
+        barrage_manager: &BarrageManager,
     ) -> bool {
         let mut goto_next_zone = false;
         let (mut current_pool, _) = self.pool.split();
@@ -682,6 +682,7 @@ impl EnemyPool {
                     bullets,
                     particles,
                     Some(&mut self.passed_pool),
+                    barrage_manager,
                 );
                 if goto_nz {
                     goto_next_zone = true;
@@ -704,6 +705,7 @@ impl EnemyPool {
         ship: &mut Ship,
         bullets: &mut BulletPool,
         particles: &mut ParticlePool,
+        barrage_manager: &BarrageManager,
     ) {
         let (mut current_pool, _) = self.passed_pool.split();
         let mut iter = current_pool.into_iter();
@@ -715,7 +717,15 @@ impl EnemyPool {
                     EnemySpec::Boss(idx) => &mut self.boss_ship_specs[idx],
                 };
                 let (release, _) = enemy.mov(
-                    enemy.spec, true, spec, tunnel, ship, bullets, particles, None,
+                    enemy.spec,
+                    true,
+                    spec,
+                    tunnel,
+                    ship,
+                    bullets,
+                    particles,
+                    None,
+                    barrage_manager,
                 );
                 if release {
                     enemy.remove_shallow();
@@ -771,48 +781,71 @@ impl EnemyPool {
         release_shot
     }
 
-    pub fn draw(&self, tunnel: &Tunnel, bullets: &BulletPool) {
+    pub fn draw(&self, tunnel: &Tunnel, bullets: &BulletPool, screen: &Screen) {
         for enemy in &self.pool {
             let spec = match enemy.spec {
                 EnemySpec::Small(idx) => &self.small_ship_specs[idx],
                 EnemySpec::Medium(idx) => &self.medium_ship_specs[idx],
                 EnemySpec::Boss(idx) => &self.boss_ship_specs[idx],
             };
-            enemy.draw(spec, tunnel, bullets, &self.bit_shape);
+            enemy.draw(spec, tunnel, bullets, &self.bit_shape, screen);
         }
     }
 
-    pub fn draw_passed(&self, tunnel: &Tunnel, bullets: &BulletPool) {
+    pub fn draw_hitboxes(&self, tunnel: &Tunnel, screen: &Screen) {
+        for enemy in &self.pool {
+            let spec = match enemy.spec {
+                EnemySpec::Small(idx) => &self.small_ship_specs[idx],
+                EnemySpec::Medium(idx) => &self.medium_ship_specs[idx],
+                EnemySpec::Boss(idx) => &self.boss_ship_specs[idx],
+            };
+            hitbox::draw_box(
+                enemy.pos,
+                spec.shape().collision(),
+                tunnel,
+                screen.renderer(),
+            );
+        }
+    }
+
+    pub fn draw_passed(&self, tunnel: &Tunnel, bullets: &BulletPool, screen: &Screen) {
         for enemy in &self.passed_pool {
             let spec = match enemy.spec {
                 EnemySpec::Small(idx) => &self.small_ship_specs[idx],
                 EnemySpec::Medium(idx) => &self.medium_ship_specs[idx],
                 EnemySpec::Boss(idx) => &self.boss_ship_specs[idx],
             };
-            enemy.draw(spec, tunnel, bullets, &self.bit_shape);
+            enemy.draw(spec, tunnel, bullets, &self.bit_shape, screen);
         }
     }
 
     pub fn get_num(&self) -> usize {
         self.pool.get_num()
     }
+
+    pub fn get_capacity(&self) -> usize {
+        self.pool.get_capacity()
+    }
+
+    pub fn hash_state<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.pool.get_num());
+        self.rand.hash_state(state);
+    }
 }
 
 pub mod ship_spec {
     use std::ffi::OsStr;
-    use std::rc::Rc;
 
+    use crate::util::math;
     use crate::util::rand::Rand;
     use crate::util::vector::Vector;
 
-    use crate::tt::barrage::{Barrage, BarrageManager, BulletShapeType};
-    use crate::tt::screen::Screen;
+    use crate::tt::barrage::{Barrage, BarrageManager, BulletShapeRef, BulletShapeType};
     use crate::tt::shape::ship_shape::ShipShape;
-    use crate::tt::shape::{Drawable, ResizableDrawable};
     use crate::tt::ship;
     use crate::tt::tunnel::{InsideSliceCheck, SliceEdges, Tunnel};
 
-    #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+    #[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
     enum BitType {
         Round,
         Line,
@@ -822,6 +855,7 @@ pub mod ship_spec {
 
     const SPEED_CHANGE_RATIO: f32 = 0.2;
 
+    #[derive(Serialize, Deserialize)]
     pub struct ShipSpec {
         pub shape: ShipShape,
         damaged_shape: ShipShape,
@@ -845,7 +879,6 @@ pub mod ship_spec {
             rand: &mut Rand,
             level: f32,
             grade: u32,
-            screen: &Screen,
             barrage_manager: &mut BarrageManager,
         ) -> Self {
             let base_speed = 0.05 + rand.gen_f32(0.1);
@@ -866,8 +899,8 @@ pub mod ship_spec {
             let barrage =
                 ShipSpec::create_barrage(rand, brg_rank, 0, brg_interval as u32, barrage_manager);
             Self {
-                shape: ShipShape::new_small(false, screen, rs),
-                damaged_shape: ShipShape::new_small(true, screen, rs),
+                shape: ShipShape::new_small(false, rs),
+                damaged_shape: ShipShape::new_small(true, rs),
                 barrage,
                 shield: 1,
                 base_speed,
@@ -887,7 +920,6 @@ pub mod ship_spec {
         pub fn new_medium(
             rand: &mut Rand,
             level: f32,
-            screen: &Screen,
             barrage_manager: &mut BarrageManager,
         ) -> Self {
             let base_speed = 0.1 + rand.gen_f32(0.1);
@@ -912,8 +944,8 @@ pub mod ship_spec {
                 barrage_manager,
             );
             Self {
-                shape: ShipShape::new_medium(false, screen, rs),
-                damaged_shape: ShipShape::new_medium(true, screen, rs),
+                shape: ShipShape::new_medium(false, rs),
+                damaged_shape: ShipShape::new_medium(true, rs),
                 barrage,
                 shield: 10,
                 base_speed,
@@ -935,7 +967,6 @@ pub mod ship_spec {
             level: f32,
             speed: f32,
             medium_boss: bool,
-            screen: &Screen,
             barrage_manager: &mut BarrageManager,
         ) -> Self {
             let base_speed = 0.1 + rand.gen_f32(0.1);
@@ -985,8 +1016,8 @@ pub mod ship_spec {
                 None
             };
             Self {
-                shape: ShipShape::new_large(false, screen, rs),
-                damaged_shape: ShipShape::new_large(true, screen, rs),
+                shape: ShipShape::new_large(false, rs),
+                damaged_shape: ShipShape::new_large(true, rs),
                 barrage,
                 shield: 30,
                 base_speed,
@@ -1034,16 +1065,16 @@ pub mod ship_spec {
             long_range: bool,
             barrage_manager: &mut BarrageManager,
         ) -> Barrage {
-            let mut rank = f32::sqrt(level) / (8. - rand.gen_usize(3) as f32);
+            let mut rank = math::sqrt(level) / (8. - rand.gen_usize(3) as f32);
             if rank > 0.8 {
                 rank = rand.gen_f32(0.2) + 0.8;
             }
-            let mut speed_rank = f32::sqrt(rank) * (rand.gen_f32(0.2) + 0.8);
+            let mut speed_rank = math::sqrt(rank) * (rand.gen_f32(0.2) + 0.8);
             if speed_rank < 1. {
                 speed_rank = 1.;
             }
             if speed_rank > 2. {
-                speed_rank = f32::sqrt(speed_rank * 2.);
+                speed_rank = math::sqrt(speed_rank * 2.);
             }
             let mut morph_rank = level / (rank + 2.) / speed_rank;
             let mut morph_cnt = 0;
@@ -1051,16 +1082,13 @@ pub mod ship_spec {
                 morph_cnt += 1;
                 morph_rank /= 3.;
             }
-            let (bsr, dbsr) = barrage_manager.get_shape(shape_type);
-            let bsr = Rc::new(ResizableDrawable::new(bsr, size * 1.25)) as Rc<dyn Drawable>;
-            let dbsr = Rc::new(ResizableDrawable::new(dbsr, size * 1.25)) as Rc<dyn Drawable>;
-            let mut br = Barrage::new(&bsr, &dbsr);
+            let mut br = Barrage::new(BulletShapeRef::new(shape_type, size * 1.25));
             br.set_wait(pre_wait, post_wait);
             br.set_long_range(long_range);
             if let Some(base_dir) = base_dir {
                 let ps = barrage_manager.get_instance_list(base_dir);
                 let pi = rand.gen_usize(ps.len());
-                br.add_bml(&ps[pi].1, rank, true, speed_rank);
+                br.add_bml(ps[pi].1, rank, true, speed_rank);
             } else {
                 br.add_bml(
                     barrage_manager.get_instance(OsStr::new("basic"), OsStr::new("straight.xml")),
@@ -1077,7 +1105,7 @@ pub mod ship_spec {
                 while used_ps[pi] {
                     pi = (pi + psn - 1) % psn;
                 }
-                br.add_bml(&ps[pi].1, morph_rank, true, speed_rank);
+                br.add_bml(ps[pi].1, morph_rank, true, speed_rank);
                 used_ps[pi] = true;
             }
             br
@@ -1172,6 +1200,7 @@ pub mod ship_spec {
         }
     }
 
+    #[derive(Serialize, Deserialize)]
     pub struct BitSpec {
         pub bit_num: u32,
         bit_type: BitType,
@@ -1188,10 +1217,10 @@ pub mod ship_spec {
                     let d = od * idx as f32 + cnt as f32 * self.bit_md;
                     (
                         Vector::new_at(
-                            self.bit_distance * 2. * f32::sin(d),
-                            self.bit_distance * 2. * f32::cos(d) * 5.,
+                            self.bit_distance * 2. * math::sin(d),
+                            self.bit_distance * 2. * math::cos(d) * 5.,
                         ),
-                        std::f32::consts::PI - f32::sin(d) * 0.05,
+                        std::f32::consts::PI - math::sin(d) * 0.05,
                     )
                 }
                 BitType::Line => {
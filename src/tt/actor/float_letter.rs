@@ -1,7 +1,5 @@
 use std::ops::{Index, IndexMut};
 
-use crate::gl;
-
 use crate::tt::actor::pool::{Pool, PoolActorRef};
 use crate::tt::letter::{self, Letter};
 use crate::tt::screen::Screen;
@@ -10,7 +8,7 @@ use crate::tt::GeneralParams;
 use crate::util::rand::Rand;
 use crate::util::vector::{Vector, Vector3};
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct FloatLetter {
     pos: Vector3,
     mx: f32,
@@ -44,13 +42,10 @@ impl FloatLetter {
     }
 
     fn draw(&self, screen: &Screen, letter: &Letter, tunnel: &Tunnel) {
-        unsafe {
-            gl::PushMatrix();
-        }
+        let renderer = screen.renderer();
+        renderer.push_matrix();
         let sp = tunnel.get_pos_v3(self.pos);
-        unsafe {
-            gl::Translatef(0., 0., sp.z);
-        }
+        renderer.translate(0., 0., sp.z);
         screen.set_alpha_color((1., 1., 1., 1.));
         letter.draw_string_full(
             &self.msg,
@@ -73,12 +68,11 @@ impl FloatLetter {
             false,
             self.d * 180. / std::f32::consts::PI,
         );
-        unsafe {
-            gl::PopMatrix();
-        }
+        renderer.pop_matrix();
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct FloatLetterPool {
     pool: Pool<FloatLetter>,
     rand: Rand,
@@ -101,6 +95,14 @@ impl FloatLetterPool {
         fl.set(msg, p, s, c, &mut self.rand);
     }
 
+    pub fn get_num(&self) -> usize {
+        self.pool.get_num()
+    }
+
+    pub fn get_capacity(&self) -> usize {
+        self.pool.get_capacity()
+    }
+
     pub fn clear(&mut self) {
         self.pool.clear();
     }
@@ -1,18 +1,18 @@
 use std::ops::{Index, IndexMut};
 
-use crate::gl;
-
 use crate::tt::actor::pool::{Pool, PoolActorRef};
 use crate::tt::screen::Screen;
 use crate::tt::tunnel::{InCourseSliceCheck, Tunnel};
 use crate::util::color::Color;
+use crate::util::math;
 use crate::util::rand::Rand;
+use crate::util::renderer::Primitive;
 use crate::util::vector::{Vector, Vector3};
 
 const GRAVITY: f32 = 0.02;
 const SIZE: f32 = 0.3;
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct Particle {
     pos: Vector3,
     vel: Vector3,
@@ -27,7 +27,7 @@ pub struct Particle {
     spec: ParticleSpec,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum ParticleSpec {
     Spark,
     Star,
@@ -64,7 +64,7 @@ impl Particle {
     ) {
         self.pos = Vector3::new_at(p.x, p.y, z);
         let sb = rand.gen_f32(0.8) + 0.4;
-        self.vel = Vector3::new_at(f32::sin(d) * speed * sb, f32::cos(d) * speed * sb, mz);
+        self.vel = Vector3::new_at(math::sin(d) * speed * sb, math::cos(d) * speed * sb, mz);
         self.color = color;
         self.cnt = c + rand.gen_usize((c / 2) as usize) as i32;
         self.lum_alp = 0.8 + rand.gen_f32(0.2);
@@ -168,79 +168,62 @@ impl Particle {
     }
 
     fn draw_spark(&self, screen: &Screen) {
-        unsafe {
-            gl::Begin(gl::GL_TRIANGLE_FAN);
-        }
+        let renderer = screen.renderer();
+        renderer.begin(Primitive::TriangleFan);
         screen.set_alpha_color(self.color.with_alpha(0.5));
-        self.psp.gl_vertex();
+        self.psp.vertex(renderer);
         screen.set_alpha_color(self.color.with_alpha(0.));
-        unsafe {
-            gl::Vertex3f(self.sp.x - SIZE, self.sp.y - SIZE, self.sp.z);
-            gl::Vertex3f(self.sp.x + SIZE, self.sp.y - SIZE, self.sp.z);
-            gl::Vertex3f(self.sp.x + SIZE, self.sp.y + SIZE, self.sp.z);
-            gl::Vertex3f(self.sp.x - SIZE, self.sp.y + SIZE, self.sp.z);
-            gl::Vertex3f(self.sp.x - SIZE, self.sp.y - SIZE, self.sp.z);
-            gl::End();
-        }
+        renderer.vertex(self.sp.x - SIZE, self.sp.y - SIZE, self.sp.z);
+        renderer.vertex(self.sp.x + SIZE, self.sp.y - SIZE, self.sp.z);
+        renderer.vertex(self.sp.x + SIZE, self.sp.y + SIZE, self.sp.z);
+        renderer.vertex(self.sp.x - SIZE, self.sp.y + SIZE, self.sp.z);
+        renderer.vertex(self.sp.x - SIZE, self.sp.y - SIZE, self.sp.z);
+        renderer.end();
         if self.in_course {
-            unsafe {
-                gl::Begin(gl::GL_TRIANGLE_FAN);
-            }
+            renderer.begin(Primitive::TriangleFan);
             screen.set_alpha_color(self.color.with_alpha(0.2));
-            self.rpsp.gl_vertex();
+            self.rpsp.vertex(renderer);
             screen.set_alpha_color(self.color.with_alpha(0.));
-            unsafe {
-                gl::Vertex3f(self.rsp.x - SIZE, self.rsp.y - SIZE, self.sp.z);
-                gl::Vertex3f(self.rsp.x + SIZE, self.rsp.y - SIZE, self.sp.z);
-                gl::Vertex3f(self.rsp.x + SIZE, self.rsp.y + SIZE, self.sp.z);
-                gl::Vertex3f(self.rsp.x - SIZE, self.rsp.y + SIZE, self.sp.z);
-                gl::Vertex3f(self.rsp.x - SIZE, self.rsp.y - SIZE, self.sp.z);
-                gl::End();
-            }
+            renderer.vertex(self.rsp.x - SIZE, self.rsp.y - SIZE, self.sp.z);
+            renderer.vertex(self.rsp.x + SIZE, self.rsp.y - SIZE, self.sp.z);
+            renderer.vertex(self.rsp.x + SIZE, self.rsp.y + SIZE, self.sp.z);
+            renderer.vertex(self.rsp.x - SIZE, self.rsp.y + SIZE, self.sp.z);
+            renderer.vertex(self.rsp.x - SIZE, self.rsp.y - SIZE, self.sp.z);
+            renderer.end();
         }
     }
 
     fn draw_star(&self, screen: &Screen) {
-        unsafe {
-            gl::Begin(gl::GL_LINES);
-        }
+        let renderer = screen.renderer();
+        renderer.begin(Primitive::Lines);
         screen.set_alpha_color(self.color.with_alpha(1.));
-        self.psp.gl_vertex();
+        self.psp.vertex(renderer);
         screen.set_alpha_color(self.color.with_alpha(0.2));
-        self.sp.gl_vertex();
-        unsafe {
-            gl::End();
-        }
+        self.sp.vertex(renderer);
+        renderer.end();
     }
 
     fn draw_fragment(&self, d1: f32, d2: f32, width: f32, height: f32, screen: &Screen) {
-        unsafe {
-            gl::PushMatrix();
-        }
-        self.sp.gl_translate();
-        unsafe {
-            gl::Rotatef(d1, 0., 0., 1.);
-            gl::Rotatef(d2, 0., 1., 0.);
-            gl::Begin(gl::GL_LINE_LOOP);
-        }
+        let renderer = screen.renderer();
+        renderer.push_matrix();
+        self.sp.translate(renderer);
+        renderer.rotate(d1, 0., 0., 1.);
+        renderer.rotate(d2, 0., 1., 0.);
+        renderer.begin(Primitive::LineLoop);
         screen.set_alpha_color(self.color.with_alpha(0.5));
-        unsafe {
-            gl::Vertex3f(width, 0., height);
-            gl::Vertex3f(-width, 0., height);
-            gl::Vertex3f(-width, 0., -height);
-            gl::Vertex3f(width, 0., -height);
-            gl::End();
-            gl::Begin(gl::GL_TRIANGLE_FAN);
-        }
+        renderer.vertex(width, 0., height);
+        renderer.vertex(-width, 0., height);
+        renderer.vertex(-width, 0., -height);
+        renderer.vertex(width, 0., -height);
+        renderer.end();
+        renderer.begin(Primitive::TriangleFan);
         screen.set_alpha_color(self.color.with_alpha(0.2));
-        unsafe {
-            gl::Vertex3f(width, 0., height);
-            gl::Vertex3f(-width, 0., height);
-            gl::Vertex3f(-width, 0., -height);
-            gl::Vertex3f(width, 0., -height);
-            gl::End();
-            gl::PopMatrix();
-        }
+        renderer.vertex(width, 0., height);
+        renderer.vertex(-width, 0., height);
+        renderer.vertex(-width, 0., -height);
+        renderer.vertex(width, 0., -height);
+        renderer.end();
+        renderer.pop_matrix();
     }
 
     fn draw_luminous(&self, screen: &Screen) {
@@ -253,25 +236,21 @@ impl Particle {
         } {
             return;
         }
-        unsafe {
-            gl::Begin(gl::GL_TRIANGLE_FAN);
-        }
+        let renderer = screen.renderer();
+        renderer.begin(Primitive::TriangleFan);
         screen.set_alpha_color(self.color.with_alpha(self.lum_alp * 0.6));
-        unsafe {
-            gl::Vertex3f(self.psp.x, self.psp.y, self.psp.z);
-        }
+        renderer.vertex(self.psp.x, self.psp.y, self.psp.z);
         screen.set_alpha_color(self.color.with_alpha(0.));
-        unsafe {
-            gl::Vertex3f(self.sp.x - SIZE, self.sp.y - SIZE, self.sp.z);
-            gl::Vertex3f(self.sp.x + SIZE, self.sp.y - SIZE, self.sp.z);
-            gl::Vertex3f(self.sp.x + SIZE, self.sp.y + SIZE, self.sp.z);
-            gl::Vertex3f(self.sp.x - SIZE, self.sp.y + SIZE, self.sp.z);
-            gl::Vertex3f(self.sp.x - SIZE, self.sp.y - SIZE, self.sp.z);
-            gl::End();
-        }
+        renderer.vertex(self.sp.x - SIZE, self.sp.y - SIZE, self.sp.z);
+        renderer.vertex(self.sp.x + SIZE, self.sp.y - SIZE, self.sp.z);
+        renderer.vertex(self.sp.x + SIZE, self.sp.y + SIZE, self.sp.z);
+        renderer.vertex(self.sp.x - SIZE, self.sp.y + SIZE, self.sp.z);
+        renderer.vertex(self.sp.x - SIZE, self.sp.y - SIZE, self.sp.z);
+        renderer.end();
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ParticlePool {
     pool: Pool<Particle>,
     rand: Rand,
@@ -310,6 +289,14 @@ impl ParticlePool {
         op(particle, &mut self.rand);
     }
 
+    pub fn get_num(&self) -> usize {
+        self.pool.get_num()
+    }
+
+    pub fn get_capacity(&self) -> usize {
+        self.pool.get_capacity()
+    }
+
     pub fn clear(&mut self) {
         self.pool.clear();
     }
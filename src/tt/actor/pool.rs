@@ -1,21 +1,24 @@
 use std::ops::{Index, IndexMut};
 
+#[derive(Serialize, Deserialize)]
 pub struct PoolActor<T> {
     actor: T,
     state: ActorState,
 }
 
+#[derive(Serialize, Deserialize)]
 enum ActorState {
     NotActing,
     Acting { generation: usize },
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PoolActorRef {
     idx: usize,
     generation: usize,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Pool<T> {
     actors: Box<[PoolActor<T>]>,
     idx: usize,
@@ -111,6 +114,10 @@ impl<T> Pool<T> {
         self.num
     }
 
+    pub fn get_capacity(&self) -> usize {
+        self.actors.len()
+    }
+
     // This is inspired by split_at_mut, enjoy ;-).
     pub fn split(&mut self) -> (PoolReleaseArea<T>, PoolGetInstanceArea<T>) {
         let generation = self.generation;
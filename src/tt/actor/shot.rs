@@ -1,20 +1,20 @@
 use std::ops::{Index, IndexMut};
 use std::rc::Rc;
 
-use crate::gl;
-
 use crate::tt::actor::bullet::BulletPool;
 use crate::tt::actor::enemy::EnemyPool;
 use crate::tt::actor::float_letter::FloatLetterPool;
 use crate::tt::actor::particle::{ParticlePool, ParticleSpec};
 use crate::tt::actor::pool::{Pool, PoolActorRef};
+use crate::tt::hitbox;
 use crate::tt::screen::Screen;
 use crate::tt::shape::shot_shape::ShotShape;
-use crate::tt::shape::{Drawable, ResizableDrawable};
+use crate::tt::shape::{Collidable, Drawable, ResizableDrawable};
 use crate::tt::ship::{self, Ship};
 use crate::tt::sound::SoundManager;
 use crate::tt::tunnel::Tunnel;
 use crate::tt::GeneralParams;
+use crate::util::math;
 use crate::util::rand::Rand;
 use crate::util::vector::Vector;
 
@@ -32,7 +32,7 @@ const CHARGE_RELEASE_RATIO: f32 = 0.25;
 
 const MAX_MULTIPLIER: u32 = 100;
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct Shot {
     pub pos: Vector,
     charge_cnt: u32,
@@ -104,6 +104,11 @@ impl Shot {
         self.range = RANGE_MIN + self.charge_cnt as f32 * RANGE_RATIO;
         self.trg_size = SIZE_MIN + self.charge_cnt as f32 * SIZE_RATIO;
         sound_manager.play_se("charge_shot.wav");
+        record_event!(ShotFired {
+            x: self.pos.x,
+            y: self.pos.y,
+            charge: true,
+        });
         false
     }
 
@@ -128,8 +133,8 @@ impl Shot {
             }
             self.charge_se_cnt += 1;
         } else {
-            self.pos.x += f32::sin(self.deg) * SPEED;
-            self.pos.y += f32::cos(self.deg) * SPEED;
+            self.pos.x += math::sin(self.deg) * SPEED;
+            self.pos.y += math::cos(self.deg) * SPEED;
             self.range -= SPEED;
             if self.range <= 0. {
                 release = true;
@@ -212,20 +217,24 @@ impl Shot {
         }
     }
 
-    fn draw(&self, tunnel: &Tunnel) {
+    fn draw(&self, tunnel: &Tunnel, screen: &Screen) {
+        let renderer = screen.renderer();
         let sp = tunnel.get_pos_v(self.pos);
-        unsafe {
-            gl::PushMatrix();
-        }
-        sp.gl_translate();
-        unsafe {
-            gl::Rotatef(self.deg * 180. / std::f32::consts::PI, 0., 1., 10.);
-            gl::Rotatef(self.cnt as f32 * 7., 0., 0., 1.);
-        }
-        self.shape.as_ref().unwrap().draw();
-        unsafe {
-            gl::PopMatrix();
-        }
+        renderer.push_matrix();
+        sp.translate(renderer);
+        renderer.rotate(self.deg * 180. / std::f32::consts::PI, 0., 1., 10.);
+        renderer.rotate(self.cnt as f32 * 7., 0., 0., 1.);
+        self.shape.as_ref().unwrap().draw(screen);
+        renderer.pop_matrix();
+    }
+
+    fn draw_hitbox(&self, tunnel: &Tunnel, screen: &Screen) {
+        hitbox::draw_box(
+            self.pos,
+            self.shape.as_ref().unwrap().collision(),
+            tunnel,
+            screen.renderer(),
+        );
     }
 
     pub fn damage(&self) -> i32 {
@@ -233,6 +242,7 @@ impl Shot {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ShotPool {
     pool: Pool<Shot>,
     shot_shape: Rc<ShotShape>,
@@ -241,11 +251,11 @@ pub struct ShotPool {
 }
 
 impl ShotPool {
-    pub fn new(n: usize, screen: &Screen) -> Self {
+    pub fn new(n: usize) -> Self {
         ShotPool {
             pool: Pool::new(n),
-            shot_shape: Rc::new(ShotShape::new(false, screen)),
-            charge_shot_shape: Rc::new(ShotShape::new(true, screen)),
+            shot_shape: Rc::new(ShotShape::new(false)),
+            charge_shot_shape: Rc::new(ShotShape::new(true)),
             rand: Rand::new(Rand::rand_seed()),
         }
     }
@@ -277,6 +287,14 @@ impl ShotPool {
         self.pool.release(shot_ref);
     }
 
+    pub fn get_num(&self) -> usize {
+        self.pool.get_num()
+    }
+
+    pub fn get_capacity(&self) -> usize {
+        self.pool.get_capacity()
+    }
+
     pub fn clear(&mut self) {
         self.pool.clear();
     }
@@ -308,9 +326,15 @@ impl ShotPool {
         }
     }
 
-    pub fn draw(&self, tunnel: &Tunnel) {
+    pub fn draw(&self, tunnel: &Tunnel, screen: &Screen) {
+        for shot in &self.pool {
+            shot.draw(tunnel, screen);
+        }
+    }
+
+    pub fn draw_hitboxes(&self, tunnel: &Tunnel, screen: &Screen) {
         for shot in &self.pool {
-            shot.draw(tunnel);
+            shot.draw_hitbox(tunnel, screen);
         }
     }
 }
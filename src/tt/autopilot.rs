@@ -0,0 +1,286 @@
+// A pilot playing the game by itself: it keeps the ship in the middle of the course ahead, gets
+// out of the way of the bullets about to reach it, fires and charges shots in turn, and goes on
+// after game over. It only reads the game, so that the same game always plays the same way.
+
+use piston::input::ButtonArgs;
+use rle_vec::RleVec;
+
+use crate::tt::actor::bullet::BulletPool;
+use crate::tt::errors::GameError;
+use crate::tt::pad::{Pad, PadButtons, PadDirection, PadState, ReplayPosition, VirtualPad};
+use crate::tt::replay::ReplayOutcome;
+use crate::tt::ship::Ship;
+use crate::tt::simulation::Simulation;
+use crate::tt::state::ReplayData;
+use crate::tt::tunnel::{InCourseSliceCheck, Tunnel};
+use crate::util::vector::Vector;
+
+// How far ahead the course is followed.
+const LOOKAHEAD_DEPTH: f32 = 6.;
+// The bullets reaching the depth of the ship within this many ticks, this close to it, are
+// dodged.
+const DANGER_TICKS: f32 = 40.;
+const DANGER_ANGLE: f32 = 0.3;
+const HIT_DEPTH: f32 = 0.5;
+// Steering for less than this angle would only make the ship wobble.
+const STEER_THRESHOLD: f32 = 0.02;
+// Out of each cycle, the shot is charged for the first ticks then fired for the rest.
+const FIRE_CYCLE: u32 = 180;
+const CHARGE_TICKS: u32 = 45;
+const GAME_OVER_PRESS_INTERVAL: u32 = 30;
+
+pub struct Autopilot {
+    cnt: u32,
+}
+
+// Plays in place of the player, who keeps the pause and escape of the wrapped pad, and the whole
+// of it out of the games.
+pub struct AutopilotPad<P: Pad> {
+    pad: P,
+    autopilot: Autopilot,
+    virtual_pad: VirtualPad,
+    // From the start of a game until its record is taken when leaving it.
+    in_game: bool,
+}
+
+#[allow(clippy::new_without_default)]
+impl Autopilot {
+    pub fn new() -> Self {
+        Autopilot { cnt: 0 }
+    }
+
+    pub fn think(&mut self, ship: &Ship, tunnel: &Tunnel, bullets: &BulletPool) -> PadState {
+        self.cnt = self.cnt.wrapping_add(1);
+        if ship.is_game_over() {
+            // Pressed then released, A leaves the game over screen.
+            let buttons = if (self.cnt / GAME_OVER_PRESS_INTERVAL) % 2 == 0 {
+                PadButtons::A
+            } else {
+                PadButtons::NONE
+            };
+            return PadState {
+                buttons,
+                ..PadState::default()
+            };
+        }
+        let pos = ship.rel_pos();
+        let mut dodge = dodge(pos, bullets);
+        if dodge != 0. {
+            dodge = dodge.signum() * DANGER_ANGLE;
+            // Rather the other way than out of the course.
+            let dodge_pos = Vector::new_at(normalize_angle(pos.x + dodge), pos.y);
+            if let InCourseSliceCheck::NotInCourse(_) = tunnel.check_in_course(dodge_pos) {
+                dodge = -dodge;
+            }
+        }
+        let target = if dodge != 0. {
+            pos.x + dodge
+        } else {
+            course_center(pos, tunnel)
+        };
+        let mut direction = PadDirection::NONE;
+        // Banking to the right lowers the angle of the ship.
+        let steer = angle_diff(target, pos.x);
+        if steer < -STEER_THRESHOLD {
+            direction |= PadDirection::RIGHT;
+        } else if steer > STEER_THRESHOLD {
+            direction |= PadDirection::LEFT;
+        }
+        if dodge == 0. {
+            direction |= PadDirection::UP;
+        } else {
+            direction |= PadDirection::DOWN;
+        }
+        let buttons = if self.cnt % FIRE_CYCLE < CHARGE_TICKS {
+            PadButtons::B
+        } else {
+            PadButtons::A
+        };
+        PadState {
+            direction,
+            buttons,
+            axes: None,
+        }
+    }
+}
+
+// From b to a, in [-PI, PI).
+fn angle_diff(a: f32, b: f32) -> f32 {
+    normalize_angle(a - b + std::f32::consts::PI) - std::f32::consts::PI
+}
+
+// In [0, 2 PI).
+fn normalize_angle(d: f32) -> f32 {
+    let d = d % (std::f32::consts::PI * 2.);
+    if d < 0. {
+        d + std::f32::consts::PI * 2.
+    } else {
+        d
+    }
+}
+
+fn course_center(pos: Vector, tunnel: &Tunnel) -> f32 {
+    let slice = tunnel.get_slice(pos.y + LOOKAHEAD_DEPTH);
+    if slice.is_nearly_round() {
+        return pos.x;
+    }
+    let edges = slice.get_edges();
+    let mut width = edges.right - edges.left;
+    if width < 0. {
+        width += std::f32::consts::PI * 2.;
+    }
+    edges.left + width / 2.
+}
+
+// Which way to go, positive for a higher angle, to get out of the way of the bullets.
+fn dodge(pos: Vector, bullets: &BulletPool) -> f32 {
+    let mut dodge = 0.;
+    for (bullet_pos, bullet_ppos) in bullets.hit_paths() {
+        let dy = bullet_pos.y - pos.y;
+        let vy = bullet_pos.y - bullet_ppos.y;
+        let ticks = if dy.abs() < HIT_DEPTH {
+            0.
+        } else if vy * dy < 0. {
+            -dy / vy
+        } else {
+            continue;
+        };
+        if ticks > DANGER_TICKS {
+            continue;
+        }
+        // Where the bullet will be when it reaches the depth of the ship.
+        let x = bullet_pos.x + angle_diff(bullet_pos.x, bullet_ppos.x) * ticks;
+        let dx = angle_diff(pos.x, x);
+        if dx.abs() < DANGER_ANGLE {
+            dodge += dx.signum() * (1. - ticks / DANGER_TICKS);
+        }
+    }
+    dodge
+}
+
+// Plays a game without any window nor sound, until game over or the given number of frames.
+pub fn play(
+    grade: u32,
+    seed: u64,
+    max_frames: Option<u32>,
+) -> Result<(ReplayOutcome, ReplayData), GameError> {
+    let mut simulation = Simulation::new()?;
+    simulation.start(grade, 1., seed);
+    let mut autopilot = Autopilot::new();
+    while !simulation.is_game_over() && max_frames.map_or(true, |max| simulation.frame() < max) {
+        let world = simulation.world();
+        let pad_state = autopilot.think(&world.ship, &world.tunnel, &world.bullets);
+        simulation.step(pad_state);
+    }
+
+    let outcome = ReplayOutcome {
+        score: simulation.score(),
+        level: simulation.level() as u32,
+        frame_count: simulation.frame(),
+        first_desync: None,
+        state_hash: simulation.state_hash(),
+    };
+    Ok((outcome, simulation.replay_data()))
+}
+
+impl<P: Pad> AutopilotPad<P> {
+    pub fn new(pad: P) -> Self {
+        AutopilotPad {
+            pad,
+            autopilot: Autopilot::new(),
+            virtual_pad: VirtualPad::new(),
+            in_game: false,
+        }
+    }
+}
+
+impl<P: Pad> Pad for AutopilotPad<P> {
+    fn start_record(&mut self) {
+        self.in_game = true;
+        self.virtual_pad.start_record();
+    }
+
+    fn start_replay(&mut self, record: RleVec<PadState>) {
+        self.in_game = false;
+        self.virtual_pad.start_replay(record);
+    }
+
+    fn handle_button_event(&mut self, button_args: &ButtonArgs) {
+        self.pad.handle_button_event(button_args);
+    }
+
+    fn handle_focus_event(&mut self, focus: bool) {
+        self.pad.handle_focus_event(focus);
+    }
+
+    fn get_state(&self) -> PadState {
+        if self.in_game {
+            self.virtual_pad.get_state()
+        } else {
+            self.pad.get_state()
+        }
+    }
+
+    fn record_state(&mut self) -> PadState {
+        self.virtual_pad.record_state()
+    }
+
+    fn replay_state(&mut self) -> Option<PadState> {
+        self.virtual_pad.replay_state()
+    }
+
+    fn pause_pressed(&self) -> bool {
+        self.pad.pause_pressed()
+    }
+
+    fn esc_pressed(&self) -> bool {
+        self.pad.esc_pressed()
+    }
+
+    fn get_record(&mut self) -> RleVec<PadState> {
+        self.in_game = false;
+        self.virtual_pad.get_record()
+    }
+
+    fn replay_position(&self) -> ReplayPosition {
+        self.virtual_pad.replay_position()
+    }
+
+    fn set_replay_position(&mut self, position: ReplayPosition) {
+        self.virtual_pad.set_replay_position(position);
+    }
+
+    fn poll_devices(&mut self) {
+        self.pad.poll_devices();
+    }
+
+    fn observe(&mut self, ship: &Ship, tunnel: &Tunnel, bullets: &BulletPool) {
+        let state = self.autopilot.think(ship, tunnel, bullets);
+        self.virtual_pad.set_state(state);
+    }
+}
+
+#[test]
+fn autopilot_should_play_the_same_game_on_the_same_seed() {
+    let (outcome, _) = play(0, 42, Some(1800)).unwrap();
+    assert_eq!(outcome, play(0, 42, Some(1800)).unwrap().0);
+    assert!(outcome.score > 0);
+}
+
+#[test]
+fn autopilot_pad_should_leave_the_wrapped_pad_out_of_the_games() {
+    let mut wrapped = VirtualPad::new();
+    wrapped.set_state(PadState {
+        buttons: PadButtons::B,
+        ..PadState::default()
+    });
+    let mut pad = AutopilotPad::new(wrapped);
+    pad.start_record();
+    pad.virtual_pad.set_state(PadState {
+        buttons: PadButtons::A,
+        ..PadState::default()
+    });
+    assert_eq!(pad.get_state().buttons, PadButtons::A);
+    pad.get_record();
+    assert_eq!(pad.get_state().buttons, PadButtons::B);
+}
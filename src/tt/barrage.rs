@@ -14,10 +14,10 @@ use crate::tt::shape::bullet_shape::BulletShape;
 use crate::tt::shape::Drawable;
 use crate::util::vector::Vector;
 
+#[derive(Serialize, Deserialize)]
 pub struct Barrage {
     bml_params: Rc<Vec<BMLParam>>,
-    shape: Rc<dyn Drawable>,
-    disap_shape: Rc<dyn Drawable>,
+    shape: BulletShapeRef,
     long_range: bool,
     prev_wait: u32,
     post_wait: u32,
@@ -25,11 +25,10 @@ pub struct Barrage {
 }
 
 impl Barrage {
-    pub fn new(shape: &Rc<dyn Drawable>, disap_shape: &Rc<dyn Drawable>) -> Self {
+    pub fn new(shape: BulletShapeRef) -> Self {
         Barrage {
             bml_params: Rc::new(Vec::new()),
-            shape: shape.clone(),
-            disap_shape: disap_shape.clone(),
+            shape,
             long_range: false,
             prev_wait: 0,
             post_wait: 0,
@@ -50,16 +49,20 @@ impl Barrage {
         self.no_x_reverse = true;
     }
 
-    pub fn add_bml(&mut self, bml: &Rc<BulletML>, r: f32, re: bool, s: f32) {
+    pub fn add_bml(&mut self, bml: BulletMLId, r: f32, re: bool, s: f32) {
         Rc::get_mut(&mut self.bml_params).unwrap().push(BMLParam {
-            bml: bml.clone(),
+            bml,
             rank: r,
             root_rank_effect: if re { 1. } else { 0. },
             speed: s,
         });
     }
 
-    pub fn add_top_bullet(&mut self, bullets: &mut BulletPool) -> Option<PoolActorRef> {
+    pub fn add_top_bullet(
+        &mut self,
+        bullets: &mut BulletPool,
+        barrage_manager: &BarrageManager,
+    ) -> Option<PoolActorRef> {
         let x_reverse = if self.no_x_reverse {
             1.
         } else {
@@ -70,60 +73,87 @@ impl Barrage {
             Vector::default(),
             std::f32::consts::PI,
             0.,
-            &self.shape,
-            &self.disap_shape,
+            self.shape,
             x_reverse,
             1.,
             self.long_range,
             self.prev_wait,
             self.post_wait,
+            barrage_manager,
         )
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum BulletShapeType {
     Triangle,
     Square,
     Bar,
 }
 
+// The shapes and the bullet patterns are shared resources owned by the manager, bullets only keep
+// handles on them so that their state can be saved.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct BulletShapeRef {
+    shape_type: BulletShapeType,
+    size: f32,
+}
+
+impl BulletShapeRef {
+    pub fn new(shape_type: BulletShapeType, size: f32) -> Self {
+        BulletShapeRef { shape_type, size }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct BulletMLId(usize);
+
 pub struct BarrageManager {
-    bmls: BTreeMap<OsString, BTreeMap<OsString, Rc<BulletML>>>,
-    square_bullet_shapes: (Rc<BulletShape>, Rc<BulletShape>),
-    triangle_bullet_shapes: (Rc<BulletShape>, Rc<BulletShape>),
-    bar_bullet_shapes: (Rc<BulletShape>, Rc<BulletShape>),
+    bmls: BTreeMap<OsString, BTreeMap<OsString, BulletMLId>>,
+    instances: Vec<BulletML>,
+    square_bullet_shapes: (BulletShape, BulletShape),
+    triangle_bullet_shapes: (BulletShape, BulletShape),
+    bar_bullet_shapes: (BulletShape, BulletShape),
 }
 
 const BARRAGE_DIR_NAME: &str = "barrage";
 
 impl BarrageManager {
-    pub fn load(screen: &Screen) -> Result<Self, GameError> {
-        Self::new(Self::load_instances()?, screen)
+    pub fn load() -> Result<Self, GameError> {
+        Ok(Self::new(Self::load_instances()?))
     }
 
-    fn new(
-        bmls: BTreeMap<OsString, BTreeMap<OsString, Rc<BulletML>>>,
-        screen: &Screen,
-    ) -> Result<Self, GameError> {
-        Ok(BarrageManager {
+    fn new(bmls: BTreeMap<OsString, BTreeMap<OsString, BulletML>>) -> Self {
+        let mut instances = Vec::new();
+        let bmls = bmls
+            .into_iter()
+            .map(|(dir_name, files)| {
+                let files = files
+                    .into_iter()
+                    .map(|(file_name, bml)| {
+                        instances.push(bml);
+                        (file_name, BulletMLId(instances.len() - 1))
+                    })
+                    .collect();
+                (dir_name, files)
+            })
+            .collect();
+        BarrageManager {
             bmls,
+            instances,
             square_bullet_shapes: (
-                Rc::new(BulletShape::new_square(false, screen)),
-                Rc::new(BulletShape::new_square(true, screen)),
+                BulletShape::new_square(false),
+                BulletShape::new_square(true),
             ),
             triangle_bullet_shapes: (
-                Rc::new(BulletShape::new_triangle(false, screen)),
-                Rc::new(BulletShape::new_triangle(true, screen)),
-            ),
-            bar_bullet_shapes: (
-                Rc::new(BulletShape::new_bar(false, screen)),
-                Rc::new(BulletShape::new_bar(true, screen)),
+                BulletShape::new_triangle(false),
+                BulletShape::new_triangle(true),
             ),
-        })
+            bar_bullet_shapes: (BulletShape::new_bar(false), BulletShape::new_bar(true)),
+        }
     }
 
-    fn load_instances() -> Result<BTreeMap<OsString, BTreeMap<OsString, Rc<BulletML>>>, GameError> {
+    fn load_instances() -> Result<BTreeMap<OsString, BTreeMap<OsString, BulletML>>, GameError> {
         let mut bmls = BTreeMap::new();
         let dirs = fs::read_dir(BARRAGE_DIR_NAME)
             .map_err(Box::from)
@@ -157,7 +187,7 @@ impl BarrageManager {
                                 .or_insert_with(BTreeMap::new);
                             entry.insert(
                                 file_name.file_name().to_os_string(),
-                                Rc::new(BarrageManager::load_instance(&file_name.path())?),
+                                BarrageManager::load_instance(&file_name.path())?,
                             );
                         }
                     }
@@ -168,20 +198,23 @@ impl BarrageManager {
     }
 
     pub fn load_instance(path: &PathBuf) -> Result<BulletML, bulletml::errors::ParseError> {
-        // With fasteval capacity set to 19, it crashes. This is covered by a test below.
-        BulletMLParser::with_capacities(0, 20).parse_file(path.as_path())
+        BulletMLParser::new().parse_file(path.as_path())
     }
 
-    pub fn get_instance(&self, dir_name: &OsStr, file_name: &OsStr) -> &Rc<BulletML> {
-        &self.bmls[&dir_name.to_os_string()][&file_name.to_os_string()]
+    pub fn get_instance(&self, dir_name: &OsStr, file_name: &OsStr) -> BulletMLId {
+        self.bmls[&dir_name.to_os_string()][&file_name.to_os_string()]
     }
 
-    pub fn get_instance_list(&self, dir_name: &OsStr) -> Vec<(&OsString, &Rc<BulletML>)> {
+    pub fn get_instance_list(&self, dir_name: &OsStr) -> Vec<(&OsString, BulletMLId)> {
         let dir_entry = &self.bmls[&dir_name.to_os_string()];
-        dir_entry.iter().collect()
+        dir_entry.iter().map(|(name, id)| (name, *id)).collect()
+    }
+
+    pub fn bml(&self, id: BulletMLId) -> &BulletML {
+        &self.instances[id.0]
     }
 
-    pub fn get_shape(&self, shape_type: BulletShapeType) -> (&Rc<BulletShape>, &Rc<BulletShape>) {
+    fn get_shape(&self, shape_type: BulletShapeType) -> (&BulletShape, &BulletShape) {
         match shape_type {
             BulletShapeType::Square => (&self.square_bullet_shapes.0, &self.square_bullet_shapes.1),
             BulletShapeType::Triangle => (
@@ -191,6 +224,16 @@ impl BarrageManager {
             BulletShapeType::Bar => (&self.bar_bullet_shapes.0, &self.bar_bullet_shapes.1),
         }
     }
+
+    pub fn draw_shape(&self, shape: BulletShapeRef, disappearing: bool, screen: &Screen) {
+        let (bs, dbs) = self.get_shape(shape.shape_type);
+        screen.renderer().scale(shape.size, shape.size, shape.size);
+        if disappearing {
+            dbs.draw(screen);
+        } else {
+            bs.draw(screen);
+        }
+    }
 }
 
 #[test]
@@ -0,0 +1,189 @@
+use piston::input::keyboard::Key;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    A,
+    B,
+    Pause,
+    Esc,
+}
+
+pub const ACTION_NUM: usize = 8;
+
+pub const ACTIONS: [Action; ACTION_NUM] = [
+    Action::Up,
+    Action::Down,
+    Action::Left,
+    Action::Right,
+    Action::A,
+    Action::B,
+    Action::Pause,
+    Action::Esc,
+];
+
+impl Action {
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Up => "UP",
+            Action::Down => "DOWN",
+            Action::Left => "LEFT",
+            Action::Right => "RIGHT",
+            Action::A => "BUTTON A",
+            Action::B => "BUTTON B",
+            Action::Pause => "PAUSE",
+            Action::Esc => "ESCAPE",
+        }
+    }
+}
+
+// A button of the pad: named after the SDL game controller layout when the pad is known to the
+// mapping database, its raw index otherwise.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Debug)]
+pub enum PadButton {
+    Controller(String),
+    Joystick(u32),
+}
+
+// The keys and pad buttons of each action, indexed by action. The sticks always give the
+// directions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bindings {
+    keys: [Vec<Key>; ACTION_NUM],
+    controller_buttons: [Vec<String>; ACTION_NUM],
+    joystick_buttons: [Vec<u32>; ACTION_NUM],
+}
+
+impl Bindings {
+    pub fn keys(&self, action: Action) -> &[Key] {
+        &self.keys[action as usize]
+    }
+
+    pub fn controller_buttons(&self, action: Action) -> &[String] {
+        &self.controller_buttons[action as usize]
+    }
+
+    pub fn joystick_buttons(&self, action: Action) -> &[u32] {
+        &self.joystick_buttons[action as usize]
+    }
+
+    pub fn set_key(&mut self, action: Action, key: Key) {
+        self.keys[action as usize] = vec![key];
+    }
+
+    pub fn set_pad_button(&mut self, action: Action, button: PadButton) {
+        match button {
+            PadButton::Controller(button) => {
+                self.controller_buttons[action as usize] = vec![button]
+            }
+            PadButton::Joystick(button) => self.joystick_buttons[action as usize] = vec![button],
+        }
+    }
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings {
+            keys: [
+                vec![Key::Up, Key::NumPad8, Key::W],
+                vec![Key::Down, Key::NumPad2, Key::S],
+                vec![Key::Left, Key::NumPad4, Key::A],
+                vec![Key::Right, Key::NumPad6, Key::D],
+                vec![Key::Z, Key::Period, Key::LCtrl],
+                vec![Key::X, Key::Slash, Key::LAlt, Key::LShift],
+                vec![Key::P],
+                vec![Key::Escape],
+            ],
+            controller_buttons: [
+                vec!["dpup".to_string()],
+                vec!["dpdown".to_string()],
+                vec!["dpleft".to_string()],
+                vec!["dpright".to_string()],
+                vec!["a".to_string(), "y".to_string(), "leftshoulder".to_string()],
+                vec![
+                    "b".to_string(),
+                    "x".to_string(),
+                    "rightshoulder".to_string(),
+                ],
+                vec!["start".to_string()],
+                vec!["back".to_string()],
+            ],
+            joystick_buttons: [
+                vec![],
+                vec![],
+                vec![],
+                vec![],
+                vec![0, 3, 4, 7],
+                vec![1, 2, 5, 6],
+                vec![],
+                vec![],
+            ],
+        }
+    }
+}
+
+// Walks through the actions, each one taking the next key or pad button pressed.
+pub struct Rebinding {
+    bindings: Bindings,
+    action_idx: usize,
+}
+
+// Keeps the current bindings of the action being rebound.
+pub const REBINDING_SKIP_KEY: Key = Key::F1;
+
+impl Rebinding {
+    pub fn new(bindings: Bindings) -> Self {
+        Rebinding {
+            bindings,
+            action_idx: 0,
+        }
+    }
+
+    pub fn action(&self) -> Action {
+        ACTIONS[self.action_idx]
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.action_idx >= ACTION_NUM
+    }
+
+    pub fn bindings(&self) -> &Bindings {
+        &self.bindings
+    }
+
+    pub fn key_pressed(&mut self, key: Key) {
+        if key != REBINDING_SKIP_KEY {
+            self.bindings.set_key(self.action(), key);
+        }
+        self.action_idx += 1;
+    }
+
+    pub fn pad_button_pressed(&mut self, button: PadButton) {
+        self.bindings.set_pad_button(self.action(), button);
+        self.action_idx += 1;
+    }
+}
+
+#[test]
+fn rebinding_should_replace_or_keep_each_action() {
+    let mut rebinding = Rebinding::new(Bindings::default());
+    rebinding.key_pressed(Key::I);
+    rebinding.key_pressed(REBINDING_SKIP_KEY);
+    rebinding.pad_button_pressed(PadButton::Joystick(9));
+    for _ in 3..ACTION_NUM {
+        rebinding.key_pressed(REBINDING_SKIP_KEY);
+    }
+    assert!(rebinding.is_done());
+    let bindings = rebinding.bindings();
+    assert_eq!(bindings.keys(Action::Up), &[Key::I]);
+    assert_eq!(
+        bindings.keys(Action::Down),
+        Bindings::default().keys(Action::Down)
+    );
+    assert_eq!(bindings.joystick_buttons(Action::Left), &[9]);
+    assert_eq!(bindings.keys(Action::Esc), &[Key::Escape]);
+}
@@ -4,6 +4,7 @@ use crate::util::vector::Vector3;
 
 const ZOOM_CNT: i32 = 24;
 
+#[derive(Serialize, Deserialize)]
 pub struct Camera {
     rand: Rand,
     camera_pos: Vector3,
@@ -21,7 +22,7 @@ pub struct Camera {
     move_type: MoveType,
 }
 
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
 enum MoveType {
     Float,
     Fix,
@@ -0,0 +1,54 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::tt::errors::GameError;
+use crate::tt::screen::Screen;
+
+const SCREENSHOT_DIR_NAME: &str = "screenshots";
+
+fn save(screen: &Screen, path: &Path) -> Result<(), GameError> {
+    screen
+        .capture()
+        .save(path)
+        .map_err(Box::from)
+        .map_err(GameError::new_capture)
+}
+
+// Saves the frame in the screenshot directory, under a name made of the time.
+pub fn save_screenshot(screen: &Screen) -> Result<PathBuf, GameError> {
+    fs::create_dir_all(SCREENSHOT_DIR_NAME)
+        .map_err(Box::from)
+        .map_err(GameError::new_capture)?;
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let path = Path::new(SCREENSHOT_DIR_NAME).join(format!("{}.png", millis));
+    save(screen, &path)?;
+    Ok(path)
+}
+
+// Saves every frame in the directory, numbered from 0.
+pub struct FrameDump {
+    dir: PathBuf,
+    frame: u32,
+}
+
+impl FrameDump {
+    pub fn new(dir: &Path) -> Result<Self, GameError> {
+        fs::create_dir_all(dir)
+            .map_err(Box::from)
+            .map_err(GameError::new_capture)?;
+        Ok(FrameDump {
+            dir: dir.to_path_buf(),
+            frame: 0,
+        })
+    }
+
+    pub fn save_frame(&mut self, screen: &Screen) -> Result<(), GameError> {
+        save(screen, &self.dir.join(format!("{:06}.png", self.frame)))?;
+        self.frame += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+// The pad the game reads, opened as an SDL game controller when the mapping database knows it, so
+// that its buttons have the same names whatever the layout, and as a raw joystick otherwise.
+//
+// Pads may come and go while the game runs: the selected one is opened as soon as it shows up.
+
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::joystick::Joystick;
+use sdl2::{GameControllerSubsystem, JoystickSubsystem};
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::tt::bindings::{Action, Bindings, PadButton};
+use crate::tt::errors::GameError;
+use crate::tt::pad::{PadAxes, PadDirection, AXIS_MAX};
+
+// Mappings found in this file, if any, come in addition to the ones built in SDL.
+pub const MAPPING_FILE_NAME: &str = "gamecontrollerdb.txt";
+
+// In percent of the stick course, 50 being the threshold of the original game.
+pub const DEFAULT_DEADZONE: u32 = 50;
+
+const HOTPLUG_INTERVAL: Duration = Duration::from_secs(1);
+
+const BUTTONS: [Button; 15] = [
+    Button::A,
+    Button::B,
+    Button::X,
+    Button::Y,
+    Button::Back,
+    Button::Guide,
+    Button::Start,
+    Button::LeftStick,
+    Button::RightStick,
+    Button::LeftShoulder,
+    Button::RightShoulder,
+    Button::DPadUp,
+    Button::DPadDown,
+    Button::DPadLeft,
+    Button::DPadRight,
+];
+
+enum Device {
+    Controller(GameController),
+    Joystick(Joystick),
+}
+
+pub struct Controller {
+    controller_subsystem: GameControllerSubsystem,
+    joystick_subsystem: JoystickSubsystem,
+    // The index of the pad to open, the first one found if none.
+    selected: Option<u32>,
+    deadzone: i16,
+    device: Option<Device>,
+    last_hotplug_check: Instant,
+}
+
+impl Controller {
+    pub fn new(
+        controller_subsystem: GameControllerSubsystem,
+        joystick_subsystem: JoystickSubsystem,
+        selected: Option<u32>,
+        deadzone: u32,
+    ) -> Result<Self, GameError> {
+        load_mappings(&controller_subsystem)?;
+        controller_subsystem.set_event_state(false);
+        joystick_subsystem.set_event_state(false);
+        let mut controller = Controller {
+            controller_subsystem,
+            joystick_subsystem,
+            selected,
+            deadzone: deadzone_threshold(deadzone),
+            device: None,
+            last_hotplug_check: Instant::now(),
+        };
+        controller.open();
+        Ok(controller)
+    }
+
+    pub fn update(&mut self) {
+        self.controller_subsystem.update();
+        if self.last_hotplug_check.elapsed() < HOTPLUG_INTERVAL {
+            return;
+        }
+        self.last_hotplug_check = Instant::now();
+        let attached = match &self.device {
+            Some(Device::Controller(c)) => c.attached(),
+            Some(Device::Joystick(j)) => j.attached(),
+            None => false,
+        };
+        if !attached {
+            if self.device.take().is_some() {
+                println!("Pad disconnected");
+            }
+            self.open();
+        }
+    }
+
+    fn open(&mut self) {
+        let num = self.joystick_subsystem.num_joysticks().unwrap_or(0);
+        let index = self.selected.unwrap_or(0);
+        if index >= num {
+            return;
+        }
+        let device = if self.controller_subsystem.is_game_controller(index) {
+            self.controller_subsystem
+                .open(index)
+                .map(Device::Controller)
+                .map_err(|err| err.to_string())
+        } else {
+            self.joystick_subsystem
+                .open(index)
+                .map(Device::Joystick)
+                .map_err(|err| err.to_string())
+        };
+        match device {
+            Ok(device) => {
+                match &device {
+                    Device::Controller(c) => println!("Pad connected: {}", c.name()),
+                    Device::Joystick(j) => println!("Pad connected: {}", j.name()),
+                }
+                self.device = Some(device);
+            }
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+
+    fn stick(&self) -> Option<(i16, i16)> {
+        match &self.device {
+            Some(Device::Controller(c)) => Some((c.axis(Axis::LeftX), c.axis(Axis::LeftY))),
+            Some(Device::Joystick(j)) => Some((j.axis(0).unwrap_or(0), j.axis(1).unwrap_or(0))),
+            None => None,
+        }
+    }
+
+    pub fn direction(&self, bindings: &Bindings) -> PadDirection {
+        let (x, y) = match self.stick() {
+            Some(stick) => stick,
+            None => return PadDirection::NONE,
+        };
+        let mut direction = PadDirection::NONE;
+        if x > self.deadzone || self.pressed(bindings, Action::Right) {
+            direction |= PadDirection::RIGHT;
+        }
+        if x < -self.deadzone || self.pressed(bindings, Action::Left) {
+            direction |= PadDirection::LEFT;
+        }
+        if y > self.deadzone || self.pressed(bindings, Action::Down) {
+            direction |= PadDirection::DOWN;
+        }
+        if y < -self.deadzone || self.pressed(bindings, Action::Up) {
+            direction |= PadDirection::UP;
+        }
+        direction
+    }
+
+    pub fn axes(&self) -> PadAxes {
+        let (x, y) = self.stick().unwrap_or((0, 0));
+        PadAxes {
+            x: quantize_axis(x, self.deadzone),
+            // SDL gives y to the bottom.
+            y: -quantize_axis(y, self.deadzone),
+        }
+    }
+
+    pub fn pressed(&self, bindings: &Bindings, action: Action) -> bool {
+        match &self.device {
+            Some(Device::Controller(c)) => bindings
+                .controller_buttons(action)
+                .iter()
+                .filter_map(|button| Button::from_string(button))
+                .any(|button| c.button(button)),
+            Some(Device::Joystick(j)) => bindings
+                .joystick_buttons(action)
+                .iter()
+                .any(|button| j.button(*button).unwrap_or(false)),
+            None => false,
+        }
+    }
+
+    pub fn pressed_buttons(&self) -> HashSet<PadButton> {
+        match &self.device {
+            Some(Device::Controller(c)) => BUTTONS
+                .iter()
+                .filter(|button| c.button(**button))
+                .map(|button| PadButton::Controller(button.string()))
+                .collect(),
+            Some(Device::Joystick(j)) => (0..j.num_buttons())
+                .filter(|button| j.button(*button).unwrap_or(false))
+                .map(PadButton::Joystick)
+                .collect(),
+            None => HashSet::new(),
+        }
+    }
+}
+
+pub fn load_mappings(controller_subsystem: &GameControllerSubsystem) -> Result<(), GameError> {
+    if Path::new(MAPPING_FILE_NAME).exists() {
+        controller_subsystem
+            .load_mappings(MAPPING_FILE_NAME)
+            .map_err(Box::from)
+            .map_err(GameError::new_joystick)?;
+    }
+    Ok(())
+}
+
+// The name of each connected pad, by index.
+pub fn list(
+    controller_subsystem: &GameControllerSubsystem,
+    joystick_subsystem: &JoystickSubsystem,
+) -> Vec<String> {
+    let num = joystick_subsystem.num_joysticks().unwrap_or(0);
+    (0..num)
+        .map(|index| {
+            let name = if controller_subsystem.is_game_controller(index) {
+                controller_subsystem
+                    .name_for_index(index)
+                    .map(|name| format!("{} (controller)", name))
+            } else {
+                joystick_subsystem
+                    .name_for_index(index)
+                    .map(|name| format!("{} (joystick)", name))
+            };
+            name.unwrap_or_else(|err| err.to_string())
+        })
+        .collect()
+}
+
+// From a percentage of the stick course to an axis value, the course being 32768 either way.
+fn deadzone_threshold(deadzone: u32) -> i16 {
+    (32768 * deadzone.min(100) / 100).min(i16::MAX as u32) as i16
+}
+
+// The course left out of the deadzone is spread over the whole quantized range.
+fn quantize_axis(value: i16, deadzone: i16) -> i8 {
+    let value = i32::from(value);
+    let deadzone = i32::from(deadzone);
+    let quantized = if value > deadzone {
+        (value - deadzone) * i32::from(AXIS_MAX) / (i32::from(i16::MAX) - deadzone)
+    } else if value < -deadzone {
+        (value + deadzone) * i32::from(AXIS_MAX) / (-i32::from(i16::MIN) - deadzone)
+    } else {
+        0
+    };
+    quantized as i8
+}
+
+#[test]
+fn default_deadzone_should_match_the_original_threshold() {
+    assert_eq!(deadzone_threshold(DEFAULT_DEADZONE), 16384);
+    assert_eq!(deadzone_threshold(0), 0);
+    assert_eq!(deadzone_threshold(100), i16::MAX);
+}
+
+#[test]
+fn axes_should_be_quantized_out_of_the_deadzone() {
+    let deadzone = deadzone_threshold(DEFAULT_DEADZONE);
+    assert_eq!(quantize_axis(deadzone, deadzone), 0);
+    assert_eq!(quantize_axis(-deadzone, deadzone), 0);
+    assert_eq!(quantize_axis(i16::MAX, deadzone), AXIS_MAX);
+    assert_eq!(quantize_axis(i16::MIN, deadzone), -AXIS_MAX);
+    assert_eq!(quantize_axis(24576, 0), 95);
+}
@@ -18,6 +18,41 @@ pub enum GameError {
         #[new(value = "Backtrace::capture()")]
         backtrace: Backtrace,
     },
+    #[error("Replay error")]
+    Replay {
+        source: Box<dyn std::error::Error>,
+        #[cfg(nightly)]
+        #[new(value = "Backtrace::capture()")]
+        backtrace: Backtrace,
+    },
+    #[error("Game recorder error")]
+    GameRecorder {
+        source: Box<dyn std::error::Error>,
+        #[cfg(nightly)]
+        #[new(value = "Backtrace::capture()")]
+        backtrace: Backtrace,
+    },
+    #[error("Capture error")]
+    Capture {
+        source: Box<dyn std::error::Error>,
+        #[cfg(nightly)]
+        #[new(value = "Backtrace::capture()")]
+        backtrace: Backtrace,
+    },
+    #[error("Snapshot error")]
+    Snapshot {
+        source: Box<dyn std::error::Error>,
+        #[cfg(nightly)]
+        #[new(value = "Backtrace::capture()")]
+        backtrace: Backtrace,
+    },
+    #[error("Theme error")]
+    Theme {
+        source: Box<dyn std::error::Error>,
+        #[cfg(nightly)]
+        #[new(value = "Backtrace::capture()")]
+        backtrace: Backtrace,
+    },
     #[error("Sound initialization error")]
     SoundInit {
         source: Box<dyn std::error::Error>,
@@ -34,6 +69,13 @@ pub enum GameError {
         backtrace: Backtrace,
     },
 
+    #[error("Shader error")]
+    Shader {
+        source: Box<dyn std::error::Error>,
+        #[cfg(nightly)]
+        #[new(value = "Backtrace::capture()")]
+        backtrace: Backtrace,
+    },
     #[error("Window initialization error")]
     WindowInit {
         source: Box<dyn std::error::Error>,
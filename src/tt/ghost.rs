@@ -0,0 +1,79 @@
+use crate::util::renderer::BlendFunc;
+use crate::util::vector::Vector;
+
+use crate::tt::errors::GameError;
+use crate::tt::letter::Direction;
+use crate::tt::pad::PadState;
+use crate::tt::screen::Screen;
+use crate::tt::ship::Ship;
+use crate::tt::simulation::Simulation;
+use crate::tt::state::ReplayData;
+use crate::tt::tunnel::Tunnel;
+use crate::tt::GeneralParams;
+
+const ALPHA: f32 = 0.4;
+
+// How far behind the ship the ghost is still drawn.
+const MIN_DRAW_DEPTH: f32 = -5.;
+
+// The ship of a recorded run, to race against on the same seed.
+//
+// The recorded run is re-simulated in a world of its own, the ghost ship being the ship of that
+// world. Both tunnels are the same, which allows to draw the ghost ship in the player's one.
+pub struct Ghost {
+    replay_data: ReplayData,
+    simulation: Simulation,
+}
+
+impl Ghost {
+    pub fn new(replay_data: ReplayData) -> Result<Self, GameError> {
+        Ok(Ghost {
+            replay_data,
+            simulation: Simulation::new()?,
+        })
+    }
+
+    // The grade, level and seed the player must run for the race to make sense.
+    pub fn start_params(&self) -> (u32, f32, u64) {
+        self.replay_data.start_params()
+    }
+
+    pub fn start(&mut self) {
+        self.simulation.start_replay(&self.replay_data);
+    }
+
+    pub fn mov(&mut self) {
+        if !self.simulation.is_game_over() {
+            self.simulation.step(PadState::default());
+        }
+    }
+
+    fn ship(&self) -> &Ship {
+        &self.simulation.world().ship
+    }
+
+    pub fn draw(&self, ship: &Ship, tunnel: &Tunnel, screen: &Screen) {
+        let ghost_ship = self.ship();
+        let y = ghost_ship.distance(tunnel) - ship.distance(tunnel) + ghost_ship.rel_pos().y;
+        if y < MIN_DRAW_DEPTH || y > ship.in_sight_depth() {
+            return;
+        }
+        let pos3 = tunnel.get_pos_v(Vector::new_at(ghost_ship.pos().x, y));
+        let renderer = screen.renderer();
+        renderer.set_blend_func(BlendFunc::ConstantAdditive(ALPHA));
+        ghost_ship.draw_at(pos3, screen);
+        renderer.set_blend_func(BlendFunc::Additive);
+    }
+
+    // Positive differences mean that the player is ahead of the ghost.
+    pub fn draw_front(&self, params: &GeneralParams, ship: &Ship) {
+        let letter = params.letter;
+        let distance = ship.distance(params.tunnel) - self.ship().distance(params.tunnel);
+        let score = i64::from(params.shared_state.score()) - i64::from(self.simulation.score());
+        letter.draw_string_color("GHOST", 20., 64., 7., Direction::ToRight, 1);
+        letter.draw_string_color("DIST", 20., 84., 6., Direction::ToRight, 1);
+        letter.draw_string(&format!("{:+}", distance as i64), 90., 84., 6.);
+        letter.draw_string_color("SCORE", 20., 100., 6., Direction::ToRight, 1);
+        letter.draw_string(&format!("{:+}", score), 90., 100., 6.);
+    }
+}
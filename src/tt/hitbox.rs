@@ -0,0 +1,94 @@
+// Debug overlay of the hit areas, drawn in tunnel space over the scene.
+//
+// Boxes are the raw `Collidable::collision` half extents: x is scaled the way the collision
+// checks scale the angle difference, so that the boxes match what the game actually tests.
+
+use crate::tt::ship::{self, Ship};
+use crate::tt::tunnel::{self, Tunnel};
+use crate::tt::{GeneralParams, MoreParams};
+use crate::util::renderer::{Primitive, Renderer};
+use crate::util::vector::Vector;
+
+const HIT_WIDTH_SEGMENTS: usize = 16;
+
+pub fn draw(params: &GeneralParams, more_params: &MoreParams) {
+    let screen = &*params.screen;
+    let tunnel = &*params.tunnel;
+    screen.renderer().set_line_width(1.);
+    screen.set_color((0.3, 1., 0.3));
+    draw_slice_edges(more_params.ship, tunnel, screen.renderer());
+    screen.set_color((1., 0.3, 0.3));
+    more_params.enemies.draw_hitboxes(tunnel, screen);
+    screen.set_color((1., 1., 0.3));
+    more_params.bullets.draw_hitboxes(tunnel, screen);
+    screen.set_color((0.3, 0.6, 1.));
+    more_params.shots.draw_hitboxes(tunnel, screen);
+    screen.set_color((1., 1., 1.));
+    more_params.ship.draw_hitbox(tunnel, screen);
+}
+
+pub fn draw_box(pos: Vector, collision: Vector, tunnel: &Tunnel, renderer: &dyn Renderer) {
+    let cx = collision.x / ((tunnel.get_radius(pos.y) / tunnel::DEFAULT_RAD) * 3.);
+    renderer.begin(Primitive::LineLoop);
+    for (x, y) in &[
+        (-cx, -collision.y),
+        (cx, -collision.y),
+        (cx, collision.y),
+        (-cx, collision.y),
+    ] {
+        tunnel
+            .get_pos_v(Vector::new_at(pos.x + x, pos.y + y))
+            .vertex(renderer);
+    }
+    renderer.end();
+}
+
+// A bullet hits the ship when the ship is within the hit width of the path it just covered.
+pub fn draw_path(pos: Vector, ppos: Vector, tunnel: &Tunnel, renderer: &dyn Renderer) {
+    renderer.begin(Primitive::Lines);
+    tunnel.get_pos_v(ppos).vertex(renderer);
+    tunnel.get_pos_v(pos).vertex(renderer);
+    renderer.end();
+}
+
+pub fn draw_hit_width(pos: Vector, tunnel: &Tunnel, renderer: &dyn Renderer) {
+    let r = f32::sqrt(ship::HIT_WIDTH);
+    renderer.begin(Primitive::LineLoop);
+    for i in 0..HIT_WIDTH_SEGMENTS {
+        let d = i as f32 * std::f32::consts::PI * 2. / HIT_WIDTH_SEGMENTS as f32;
+        tunnel
+            .get_pos_v(Vector::new_at(pos.x + r * d.sin(), pos.y + r * d.cos()))
+            .vertex(renderer);
+    }
+    renderer.end();
+}
+
+// The course edges of every slice in sight, out of which the ship is pushed back.
+fn draw_slice_edges(ship: &Ship, tunnel: &Tunnel, renderer: &dyn Renderer) {
+    let mut prev: Option<(f32, tunnel::SliceEdges)> = None;
+    let mut y = 0.;
+    while y < ship.in_sight_depth() {
+        let slice = tunnel.get_slice(y);
+        let edges = if slice.is_nearly_round() {
+            None
+        } else {
+            Some(slice.get_edges())
+        };
+        if let (Some((py, pe)), Some(e)) = (&prev, &edges) {
+            renderer.begin(Primitive::Lines);
+            for (pd, d) in &[(pe.left, e.left), (pe.right, e.right)] {
+                tunnel.get_pos_v(Vector::new_at(*pd, *py)).vertex(renderer);
+                let mut d = *d;
+                if d - pd > std::f32::consts::PI {
+                    d -= std::f32::consts::PI * 2.;
+                } else if pd - d > std::f32::consts::PI {
+                    d += std::f32::consts::PI * 2.;
+                }
+                tunnel.get_pos_v(Vector::new_at(d, y)).vertex(renderer);
+            }
+            renderer.end();
+        }
+        prev = edges.map(|e| (y, e));
+        y += 1.;
+    }
+}
@@ -0,0 +1,70 @@
+// HUD widget showing the pad state read by the ship on the last tick, be it live or replayed.
+
+use crate::tt::letter::LETTER_WIDTH;
+use crate::tt::pad::{PadButtons, PadDirection, PadState, AXIS_MAX};
+use crate::tt::screen::Screen;
+use crate::tt::GeneralParams;
+use crate::util::renderer::Primitive;
+
+// Half the size of a key.
+const KEY_SIZE: f32 = 9.;
+const KEY_SPACING: f32 = KEY_SIZE * 2. + 3.;
+const LABEL_SCALE: f32 = 5.;
+
+pub fn draw(params: &GeneralParams, pad_state: PadState) {
+    let screen = &*params.screen;
+    let (o_width, o_height) = screen.ortho_size();
+    let cx = o_width as f32 - 150.;
+    let cy = o_height as f32 / 2. + 40.;
+    for (direction, x, y) in &[
+        (PadDirection::UP, 0., -KEY_SPACING),
+        (PadDirection::DOWN, 0., KEY_SPACING),
+        (PadDirection::LEFT, -KEY_SPACING, 0.),
+        (PadDirection::RIGHT, KEY_SPACING, 0.),
+    ] {
+        draw_key(
+            screen,
+            cx + x,
+            cy + y,
+            pad_state.direction.contains(*direction),
+        );
+    }
+    // In analog mode, where the stick is within the reach of the direction keys.
+    if let Some(axes) = pad_state.axes {
+        let x = cx + KEY_SPACING * f32::from(axes.x) / f32::from(AXIS_MAX);
+        let y = cy - KEY_SPACING * f32::from(axes.y) / f32::from(AXIS_MAX);
+        screen.set_color((1., 0.9, 0.5));
+        draw_square(screen, x, y, KEY_SIZE / 3., Primitive::Quads);
+    }
+    for (button, name, x) in &[
+        (PadButtons::A, "A", KEY_SPACING * 3.),
+        (PadButtons::B, "B", KEY_SPACING * 4.),
+    ] {
+        draw_key(screen, cx + x, cy, pad_state.buttons.contains(*button));
+        params.letter.draw_string(
+            name,
+            cx + x - LETTER_WIDTH * LABEL_SCALE / 2.,
+            cy + KEY_SIZE + 4.,
+            LABEL_SCALE,
+        );
+    }
+}
+
+fn draw_key(screen: &Screen, x: f32, y: f32, pressed: bool) {
+    if pressed {
+        screen.set_alpha_color((1., 1., 1., 0.6));
+        draw_square(screen, x, y, KEY_SIZE, Primitive::Quads);
+    }
+    screen.set_color((1., 1., 1.));
+    draw_square(screen, x, y, KEY_SIZE, Primitive::LineLoop);
+}
+
+fn draw_square(screen: &Screen, x: f32, y: f32, size: f32, primitive: Primitive) {
+    let renderer = screen.renderer();
+    renderer.begin(primitive);
+    renderer.vertex(x - size, y - size, 0.);
+    renderer.vertex(x + size, y - size, 0.);
+    renderer.vertex(x + size, y + size, 0.);
+    renderer.vertex(x - size, y + size, 0.);
+    renderer.end();
+}
@@ -1,18 +1,21 @@
-const LETTER_WIDTH: f32 = 2.1;
+pub const LETTER_WIDTH: f32 = 2.1;
 const LETTER_HEIGHT: f32 = 3.0;
 const COLOR_NUM: usize = 4;
-const COLOR_RGB: [(f32, f32, f32); 2] = [(1., 1., 1.), (0.9, 0.7, 0.5)];
 const LETTER_NUM: usize = 44;
 const DISPLAY_LIST_NUM: usize = LETTER_NUM * COLOR_NUM;
 
-use crate::gl;
+use std::rc::Rc;
 
 use crate::tt::screen::Screen;
 use crate::util::color::Color;
-use crate::util::display_list::DisplayList;
+use crate::util::display_list::{DisplayList, LazyDisplayList};
+use crate::util::renderer::{Primitive, Renderer};
 
 pub struct Letter {
-    display_list: DisplayList,
+    renderer: Rc<dyn Renderer>,
+    brightness: f32,
+    color_rgb: [(f32, f32, f32); 2],
+    display_list: LazyDisplayList,
 }
 
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
@@ -25,41 +28,55 @@ pub enum Direction {
 
 impl Letter {
     pub fn new(screen: &Screen) -> Self {
-        let mut display_list = DisplayList::new(DISPLAY_LIST_NUM as u32);
+        Letter {
+            renderer: screen.shared_renderer().clone(),
+            brightness: screen.brightness(),
+            color_rgb: screen.theme().letter,
+            display_list: LazyDisplayList::new(DISPLAY_LIST_NUM as u32),
+        }
+    }
+
+    fn create_display_list(&self, display_list: &mut DisplayList) {
         for j in 0..COLOR_NUM {
             for i in 0..LETTER_NUM {
                 display_list.new_list();
-                Letter::draw_letter_internal(i, j, screen);
+                Letter::draw_letter_internal(
+                    display_list.renderer(),
+                    i,
+                    j,
+                    &self.color_rgb,
+                    self.brightness,
+                );
                 display_list.end_list();
             }
         }
-        Letter { display_list }
+    }
+
+    fn call_display_list(&self, i: usize) {
+        self.display_list
+            .call(&self.renderer, i as u32, |display_list| {
+                self.create_display_list(display_list)
+            });
     }
 
     fn draw_letter(&self, letter: usize, x: f32, y: f32, scale: f32, deg: f32, color: usize) {
-        unsafe {
-            gl::PushMatrix();
-            gl::Translatef(x, y, 0.);
-            gl::Scalef(scale, scale, scale);
-            gl::Rotatef(deg, 0., 0., 1.);
-        }
-        self.display_list.call((letter + color * LETTER_NUM) as u32);
-        unsafe {
-            gl::PopMatrix();
-        }
+        let renderer = &*self.renderer;
+        renderer.push_matrix();
+        renderer.translate(x, y, 0.);
+        renderer.scale(scale, scale, scale);
+        renderer.rotate(deg, 0., 0., 1.);
+        self.call_display_list(letter + color * LETTER_NUM);
+        renderer.pop_matrix();
     }
 
     fn draw_letter_rev(&self, letter: usize, x: f32, y: f32, scale: f32, deg: f32, color: usize) {
-        unsafe {
-            gl::PushMatrix();
-            gl::Translatef(x, y, 0.);
-            gl::Scalef(scale, -scale, scale);
-            gl::Rotatef(deg, 0., 0., 1.);
-        }
-        self.display_list.call((letter + color * LETTER_NUM) as u32);
-        unsafe {
-            gl::PopMatrix();
-        }
+        let renderer = &*self.renderer;
+        renderer.push_matrix();
+        renderer.translate(x, y, 0.);
+        renderer.scale(scale, -scale, scale);
+        renderer.rotate(deg, 0., 0., 1.);
+        self.call_display_list(letter + color * LETTER_NUM);
+        renderer.pop_matrix();
     }
 
     fn convert_char_to_int(c: char) -> usize {
@@ -221,7 +238,13 @@ impl Letter {
         }
     }
 
-    fn draw_letter_internal(idx: usize, color: usize, screen: &Screen) {
+    fn draw_letter_internal(
+        renderer: &dyn Renderer,
+        idx: usize,
+        color: usize,
+        color_rgb: &[(f32, f32, f32); 2],
+        brightness: f32,
+    ) {
         let data = &SP_DATA[idx];
         for point in data.iter() {
             let x = point[0];
@@ -234,75 +257,75 @@ impl Letter {
             length *= 1.05;
             deg %= 180.;
             if color == 2 {
-                Letter::draw_box_line(x, y, size, length, deg);
+                Letter::draw_box_line(renderer, x, y, size, length, deg);
             } else if color == 3 {
-                Letter::draw_box_poly(x, y, size, length, deg);
+                Letter::draw_box_poly(renderer, x, y, size, length, deg);
             } else {
-                Letter::draw_box(x, y, size, length, deg, COLOR_RGB[color].into(), screen);
+                Letter::draw_box(
+                    renderer,
+                    x,
+                    y,
+                    size,
+                    length,
+                    deg,
+                    color_rgb[color].into(),
+                    brightness,
+                );
             }
         }
     }
 
-    fn draw_box(x: f32, y: f32, width: f32, height: f32, deg: f32, color: Color, screen: &Screen) {
-        unsafe {
-            gl::PushMatrix();
-            gl::Translatef(x - width / 2., y - height / 2., 0.);
-            gl::Rotatef(deg, 0., 0., 1.);
-        }
-        screen.set_alpha_color(color.with_alpha(0.5));
-        unsafe {
-            gl::Begin(gl::GL_TRIANGLE_FAN);
-        }
-        Letter::draw_box_part(width, height);
-        unsafe {
-            gl::End();
-        }
-        screen.set_color(color);
-        unsafe {
-            gl::Begin(gl::GL_LINE_LOOP);
-        }
-        Letter::draw_box_part(width, height);
-        unsafe {
-            gl::End();
-            gl::PopMatrix();
-        }
+    fn draw_box(
+        renderer: &dyn Renderer,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        deg: f32,
+        color: Color,
+        brightness: f32,
+    ) {
+        renderer.push_matrix();
+        renderer.translate(x - width / 2., y - height / 2., 0.);
+        renderer.rotate(deg, 0., 0., 1.);
+        Screen::set_alpha_color_with_brightness(renderer, color.with_alpha(0.5), brightness);
+        renderer.begin(Primitive::TriangleFan);
+        Letter::draw_box_part(renderer, width, height);
+        renderer.end();
+        Screen::set_alpha_color_with_brightness(renderer, color, brightness);
+        renderer.begin(Primitive::LineLoop);
+        Letter::draw_box_part(renderer, width, height);
+        renderer.end();
+        renderer.pop_matrix();
     }
 
-    fn draw_box_line(x: f32, y: f32, width: f32, height: f32, deg: f32) {
-        unsafe {
-            gl::PushMatrix();
-            gl::Translatef(x - width / 2., y - height / 2., 0.);
-            gl::Rotatef(deg, 0., 0., 1.);
-            gl::Begin(gl::GL_LINE_LOOP);
-        }
-        Letter::draw_box_part(width, height);
-        unsafe {
-            gl::End();
-            gl::PopMatrix();
-        }
+    fn draw_box_line(renderer: &dyn Renderer, x: f32, y: f32, width: f32, height: f32, deg: f32) {
+        renderer.push_matrix();
+        renderer.translate(x - width / 2., y - height / 2., 0.);
+        renderer.rotate(deg, 0., 0., 1.);
+        renderer.begin(Primitive::LineLoop);
+        Letter::draw_box_part(renderer, width, height);
+        renderer.end();
+        renderer.pop_matrix();
     }
 
-    fn draw_box_poly(x: f32, y: f32, width: f32, height: f32, deg: f32) {
-        unsafe {
-            gl::PushMatrix();
-            gl::Translatef(x - width / 2., y - height / 2., 0.);
-            gl::Rotatef(deg, 0., 0., 1.);
-            gl::Begin(gl::GL_TRIANGLE_FAN);
-            Letter::draw_box_part(width, height);
-            gl::End();
-            gl::PopMatrix();
-        }
+    fn draw_box_poly(renderer: &dyn Renderer, x: f32, y: f32, width: f32, height: f32, deg: f32) {
+        renderer.push_matrix();
+        renderer.translate(x - width / 2., y - height / 2., 0.);
+        renderer.rotate(deg, 0., 0., 1.);
+        renderer.begin(Primitive::TriangleFan);
+        Letter::draw_box_part(renderer, width, height);
+        renderer.end();
+        renderer.pop_matrix();
     }
 
-    fn draw_box_part(width: f32, height: f32) {
-        unsafe {
-            gl::Vertex3f(-width / 2., 0., 0.);
-            gl::Vertex3f(-width / 3. * 1., -height / 2., 0.);
-            gl::Vertex3f(width / 3. * 1., -height / 2., 0.);
-            gl::Vertex3f(width / 2., 0., 0.);
-            gl::Vertex3f(width / 3. * 1., height / 2., 0.);
-            gl::Vertex3f(-width / 3. * 1., height / 2., 0.);
-        }
+    fn draw_box_part(renderer: &dyn Renderer, width: f32, height: f32) {
+        renderer.vertex(-width / 2., 0., 0.);
+        renderer.vertex(-width / 3. * 1., -height / 2., 0.);
+        renderer.vertex(width / 3. * 1., -height / 2., 0.);
+        renderer.vertex(width / 2., 0., 0.);
+        renderer.vertex(width / 3. * 1., height / 2., 0.);
+        renderer.vertex(-width / 3. * 1., height / 2., 0.);
     }
 }
 
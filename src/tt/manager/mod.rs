@@ -2,17 +2,20 @@ pub mod stage;
 pub mod title;
 
 use piston::input::*;
+use std::path::{Path, PathBuf};
 
 use crate::tt::errors::GameError;
-use crate::tt::prefs::{load_prefs_file, save_prefs_file, PrefManager};
+use crate::tt::ghost::Ghost;
+use crate::tt::input_display;
+use crate::tt::perf::{self, Timing};
+use crate::tt::prefs::PrefManager;
+use crate::tt::replay::{GameplayOptions, ReplayFile};
 use crate::tt::screen::Screen;
 use crate::tt::state::in_game::InGameState;
 use crate::tt::state::title::TitleState;
 use crate::tt::state::{ReplayData, State};
 use crate::tt::{GeneralParams, MoreParams};
 
-use crate::gl;
-
 pub trait Manager {
     fn start(
         &mut self,
@@ -39,6 +42,10 @@ pub struct GameManager {
     in_game_state: InGameState,
     state: GameState,
     esc_pressed: bool,
+    options: GameplayOptions,
+    record_path: Option<PathBuf>,
+    show_hitboxes: bool,
+    show_input: bool,
 }
 
 #[derive(Debug)]
@@ -50,7 +57,7 @@ pub enum MoveAction {
 }
 
 impl GameManager {
-    pub fn new(screen: &Screen) -> Result<Self, GameError> {
+    pub fn new(screen: &Screen, options: GameplayOptions) -> Result<Self, GameError> {
         let title_state = TitleState::new(&screen)?;
         let in_game_state = InGameState::new();
         Ok(GameManager {
@@ -58,9 +65,42 @@ impl GameManager {
             in_game_state,
             state: GameState::Title,
             esc_pressed: false,
+            options,
+            record_path: None,
+            show_hitboxes: false,
+            show_input: false,
         })
     }
 
+    pub fn set_record_path(&mut self, record_path: Option<PathBuf>) {
+        self.record_path = record_path;
+    }
+
+    // Every game is then run on the seed, grade and level of the ghost.
+    pub fn set_ghost(&mut self, ghost: Option<Ghost>) {
+        self.in_game_state.set_ghost(ghost);
+    }
+
+    // Debug overlay of the collision boxes, in game and in replays.
+    pub fn toggle_hitboxes(&mut self) {
+        self.show_hitboxes = !self.show_hitboxes;
+        self.title_state.set_show_hitboxes(self.show_hitboxes);
+        self.in_game_state.set_show_hitboxes(self.show_hitboxes);
+    }
+
+    // The pad state of the ship, in game and in replays.
+    pub fn toggle_input_display(&mut self) {
+        self.show_input = !self.show_input;
+    }
+
+    pub fn in_title(&self) -> bool {
+        self.state == GameState::Title
+    }
+
+    pub fn replay_ended(&self) -> bool {
+        self.state == GameState::Title && self.title_state.replay_ended()
+    }
+
     pub fn quit_last(&self, pref_manager: &PrefManager) -> Result<(), GameError> {
         pref_manager.save()?;
         Ok(())
@@ -74,12 +114,25 @@ impl GameManager {
         from_game_over: bool,
     ) -> Result<(), GameError> {
         let replay_data = if load_last_state {
-            load_prefs_file::<ReplayData, _>("last_replay")
+            match ReplayFile::load(ReplayFile::last_replay_path()) {
+                Ok(replay_file) => replay_file.replay_data,
+                Err(err) => {
+                    println!("Replay error: {:?}", err);
+                    ReplayData::default()
+                }
+            }
         } else {
-            self.in_game_state.replay_data(params)
+            let replay_data = self.in_game_state.replay_data(params);
+            if more_params.ship.is_game_over() {
+                if let Some(record_path) = &self.record_path {
+                    ReplayFile::new(replay_data.clone(), self.options).save_in(record_path)?;
+                }
+            }
+            replay_data
         };
         if from_game_over {
-            save_prefs_file(&replay_data, "last_replay")?;
+            ReplayFile::new(replay_data.clone(), self.options)
+                .save(ReplayFile::last_replay_path())?;
         }
         self.title_state.set_replay_data(replay_data);
         self.state = GameState::Title;
@@ -87,6 +140,20 @@ impl GameManager {
         Ok(())
     }
 
+    pub fn start_playback(
+        &mut self,
+        path: &Path,
+        params: &mut GeneralParams,
+        more_params: &mut MoreParams,
+    ) -> Result<(), GameError> {
+        let replay_file = ReplayFile::load(path)?;
+        self.title_state.set_replay_data(replay_file.replay_data);
+        self.state = GameState::Title;
+        self.start_state(0, params, more_params)?;
+        self.title_state.enter_replay_mode();
+        Ok(())
+    }
+
     pub fn start_in_game(
         &mut self,
         seed: u64,
@@ -109,8 +176,15 @@ impl GameManager {
                 self.title_state.start(params, more_params)?;
             }
             GameState::InGame => {
-                let grade = params.pref_manager.selected_grade();
-                let level = params.pref_manager.selected_level();
+                // A ghost may have been recorded from between two levels, which it runs again.
+                let (grade, level, seed) = match self.in_game_state.ghost() {
+                    Some(ghost) => ghost.start_params(),
+                    None => (
+                        params.pref_manager.selected_grade(),
+                        params.pref_manager.selected_level() as f32,
+                        seed,
+                    ),
+                };
                 self.in_game_state
                     .start(grade, level, seed, params, more_params)
             }
@@ -157,10 +231,9 @@ impl Manager for GameManager {
         more_params: &mut MoreParams,
         render_args: &RenderArgs,
     ) {
+        let timer = params.perf.start();
         if params.screen.start_render_to_luminous_screen() {
-            unsafe {
-                gl::PushMatrix();
-            }
+            params.screen.renderer().push_matrix();
             more_params
                 .ship
                 .set_eye_pos(params.screen, params.camera, params.tunnel);
@@ -174,15 +247,13 @@ impl Manager for GameManager {
                         .draw_luminous(params, more_params, render_args)
                 }
             }
-            unsafe {
-                gl::PopMatrix();
-            }
+            params.screen.renderer().pop_matrix();
             params.screen.end_render_to_luminous_screen();
         }
-        Screen::clear();
-        unsafe {
-            gl::PushMatrix();
-        }
+        params.perf.stop(Timing::DrawLuminous, timer);
+        let timer = params.perf.start();
+        params.screen.clear();
+        params.screen.renderer().push_matrix();
         more_params
             .ship
             .set_eye_pos(params.screen, params.camera, params.tunnel);
@@ -190,10 +261,10 @@ impl Manager for GameManager {
             GameState::Title => self.title_state.draw(params, more_params, render_args),
             GameState::InGame => self.in_game_state.draw(params, more_params, render_args),
         }
-        unsafe {
-            gl::PopMatrix();
-        }
+        params.screen.renderer().pop_matrix();
         params.screen.draw_luminous();
+        params.perf.stop(Timing::Draw, timer);
+        let timer = params.perf.start();
         params.screen.view_ortho_fixed();
         match self.state {
             GameState::Title => {
@@ -206,12 +277,23 @@ impl Manager for GameManager {
                         .shared_state
                         .draw_front(params, more_params, render_args);
                 }
+                if self.show_input && self.title_state.replay_change_ratio() >= 1. {
+                    input_display::draw(params, more_params.ship.pad_state());
+                }
             }
-            GameState::InGame => self
-                .in_game_state
-                .draw_front(params, more_params, render_args),
+            GameState::InGame => {
+                self.in_game_state
+                    .draw_front(params, more_params, render_args);
+                if self.show_input {
+                    input_display::draw(params, more_params.ship.pad_state());
+                }
+            }
+        }
+        params.perf.stop(Timing::DrawFront, timer);
+        if params.perf.is_enabled() {
+            perf::draw(params, more_params);
         }
-        Screen::view_perspective();
+        params.screen.view_perspective();
     }
 
     fn draw_front(&self, _params: &GeneralParams, _render_args: &RenderArgs) {}
@@ -1,10 +1,13 @@
+use std::hash::Hasher;
+
+use crate::util::math;
 use crate::util::rand::Rand;
 
 use crate::tt::actor::enemy::ship_spec::ShipSpec;
 use crate::tt::actor::enemy::{Enemy, EnemySetOption};
 use crate::tt::barrage::BarrageManager;
-use crate::tt::screen::Screen;
 use crate::tt::ship;
+use crate::tt::theme::Theme;
 use crate::tt::tunnel::{SliceDrawState, Torus, Tunnel};
 use crate::tt::MoreParams;
 
@@ -14,28 +17,9 @@ const LEVEL_UP_RATIO: f32 = 0.5;
 
 const TUNNEL_COLOR_CHANGE_INTERVAL: u32 = 60;
 
-const TUNNEL_COLOR_PATTERN_POLY: [(f32, f32, f32); 7] = [
-    (0.7, 0.9, 1.),
-    (0.6, 1., 0.8),
-    (0.9, 0.7, 0.6),
-    (0.8, 0.8, 0.8),
-    (0.5, 0.9, 0.9),
-    (0.7, 0.9, 0.6),
-    (0.8, 0.5, 0.9),
-];
-
-const TUNNEL_COLOR_PATTERN_LINE: [(f32, f32, f32); 7] = [
-    (0.6, 0.7, 1.),
-    (0.4, 0.8, 0.6),
-    (0.7, 0.5, 0.6),
-    (0.6, 0.6, 0.6),
-    (0.4, 0.7, 0.7),
-    (0.6, 0.7, 0.5),
-    (0.6, 0.4, 1.),
-];
-
 const MAX_APP_DIST: f32 = 9_999_999.;
 
+#[derive(Serialize, Deserialize)]
 pub struct StageManager {
     rand: Rand,
     next_small_app_dist: f32,
@@ -47,6 +31,8 @@ pub struct StageManager {
     grade: u32,
     boss_mode_end_cnt: i32,
     medium_boss_zone: bool,
+    tunnel_color_pattern_poly: Vec<(f32, f32, f32)>,
+    tunnel_color_pattern_line: Vec<(f32, f32, f32)>,
     tunnel_color_poly_idx: usize,
     tunnel_color_line_idx: usize,
     tunnel_color_change_cnt: u32,
@@ -55,9 +41,11 @@ pub struct StageManager {
 }
 
 impl StageManager {
-    pub fn new(seed: u64) -> Self {
-        let tunnel_color_poly_idx = TUNNEL_COLOR_PATTERN_POLY.len() - 2;
-        let tunnel_color_line_idx = TUNNEL_COLOR_PATTERN_LINE.len() - 2;
+    pub fn new(seed: u64, theme: &Theme) -> Self {
+        let tunnel_color_pattern_poly = theme.tunnel_poly.clone();
+        let tunnel_color_pattern_line = theme.tunnel_line.clone();
+        let tunnel_color_poly_idx = tunnel_color_pattern_poly.len() - 2;
+        let tunnel_color_line_idx = tunnel_color_pattern_line.len() - 2;
         StageManager {
             rand: Rand::new(seed),
             next_small_app_dist: 0.,
@@ -75,9 +63,14 @@ impl StageManager {
             dark_line: true,
             slice_draw_state: SliceDrawState {
                 dark_line_ratio: 1.,
-                poly: TUNNEL_COLOR_PATTERN_POLY[tunnel_color_poly_idx].into(),
-                line: TUNNEL_COLOR_PATTERN_POLY[tunnel_color_line_idx].into(),
+                poly: tunnel_color_pattern_poly[tunnel_color_poly_idx].into(),
+                // As in the original game, the first line color comes from the polygon palette.
+                line: tunnel_color_pattern_poly
+                    [tunnel_color_line_idx % tunnel_color_pattern_poly.len()]
+                .into(),
             },
+            tunnel_color_pattern_poly,
+            tunnel_color_pattern_line,
         }
     }
 
@@ -86,7 +79,6 @@ impl StageManager {
         level: f32,
         grade: u32,
         seed: u64,
-        screen: &Screen,
         tunnel: &mut Tunnel,
         barrage_manager: &mut BarrageManager,
         more_params: &mut MoreParams,
@@ -98,27 +90,27 @@ impl StageManager {
         self.zone_end_rank = 0;
         self.medium_boss_zone = false;
         self.dark_line = true;
-        self.tunnel_color_poly_idx = TUNNEL_COLOR_PATTERN_POLY.len() + level as usize - 2;
-        self.tunnel_color_line_idx = TUNNEL_COLOR_PATTERN_LINE.len() + level as usize - 2;
+        self.tunnel_color_poly_idx = self.tunnel_color_pattern_poly.len() + level as usize - 2;
+        self.tunnel_color_line_idx = self.tunnel_color_pattern_line.len() + level as usize - 2;
         self.slice_draw_state = SliceDrawState {
             dark_line_ratio: 1.,
-            poly: TUNNEL_COLOR_PATTERN_POLY
-                [self.tunnel_color_poly_idx % TUNNEL_COLOR_PATTERN_POLY.len()]
+            poly: self.tunnel_color_pattern_poly
+                [self.tunnel_color_poly_idx % self.tunnel_color_pattern_poly.len()]
             .into(),
-            line: TUNNEL_COLOR_PATTERN_LINE
-                [self.tunnel_color_line_idx % TUNNEL_COLOR_PATTERN_LINE.len()]
+            line: self.tunnel_color_pattern_line
+                [self.tunnel_color_line_idx % self.tunnel_color_pattern_line.len()]
             .into(),
         };
-        self.create_next_zone(screen, barrage_manager, more_params);
+        self.create_next_zone(barrage_manager, more_params);
     }
 
     fn create_next_zone(
         &mut self,
-        screen: &Screen,
         barrage_manager: &mut BarrageManager,
         more_params: &mut MoreParams,
     ) {
         self.level += LEVEL_UP_RATIO;
+        record_event!(ZoneChanged { level: self.level });
         self.medium_boss_zone = !self.medium_boss_zone;
         if self.dark_line {
             self.tunnel_color_poly_idx += 1;
@@ -132,7 +124,10 @@ impl StageManager {
         self.set_next_small_app_dist();
         self.set_next_medium_app_dist();
         if self.medium_boss_zone && self.level > 5. && self.rand.gen_usize(3) != 0 {
-            self.boss_num = 1 + self.rand.gen_usize(f32::sqrt(self.level / 5.) as usize + 1) as u32;
+            self.boss_num = 1 + self
+                .rand
+                .gen_usize(math::sqrt(self.level / 5.) as usize + 1)
+                as u32;
             if self.boss_num > 4 {
                 self.boss_num = 4;
             }
@@ -144,7 +139,6 @@ impl StageManager {
             self.grade,
             self.medium_boss_zone,
             self.boss_num,
-            screen,
             barrage_manager,
         );
         let boss_app_rank = BOSS_APP_RANK[self.grade as usize] - self.boss_num + self.zone_end_rank;
@@ -158,7 +152,6 @@ impl StageManager {
 
     pub fn mov(
         &mut self,
-        screen: &Screen,
         tunnel: &Tunnel,
         barrage_manager: &mut BarrageManager,
         more_params: &mut MoreParams,
@@ -193,7 +186,7 @@ impl StageManager {
                 self.boss_mode_end_cnt -= 1;
                 more_params.bullets.clear_visible();
                 if self.boss_mode_end_cnt < 0 {
-                    self.create_next_zone(screen, barrage_manager, more_params);
+                    self.create_next_zone(barrage_manager, more_params);
                     more_params.ship.start_next_zone();
                 }
             }
@@ -223,27 +216,27 @@ impl StageManager {
                 let c_ratio =
                     self.tunnel_color_change_cnt as f32 / TUNNEL_COLOR_CHANGE_INTERVAL as f32;
                 let cp_idx_prev =
-                    (self.tunnel_color_poly_idx - 1) % TUNNEL_COLOR_PATTERN_POLY.len();
-                let cp_idx_now = self.tunnel_color_poly_idx % TUNNEL_COLOR_PATTERN_POLY.len();
+                    (self.tunnel_color_poly_idx - 1) % self.tunnel_color_pattern_poly.len();
+                let cp_idx_now = self.tunnel_color_poly_idx % self.tunnel_color_pattern_poly.len();
                 self.slice_draw_state.poly = (
-                    TUNNEL_COLOR_PATTERN_POLY[cp_idx_prev].0 * c_ratio
-                        + TUNNEL_COLOR_PATTERN_POLY[cp_idx_now].0 * (1. - c_ratio),
-                    TUNNEL_COLOR_PATTERN_POLY[cp_idx_prev].1 * c_ratio
-                        + TUNNEL_COLOR_PATTERN_POLY[cp_idx_now].1 * (1. - c_ratio),
-                    TUNNEL_COLOR_PATTERN_POLY[cp_idx_prev].2 * c_ratio
-                        + TUNNEL_COLOR_PATTERN_POLY[cp_idx_now].2 * (1. - c_ratio),
+                    self.tunnel_color_pattern_poly[cp_idx_prev].0 * c_ratio
+                        + self.tunnel_color_pattern_poly[cp_idx_now].0 * (1. - c_ratio),
+                    self.tunnel_color_pattern_poly[cp_idx_prev].1 * c_ratio
+                        + self.tunnel_color_pattern_poly[cp_idx_now].1 * (1. - c_ratio),
+                    self.tunnel_color_pattern_poly[cp_idx_prev].2 * c_ratio
+                        + self.tunnel_color_pattern_poly[cp_idx_now].2 * (1. - c_ratio),
                 )
                     .into();
                 let cl_idx_prev =
-                    (self.tunnel_color_line_idx - 1) % TUNNEL_COLOR_PATTERN_LINE.len();
-                let cl_idx_now = self.tunnel_color_line_idx % TUNNEL_COLOR_PATTERN_LINE.len();
+                    (self.tunnel_color_line_idx - 1) % self.tunnel_color_pattern_line.len();
+                let cl_idx_now = self.tunnel_color_line_idx % self.tunnel_color_pattern_line.len();
                 self.slice_draw_state.line = (
-                    TUNNEL_COLOR_PATTERN_LINE[cl_idx_prev].0 * c_ratio
-                        + TUNNEL_COLOR_PATTERN_LINE[cl_idx_now].0 * (1. - c_ratio),
-                    TUNNEL_COLOR_PATTERN_LINE[cl_idx_prev].1 * c_ratio
-                        + TUNNEL_COLOR_PATTERN_LINE[cl_idx_now].1 * (1. - c_ratio),
-                    TUNNEL_COLOR_PATTERN_LINE[cl_idx_prev].2 * c_ratio
-                        + TUNNEL_COLOR_PATTERN_LINE[cl_idx_now].2 * (1. - c_ratio),
+                    self.tunnel_color_pattern_line[cl_idx_prev].0 * c_ratio
+                        + self.tunnel_color_pattern_line[cl_idx_now].0 * (1. - c_ratio),
+                    self.tunnel_color_pattern_line[cl_idx_prev].1 * c_ratio
+                        + self.tunnel_color_pattern_line[cl_idx_now].1 * (1. - c_ratio),
+                    self.tunnel_color_pattern_line[cl_idx_prev].2 * c_ratio
+                        + self.tunnel_color_pattern_line[cl_idx_now].2 * (1. - c_ratio),
                 )
                     .into();
             }
@@ -289,6 +282,11 @@ impl StageManager {
         self.level
     }
 
+    pub fn hash_state<H: Hasher>(&self, state: &mut H) {
+        state.write_u32(self.level.to_bits());
+        self.rand.hash_state(state);
+    }
+
     pub fn medium_boss_zone(&self) -> bool {
         self.medium_boss_zone
     }
@@ -297,3 +295,28 @@ impl StageManager {
         &self.slice_draw_state
     }
 }
+
+#[test]
+fn initial_line_color_should_come_from_the_poly_palette_whatever_its_length() {
+    use crate::util::color::Color;
+
+    let theme = Theme::classic();
+    let stage_manager = StageManager::new(0, &theme);
+    let slice_draw_state = stage_manager.slice_draw_state();
+    assert_eq!(slice_draw_state.poly, Color::from(theme.tunnel_poly[5]));
+    assert_eq!(slice_draw_state.line, Color::from(theme.tunnel_poly[5]));
+
+    let mut theme = Theme::classic();
+    theme.tunnel_poly.truncate(2);
+    let stage_manager = StageManager::new(0, &theme);
+    let slice_draw_state = stage_manager.slice_draw_state();
+    assert_eq!(slice_draw_state.poly, Color::from(theme.tunnel_poly[0]));
+    assert_eq!(slice_draw_state.line, Color::from(theme.tunnel_poly[1]));
+
+    let mut theme = Theme::classic();
+    theme.tunnel_line.truncate(3);
+    let stage_manager = StageManager::new(0, &theme);
+    let slice_draw_state = stage_manager.slice_draw_state();
+    assert_eq!(slice_draw_state.poly, Color::from(theme.tunnel_poly[5]));
+    assert_eq!(slice_draw_state.line, Color::from(theme.tunnel_poly[1]));
+}
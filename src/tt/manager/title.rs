@@ -1,6 +1,5 @@
 use piston::input::RenderArgs;
 
-use crate::gl;
 use crate::glu;
 use crate::tt::errors::GameError;
 use crate::tt::manager::MoveAction;
@@ -9,6 +8,7 @@ use crate::tt::screen::Screen;
 use crate::tt::ship;
 use crate::tt::{GeneralParams, MoreParams};
 use crate::util::display_list::DisplayList;
+use crate::util::renderer::Primitive;
 use crate::util::texture::Texture;
 use crate::util::vector::{Vector, Vector3};
 
@@ -17,6 +17,19 @@ use super::Manager;
 const REPLAY_CHANGE_DURATION: u32 = 30;
 const AUTO_REPEAT_START_TIME: u32 = 30;
 const AUTO_REPEAT_CNT: u32 = 5;
+const REPLAY_SEEK_TICKS: i32 = 600;
+
+// Playback commands given while holding A in replay mode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayCommand {
+    None,
+    TogglePause,
+    FrameStep,
+    FastForward,
+    Seek(i32),
+    // To the next tenth of the replay, back to its start after the last one.
+    SeekNextTenth,
+}
 
 pub struct TitleManager {
     display_list: DisplayList,
@@ -26,26 +39,30 @@ pub struct TitleManager {
     level: u32,
     dir_pressed: bool,
     btn_pressed: bool,
+    seek_btn_pressed: bool,
     key_repeat_cnt: u32,
     replay_cnt: u32,
     replay_mode: bool,
     replay_change_ratio: f32,
+    replay_command: ReplayCommand,
 }
 
 impl TitleManager {
     pub fn new(screen: &Screen) -> Result<Self, GameError> {
         Ok(TitleManager {
             display_list: TitleManager::create_torus_shape(screen),
-            title_texture: Texture::create("title.bmp")?,
+            title_texture: Texture::create(screen.shared_renderer(), "title.bmp")?,
             cnt: 0,
             grade: 0,
             level: 1,
             dir_pressed: true,
             btn_pressed: true,
+            seek_btn_pressed: true,
             key_repeat_cnt: 0,
             replay_cnt: 0,
             replay_mode: false,
             replay_change_ratio: 0.,
+            replay_command: ReplayCommand::None,
         })
     }
 
@@ -59,7 +76,9 @@ impl TitleManager {
         let PadState {
             direction: dir,
             buttons: btn,
+            ..
         } = params.pad.get_state();
+        self.replay_command = ReplayCommand::None;
         if !self.replay_mode {
             if dir & (PadDirection::RIGHT | PadDirection::LEFT) != PadDirection::NONE {
                 if !self.dir_pressed {
@@ -116,6 +135,43 @@ impl TitleManager {
                     }
                 }
             }
+        } else if btn & PadButtons::A != PadButtons::NONE {
+            if btn & PadButtons::B != PadButtons::NONE {
+                if !self.seek_btn_pressed {
+                    self.seek_btn_pressed = true;
+                    self.replay_command = ReplayCommand::SeekNextTenth;
+                }
+            } else {
+                self.seek_btn_pressed = false;
+            }
+            if dir & PadDirection::RIGHT != PadDirection::NONE {
+                self.dir_pressed = true;
+                self.replay_command = ReplayCommand::FastForward;
+            }
+            if dir & PadDirection::LEFT != PadDirection::NONE {
+                if !self.dir_pressed {
+                    self.dir_pressed = true;
+                    self.replay_command = ReplayCommand::Seek(-REPLAY_SEEK_TICKS);
+                } else {
+                    self.key_repeat_cnt += 1;
+                    if self.key_repeat_cnt >= AUTO_REPEAT_START_TIME
+                        && self.key_repeat_cnt % AUTO_REPEAT_CNT == 0
+                    {
+                        self.replay_command = ReplayCommand::Seek(-REPLAY_SEEK_TICKS);
+                    }
+                }
+            }
+            if dir & (PadDirection::UP | PadDirection::DOWN) != PadDirection::NONE {
+                if !self.dir_pressed {
+                    self.dir_pressed = true;
+                    if dir & PadDirection::UP != PadDirection::NONE {
+                        self.replay_command = ReplayCommand::TogglePause;
+                    }
+                    if dir & PadDirection::DOWN != PadDirection::NONE {
+                        self.replay_command = ReplayCommand::FrameStep;
+                    }
+                }
+            }
         } else {
             if dir & (PadDirection::RIGHT | PadDirection::LEFT) != PadDirection::NONE {
                 if !self.dir_pressed {
@@ -180,7 +236,8 @@ impl TitleManager {
         let mut ring_ofs = Vector3::default();
         let torus_rad = 5.;
         let ring_rad = 0.7;
-        let mut display_list = DisplayList::new(3);
+        let renderer = screen.renderer();
+        let mut display_list = DisplayList::new(screen.shared_renderer(), 3);
         display_list.new_list();
         let mut d1 = 0.;
         for _i in 0..32 {
@@ -188,11 +245,9 @@ impl TitleManager {
             for _j in 0..16 {
                 cp.x = f32::sin(d1) * torus_rad;
                 cp.y = f32::cos(d1) * torus_rad;
-                unsafe {
-                    gl::Begin(gl::GL_LINE_STRIP);
-                }
+                renderer.begin(Primitive::LineStrip);
                 TitleManager::create_ring_offset(&mut ring_ofs, &cp, ring_rad, d1, d2);
-                ring_ofs.gl_vertex();
+                ring_ofs.vertex(renderer);
                 TitleManager::create_ring_offset(
                     &mut ring_ofs,
                     &cp,
@@ -200,7 +255,7 @@ impl TitleManager {
                     d1,
                     d2 + std::f32::consts::PI * 2. / 16.,
                 );
-                ring_ofs.gl_vertex();
+                ring_ofs.vertex(renderer);
                 cp.x = f32::sin(d1 + std::f32::consts::PI * 2. / 32.) * torus_rad;
                 cp.y = f32::cos(d1 + std::f32::consts::PI * 2. / 32.) * torus_rad;
                 TitleManager::create_ring_offset(
@@ -210,10 +265,8 @@ impl TitleManager {
                     d1 + std::f32::consts::PI * 2. / 32.,
                     d2 + std::f32::consts::PI * 2. / 16.,
                 );
-                ring_ofs.gl_vertex();
-                unsafe {
-                    gl::End();
-                }
+                ring_ofs.vertex(renderer);
+                renderer.end();
                 d2 += std::f32::consts::PI * 2. / 16.;
             }
             d1 += std::f32::consts::PI * 2. / 32.;
@@ -221,22 +274,20 @@ impl TitleManager {
         display_list.end_list();
         display_list.new_list();
         d1 = 0.;
-        unsafe {
-            gl::Begin(gl::GL_QUADS);
-        }
+        renderer.begin(Primitive::Quads);
         for _i in 0..32 {
             cp.x = f32::sin(d1) * (torus_rad + ring_rad);
             cp.y = f32::cos(d1) * (torus_rad + ring_rad);
-            cp.gl_vertex();
+            cp.vertex(renderer);
             cp.x = f32::sin(d1) * (torus_rad + ring_rad * 10.);
             cp.y = f32::cos(d1) * (torus_rad + ring_rad * 10.);
-            cp.gl_vertex();
+            cp.vertex(renderer);
             cp.x = f32::sin(d1 + std::f32::consts::PI * 2. / 32.) * (torus_rad + ring_rad * 10.);
             cp.y = f32::cos(d1 + std::f32::consts::PI * 2. / 32.) * (torus_rad + ring_rad * 10.);
-            cp.gl_vertex();
+            cp.vertex(renderer);
             cp.x = f32::sin(d1 + std::f32::consts::PI * 2. / 32.) * (torus_rad + ring_rad);
             cp.y = f32::cos(d1 + std::f32::consts::PI * 2. / 32.) * (torus_rad + ring_rad);
-            cp.gl_vertex();
+            cp.vertex(renderer);
             d1 += std::f32::consts::PI * 2. / 32.;
         }
         d1 = 0.;
@@ -246,7 +297,7 @@ impl TitleManager {
                 cp.x = f32::sin(d1) * torus_rad;
                 cp.y = f32::cos(d1) * torus_rad;
                 TitleManager::create_ring_offset(&mut ring_ofs, &cp, ring_rad, d1, d2);
-                ring_ofs.gl_vertex();
+                ring_ofs.vertex(renderer);
                 TitleManager::create_ring_offset(
                     &mut ring_ofs,
                     &cp,
@@ -254,7 +305,7 @@ impl TitleManager {
                     d1,
                     d2 + std::f32::consts::PI * 2. / 16.,
                 );
-                ring_ofs.gl_vertex();
+                ring_ofs.vertex(renderer);
                 cp.x = f32::sin(d1 + std::f32::consts::PI * 2. / 32.) * torus_rad;
                 cp.y = f32::cos(d1 + std::f32::consts::PI * 2. / 32.) * torus_rad;
                 TitleManager::create_ring_offset(
@@ -264,7 +315,7 @@ impl TitleManager {
                     d1 + std::f32::consts::PI * 2. / 32.,
                     d2 + std::f32::consts::PI * 2. / 16.,
                 );
-                ring_ofs.gl_vertex();
+                ring_ofs.vertex(renderer);
                 TitleManager::create_ring_offset(
                     &mut ring_ofs,
                     &cp,
@@ -272,44 +323,34 @@ impl TitleManager {
                     d1 + std::f32::consts::PI * 2. / 32.,
                     d2,
                 );
-                ring_ofs.gl_vertex();
+                ring_ofs.vertex(renderer);
                 d2 += std::f32::consts::PI * 2. / 16.;
             }
             d1 += std::f32::consts::PI * 2. / 32.;
         }
-        unsafe {
-            gl::End();
-        }
+        renderer.end();
         display_list.end_list();
         display_list.new_list();
         d1 = 0.;
         screen.set_color((1., 1., 1.));
-        unsafe {
-            gl::Begin(gl::GL_LINE_LOOP);
-        }
+        renderer.begin(Primitive::LineLoop);
         for _i in 0..128 {
             cp.x = f32::sin(d1);
             cp.y = f32::cos(d1);
-            cp.gl_vertex();
+            cp.vertex(renderer);
             d1 += std::f32::consts::PI * 2. / 128.;
         }
-        unsafe {
-            gl::End();
-        }
+        renderer.end();
         screen.set_alpha_color((1., 1., 1., 0.3));
-        unsafe {
-            gl::Begin(gl::GL_TRIANGLE_FAN);
-            gl::Vertex3f(0., 0., 0.);
-        }
+        renderer.begin(Primitive::TriangleFan);
+        renderer.vertex(0., 0., 0.);
         for _i in 0..129 {
             cp.x = f32::sin(d1);
             cp.y = f32::cos(d1);
-            cp.gl_vertex();
+            cp.vertex(renderer);
             d1 += std::f32::consts::PI * 2. / 128.;
         }
-        unsafe {
-            gl::End();
-        }
+        renderer.end();
         display_list.end_list();
         display_list
     }
@@ -324,17 +365,14 @@ impl TitleManager {
         Vector::new_at(x, y)
     }
 
-    fn draw_cursor_ring(&self, pos: Vector, s: f32) {
-        unsafe {
-            gl::PushMatrix();
-            gl::Translatef(pos.x, pos.y, 0.);
-            gl::Rotatef(-20., 0., 0., 1.);
-            gl::Scalef(s * 2., s, 1.);
-        }
+    fn draw_cursor_ring(&self, pos: Vector, s: f32, screen: &Screen) {
+        let renderer = screen.renderer();
+        renderer.push_matrix();
+        renderer.translate(pos.x, pos.y, 0.);
+        renderer.rotate(-20., 0., 0., 1.);
+        renderer.scale(s * 2., s, 1.);
         self.display_list.call(2);
-        unsafe {
-            gl::PopMatrix();
-        }
+        renderer.pop_matrix();
     }
 
     fn create_ring_offset(
@@ -355,6 +393,17 @@ impl TitleManager {
     pub fn replay_change_ratio(&self) -> f32 {
         self.replay_change_ratio
     }
+
+    // Goes to replay mode without the transition, the title being skipped.
+    pub fn enter_replay_mode(&mut self) {
+        self.replay_mode = true;
+        self.replay_cnt = REPLAY_CHANGE_DURATION;
+        self.replay_change_ratio = 1.;
+    }
+
+    pub fn replay_command(&self) -> ReplayCommand {
+        self.replay_command
+    }
 }
 
 impl Manager for TitleManager {
@@ -370,8 +419,10 @@ impl Manager for TitleManager {
         self.key_repeat_cnt = 0;
         self.dir_pressed = true;
         self.btn_pressed = true;
+        self.seek_btn_pressed = true;
         self.replay_cnt = 0;
         self.replay_mode = false;
+        self.replay_command = ReplayCommand::None;
         Ok(())
     }
 
@@ -384,143 +435,116 @@ impl Manager for TitleManager {
         if self.replay_change_ratio >= 1.0 {
             return;
         }
-        unsafe {
-            gl::PopMatrix();
-        }
-        params.screen.view_ortho_fixed();
-        unsafe {
-            gl::Disable(gl::GL_BLEND);
-        }
         let screen = &params.screen;
+        let renderer = screen.renderer();
+        renderer.pop_matrix();
+        screen.view_ortho_fixed();
+        renderer.set_blend(false);
         screen.set_color((0., 0., 0.));
         let mut rcr = self.replay_change_ratio * 2.;
         if rcr > 1. {
             rcr = 1.;
         }
         let (o_width, o_height) = screen.ortho_size();
-        unsafe {
-            gl::Begin(gl::GL_QUADS);
-            gl::Vertex3f(
-                o_width as f32 + 190. * o_width as f32 * (rcr - 1.) / 640.,
-                0.,
-                0.,
-            );
-            gl::Vertex3f(o_width as f32, 0., 0.);
-            gl::Vertex3f(o_width as f32, o_height as f32, 0.);
-            gl::Vertex3f(
-                o_width as f32 + 190. * o_width as f32 * (rcr - 1.) / 640.,
-                o_height as f32,
-                0.,
-            );
-            gl::End();
-            gl::Enable(gl::GL_BLEND);
-        }
-        Screen::view_perspective();
-        unsafe {
-            gl::PushMatrix();
-        }
-        glu::look_at(0., 0., -1., 0., 0., 0., 0., 1., 0.);
-        unsafe {
-            gl::PushMatrix();
-            gl::Translatef(
-                3. - self.replay_change_ratio * 2.4,
-                1.8,
-                3.5 - self.replay_change_ratio * 1.5,
-            );
-            let ratio_threshold = 480. / 640.;
-            let ortho_ratio = o_height / o_width;
-            if ortho_ratio >= ratio_threshold {
-                gl::Scalef(1., 1. + (o_height as f32 - 480.) * 1.4 / 480., 1.);
-            } else {
-                gl::Scalef(1. + (o_width as f32 - 640.) * 0.35 / 640., 1., 1.);
-            }
-            gl::Rotatef(30., 1., 0., 0.);
-            gl::Rotatef(f32::sin(self.cnt as f32 * 0.005) * 12., 0., 1., 0.);
-            gl::Rotatef(self.cnt as f32 * 0.2, 0., 0., 1.);
-            gl::Disable(gl::GL_BLEND);
+        renderer.begin(Primitive::Quads);
+        renderer.vertex(
+            o_width as f32 + 190. * o_width as f32 * (rcr - 1.) / 640.,
+            0.,
+            0.,
+        );
+        renderer.vertex(o_width as f32, 0., 0.);
+        renderer.vertex(o_width as f32, o_height as f32, 0.);
+        renderer.vertex(
+            o_width as f32 + 190. * o_width as f32 * (rcr - 1.) / 640.,
+            o_height as f32,
+            0.,
+        );
+        renderer.end();
+        renderer.set_blend(true);
+        screen.view_perspective();
+        renderer.push_matrix();
+        glu::look_at(renderer, 0., 0., -1., 0., 0., 0., 0., 1., 0.);
+        renderer.push_matrix();
+        renderer.translate(
+            3. - self.replay_change_ratio * 2.4,
+            1.8,
+            3.5 - self.replay_change_ratio * 1.5,
+        );
+        let ratio_threshold = 480. / 640.;
+        let ortho_ratio = o_height / o_width;
+        if ortho_ratio >= ratio_threshold {
+            renderer.scale(1., 1. + (o_height as f32 - 480.) * 1.4 / 480., 1.);
+        } else {
+            renderer.scale(1. + (o_width as f32 - 640.) * 0.35 / 640., 1., 1.);
         }
+        renderer.rotate(30., 1., 0., 0.);
+        renderer.rotate(f32::sin(self.cnt as f32 * 0.005) * 12., 0., 1., 0.);
+        renderer.rotate(self.cnt as f32 * 0.2, 0., 0., 1.);
+        renderer.set_blend(false);
         screen.set_color((0., 0., 0.));
         self.display_list.call(1);
-        unsafe {
-            gl::Enable(gl::GL_BLEND);
-        }
+        renderer.set_blend(true);
         screen.set_alpha_color((1., 1., 1., 0.5));
         self.display_list.call(0);
-        unsafe {
-            gl::PopMatrix();
-        }
+        renderer.pop_matrix();
     }
 
     fn draw_front(&self, params: &GeneralParams, _render_args: &RenderArgs) {
         if self.replay_change_ratio > 0. {
             return;
         }
-        let (o_width, o_height) = params.screen.ortho_size();
-        unsafe {
-            gl::PushMatrix();
-            gl::Translatef(o_width as f32 - 132., o_height as f32 - 80., 0.);
-            gl::Rotatef(-20., 0., 0., 1.);
-            gl::Scalef(128., 64., 1.);
-            gl::LineWidth(2.);
-        }
-        self.display_list.call(2);
-        unsafe {
-            gl::LineWidth(1.);
-            gl::PopMatrix();
-        }
         let screen = &params.screen;
+        let renderer = screen.renderer();
+        let (o_width, o_height) = screen.ortho_size();
+        renderer.push_matrix();
+        renderer.translate(o_width as f32 - 132., o_height as f32 - 80., 0.);
+        renderer.rotate(-20., 0., 0., 1.);
+        renderer.scale(128., 64., 1.);
+        renderer.set_line_width(2.);
+        self.display_list.call(2);
+        renderer.set_line_width(1.);
+        renderer.pop_matrix();
         screen.set_color((1., 1., 1.));
-        unsafe {
-            gl::Enable(gl::GL_TEXTURE_2D);
-        }
         self.title_texture.bind();
-        unsafe {
-            gl::Begin(gl::GL_TRIANGLE_FAN);
-            gl::TexCoord2f(0., 0.);
-            gl::Vertex3f(o_width as f32 - 170., o_height as f32 - 100., 0.);
-            gl::TexCoord2f(1., 0.);
-            gl::Vertex3f(o_width as f32 - 42., o_height as f32 - 100., 0.);
-            gl::TexCoord2f(1., 1.);
-            gl::Vertex3f(o_width as f32 - 42., o_height as f32 - 52., 0.);
-            gl::TexCoord2f(0., 1.);
-            gl::Vertex3f(o_width as f32 - 170., o_height as f32 - 52., 0.);
-            gl::End();
-            gl::Disable(gl::GL_TEXTURE_2D);
-        }
+        renderer.begin(Primitive::TriangleFan);
+        renderer.tex_coord(0., 0.);
+        renderer.vertex(o_width as f32 - 170., o_height as f32 - 100., 0.);
+        renderer.tex_coord(1., 0.);
+        renderer.vertex(o_width as f32 - 42., o_height as f32 - 100., 0.);
+        renderer.tex_coord(1., 1.);
+        renderer.vertex(o_width as f32 - 42., o_height as f32 - 52., 0.);
+        renderer.tex_coord(0., 1.);
+        renderer.vertex(o_width as f32 - 170., o_height as f32 - 52., 0.);
+        renderer.end();
+        renderer.bind_texture(None);
         let letter = params.letter;
         for i in 0..ship::GRADE_NUM {
-            unsafe {
-                gl::LineWidth(2.);
-            }
+            renderer.set_line_width(2.);
             let cursor_pos = TitleManager::calc_cursor_pos(o_width as f32, i, 1);
-            self.draw_cursor_ring(cursor_pos, 15.);
+            self.draw_cursor_ring(cursor_pos, 15., screen);
             letter.draw_string(
                 ship::GRADE_LETTER[i],
                 cursor_pos.x - 4.,
                 cursor_pos.y - 10.,
                 7.,
             );
-            unsafe {
-                gl::LineWidth(1.);
-            }
+            renderer.set_line_width(1.);
             let ml = params.pref_manager.max_level(i as u32);
             if ml > 1 {
                 let e_cursor_pos = TitleManager::calc_cursor_pos(o_width as f32, i, ml);
-                self.draw_cursor_ring(e_cursor_pos, 15.);
+                self.draw_cursor_ring(e_cursor_pos, 15., screen);
                 letter.draw_num(ml as usize, e_cursor_pos.x + 7., e_cursor_pos.y - 8., 6.);
                 let l2_cursor_pos = TitleManager::calc_cursor_pos(o_width as f32, i, 2);
-                unsafe {
-                    gl::Begin(gl::GL_LINES);
-                    gl::Vertex3f(cursor_pos.x - 29., cursor_pos.y + 7., 0.);
-                    gl::Vertex3f(l2_cursor_pos.x - 29., l2_cursor_pos.y + 7., 0.);
-                    gl::Vertex3f(l2_cursor_pos.x - 29., l2_cursor_pos.y + 7., 0.);
-                    gl::Vertex3f(e_cursor_pos.x - 29., e_cursor_pos.y + 7., 0.);
-                    gl::Vertex3f(cursor_pos.x + 29., cursor_pos.y - 7., 0.);
-                    gl::Vertex3f(l2_cursor_pos.x + 29., l2_cursor_pos.y - 7., 0.);
-                    gl::Vertex3f(l2_cursor_pos.x + 29., l2_cursor_pos.y - 7., 0.);
-                    gl::Vertex3f(e_cursor_pos.x + 29., e_cursor_pos.y - 7., 0.);
-                    gl::End();
-                }
+                renderer.begin(Primitive::Lines);
+                renderer.vertex(cursor_pos.x - 29., cursor_pos.y + 7., 0.);
+                renderer.vertex(l2_cursor_pos.x - 29., l2_cursor_pos.y + 7., 0.);
+                renderer.vertex(l2_cursor_pos.x - 29., l2_cursor_pos.y + 7., 0.);
+                renderer.vertex(e_cursor_pos.x - 29., e_cursor_pos.y + 7., 0.);
+                renderer.vertex(cursor_pos.x + 29., cursor_pos.y - 7., 0.);
+                renderer.vertex(l2_cursor_pos.x + 29., l2_cursor_pos.y - 7., 0.);
+                renderer.vertex(l2_cursor_pos.x + 29., l2_cursor_pos.y - 7., 0.);
+                renderer.vertex(e_cursor_pos.x + 29., e_cursor_pos.y - 7., 0.);
+                renderer.end();
             }
         }
         let grade_str = ship::GRADE_STR[self.grade as usize];
@@ -539,6 +563,11 @@ impl Manager for TitleManager {
         letter.draw_string("-", o_width as f32 - 217., 54., 5.);
         let cursor_pos =
             TitleManager::calc_cursor_pos(o_width as f32, self.grade as usize, self.level);
-        self.draw_cursor_ring(cursor_pos, 18. + f32::sin(self.cnt as f32 * 0.1) * 3.);
+        self.draw_cursor_ring(
+            cursor_pos,
+            18. + f32::sin(self.cnt as f32 * 0.1) * 3.,
+            screen,
+        );
+        letter.draw_string("F1 KEYS", 10., o_height as f32 - 20., 5.);
     }
 }
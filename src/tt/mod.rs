@@ -1,16 +1,28 @@
 pub mod actor;
+pub mod autopilot;
 pub mod barrage;
+pub mod bindings;
 pub mod camera;
+pub mod capture;
+pub mod controller;
 pub mod errors;
+pub mod ghost;
+pub mod hitbox;
+pub mod input_display;
 pub mod letter;
 pub mod manager;
 pub mod pad;
+pub mod perf;
 pub mod prefs;
+pub mod replay;
+pub mod save_state;
 pub mod screen;
 pub mod shape;
 pub mod ship;
+pub mod simulation;
 pub mod sound;
 pub mod state;
+pub mod theme;
 pub mod tunnel;
 
 pub struct GeneralParams<'a, 'shared, 'sound> {
@@ -32,6 +44,8 @@ pub struct GeneralParams<'a, 'shared, 'sound> {
 
     pub barrage_manager: &'a mut barrage::BarrageManager,
 
+    pub perf: &'a mut perf::PerfStats,
+
     #[cfg(feature = "game_recorder")]
     pub next_recorder_id: usize,
 }
@@ -3,9 +3,11 @@ use piston::input::{Button, ButtonArgs, ButtonState};
 use rle_vec::RleVec;
 use std::collections::HashSet;
 
-use crate::tt::errors::GameError;
-
-const JOYSTICK_AXIS: i16 = 16384;
+use crate::tt::actor::bullet::BulletPool;
+use crate::tt::bindings::{Action, Bindings, PadButton, Rebinding};
+use crate::tt::controller::Controller;
+use crate::tt::ship::Ship;
+use crate::tt::tunnel::Tunnel;
 
 pub trait Pad {
     fn start_record(&mut self);
@@ -18,22 +20,116 @@ pub trait Pad {
     fn pause_pressed(&self) -> bool;
     fn esc_pressed(&self) -> bool;
     fn get_record(&mut self) -> RleVec<PadState>;
+    // Where the replay stands, saved along with the world.
+    fn replay_position(&self) -> ReplayPosition;
+    fn set_replay_position(&mut self, position: ReplayPosition);
+
+    // Called once per event, to follow the devices being plugged or unplugged.
+    fn poll_devices(&mut self) {}
+
+    // Called before each tick of the game, for the pads which play by themselves.
+    fn observe(&mut self, _ship: &Ship, _tunnel: &Tunnel, _bullets: &BulletPool) {}
+
+    // Rebinding only makes sense for the pads which read the keyboard and game pads.
+    fn start_rebinding(&mut self) {}
+
+    fn rebinding_action(&self) -> Option<Action> {
+        None
+    }
+
+    // Returns the new bindings once the last action is rebound.
+    fn update_rebinding(&mut self) -> Option<Bindings> {
+        None
+    }
 }
 
 pub struct GamePad {
     button_reversed: bool,
+    analog: bool,
     keys: HashSet<Key>,
     state: PadState,
-    joystick: Option<(sdl2::JoystickSubsystem, sdl2::joystick::Joystick)>,
+    controller: Option<Controller>,
+    recorder: PadRecorder,
+    bindings: Bindings,
+    rebinding: Option<Rebinding>,
+    pad_pressed: HashSet<PadButton>,
+}
+
+// A pad which state is given by its owner instead of being read from the keyboard or a
+// joystick. This is what the simulation, and everything driving it, uses.
+pub struct VirtualPad {
+    state: PadState,
+    recorder: PadRecorder,
+}
+
+struct PadRecorder {
     record: RleVec<PadState>,
-    record_run_index: usize,
-    record_run_sub_index: usize,
+    position: ReplayPosition,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct ReplayPosition {
+    run_index: usize,
+    run_sub_index: usize,
 }
 
 #[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct PadState {
     pub direction: PadDirection,
     pub buttons: PadButtons,
+    // Only in analog mode, the ship then moves according to the axes instead of the direction.
+    pub axes: Option<PadAxes>,
+}
+
+impl Default for PadState {
+    fn default() -> Self {
+        PadState {
+            direction: PadDirection::NONE,
+            buttons: PadButtons::NONE,
+            axes: None,
+        }
+    }
+}
+
+pub const AXIS_MAX: i8 = 127;
+
+// The stick position quantized so that replays are exact, x to the right and y to the top, both
+// in [-AXIS_MAX, AXIS_MAX].
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct PadAxes {
+    pub x: i8,
+    pub y: i8,
+}
+
+impl PadAxes {
+    // The keyboard and the digital buttons push the stick all the way where it is centred.
+    fn with_direction(mut self, direction: PadDirection) -> Self {
+        if self.x == 0 {
+            if direction.contains(PadDirection::RIGHT) {
+                self.x += AXIS_MAX;
+            }
+            if direction.contains(PadDirection::LEFT) {
+                self.x -= AXIS_MAX;
+            }
+        }
+        if self.y == 0 {
+            if direction.contains(PadDirection::UP) {
+                self.y += AXIS_MAX;
+            }
+            if direction.contains(PadDirection::DOWN) {
+                self.y -= AXIS_MAX;
+            }
+        }
+        self
+    }
+
+    pub fn steering(self) -> f32 {
+        f32::from(self.x) / f32::from(AXIS_MAX)
+    }
+
+    pub fn throttle(self) -> f32 {
+        f32::from(self.y) / f32::from(AXIS_MAX)
+    }
 }
 
 bitflags! {
@@ -63,64 +159,48 @@ bitflags! {
 impl GamePad {
     pub fn new(
         button_reversed: bool,
-        joystick_subsystem: Option<sdl2::JoystickSubsystem>,
-    ) -> Result<Self, GameError> {
-        Ok(GamePad {
+        analog: bool,
+        controller: Option<Controller>,
+        bindings: Bindings,
+    ) -> Self {
+        GamePad {
             button_reversed,
+            analog,
             keys: HashSet::new(),
-            state: PadState {
-                direction: PadDirection::NONE,
-                buttons: PadButtons::NONE,
-            },
-            joystick: joystick_subsystem.map_or_else(
-                || Ok(None),
-                |joystick_subsystem| {
-                    let num = joystick_subsystem
-                        .num_joysticks()
-                        .map_err(Box::from)
-                        .map_err(GameError::new_joystick)?;
-                    if num > 0 {
-                        joystick_subsystem.set_event_state(false);
-                        joystick_subsystem
-                            .open(0)
-                            .map_err(Box::from)
-                            .map_err(GameError::new_joystick)
-                            .map(|j| Some((joystick_subsystem, j)))
-                    } else {
-                        Ok(None)
-                    }
-                },
-            )?,
-            record: RleVec::new(),
-            record_run_index: 0,
-            record_run_sub_index: 0,
-        })
+            state: PadState::default(),
+            controller,
+            recorder: PadRecorder::new(),
+            bindings,
+            rebinding: None,
+            pad_pressed: HashSet::new(),
+        }
+    }
+
+    fn key_bound(&self, action: Action) -> bool {
+        self.bindings
+            .keys(action)
+            .iter()
+            .any(|key| self.keys.contains(key))
+    }
+
+    fn pad_button_bound(&self, action: Action) -> bool {
+        self.controller
+            .as_ref()
+            .map_or(false, |c| c.pressed(&self.bindings, action))
     }
 
     fn calc_direction(&self) -> PadDirection {
         let mut direction = PadDirection::NONE;
-        if self.keys.contains(&Key::Right)
-            || self.keys.contains(&Key::NumPad6)
-            || self.keys.contains(&Key::D)
-        {
+        if self.key_bound(Action::Right) {
             direction |= PadDirection::RIGHT;
         }
-        if self.keys.contains(&Key::Left)
-            || self.keys.contains(&Key::NumPad4)
-            || self.keys.contains(&Key::A)
-        {
+        if self.key_bound(Action::Left) {
             direction |= PadDirection::LEFT;
         }
-        if self.keys.contains(&Key::Down)
-            || self.keys.contains(&Key::NumPad2)
-            || self.keys.contains(&Key::S)
-        {
+        if self.key_bound(Action::Down) {
             direction |= PadDirection::DOWN;
         }
-        if self.keys.contains(&Key::Up)
-            || self.keys.contains(&Key::NumPad8)
-            || self.keys.contains(&Key::D)
-        {
+        if self.key_bound(Action::Up) {
             direction |= PadDirection::UP;
         }
         direction
@@ -128,42 +208,22 @@ impl GamePad {
 
     fn get_direction(&self) -> PadDirection {
         let mut direction = self.state.direction;
-        if let Some((_, j)) = &self.joystick {
-            let x = j.axis(0).unwrap_or(0);
-            if x > JOYSTICK_AXIS {
-                direction |= PadDirection::RIGHT;
-            }
-            if x < -JOYSTICK_AXIS {
-                direction |= PadDirection::LEFT;
-            }
-            let y = j.axis(1).unwrap_or(0);
-            if y > JOYSTICK_AXIS {
-                direction |= PadDirection::DOWN;
-            }
-            if y < -JOYSTICK_AXIS {
-                direction |= PadDirection::UP;
-            }
+        if let Some(c) = &self.controller {
+            direction |= c.direction(&self.bindings);
         }
         direction
     }
 
     fn calc_buttons(&self) -> PadButtons {
         let mut buttons = PadButtons::NONE;
-        if self.keys.contains(&Key::Z)
-            || self.keys.contains(&Key::Period)
-            || self.keys.contains(&Key::LCtrl)
-        {
+        if self.key_bound(Action::A) {
             buttons |= if self.button_reversed {
                 PadButtons::B
             } else {
                 PadButtons::A
             };
         }
-        if self.keys.contains(&Key::X)
-            || self.keys.contains(&Key::Slash)
-            || self.keys.contains(&Key::LAlt)
-            || self.keys.contains(&Key::LShift)
-        {
+        if self.key_bound(Action::B) {
             buttons |= if self.button_reversed {
                 PadButtons::A
             } else {
@@ -175,48 +235,48 @@ impl GamePad {
 
     fn get_buttons(&self) -> PadButtons {
         let mut buttons = self.state.buttons;
-        if let Some((_, j)) = &self.joystick {
-            let btn1 = j.button(0).unwrap_or(false)
-                || j.button(3).unwrap_or(false)
-                || j.button(4).unwrap_or(false)
-                || j.button(7).unwrap_or(false);
-            if btn1 {
-                buttons |= if self.button_reversed {
-                    PadButtons::B
-                } else {
-                    PadButtons::A
-                };
-            }
-            let btn2 = j.button(1).unwrap_or(false)
-                || j.button(2).unwrap_or(false)
-                || j.button(5).unwrap_or(false)
-                || j.button(6).unwrap_or(false);
-            if btn2 {
-                buttons |= if self.button_reversed {
-                    PadButtons::A
-                } else {
-                    PadButtons::B
-                };
-            }
+        if self.pad_button_bound(Action::A) {
+            buttons |= if self.button_reversed {
+                PadButtons::B
+            } else {
+                PadButtons::A
+            };
+        }
+        if self.pad_button_bound(Action::B) {
+            buttons |= if self.button_reversed {
+                PadButtons::A
+            } else {
+                PadButtons::B
+            };
         }
         buttons
     }
+
+    fn pressed_pad_buttons(&self) -> HashSet<PadButton> {
+        self.controller
+            .as_ref()
+            .map_or_else(HashSet::new, Controller::pressed_buttons)
+    }
 }
 
 impl Pad for GamePad {
     fn start_record(&mut self) {
-        self.record.clear();
-        self.record_run_index = 0;
-        self.record_run_sub_index = 0;
+        self.recorder.start_record();
     }
 
     fn start_replay(&mut self, record: RleVec<PadState>) {
-        self.record = record;
-        self.record_run_index = 0;
-        self.record_run_sub_index = 0;
+        self.recorder.start_replay(record);
     }
 
     fn handle_button_event(&mut self, button_args: &ButtonArgs) {
+        if let Some(rebinding) = &mut self.rebinding {
+            if let (Button::Keyboard(key), ButtonState::Press) =
+                (button_args.button, button_args.state)
+            {
+                rebinding.key_pressed(key);
+            }
+            return;
+        }
         if let Button::Keyboard(key) = button_args.button {
             match button_args.state {
                 ButtonState::Press => {
@@ -229,6 +289,7 @@ impl Pad for GamePad {
             self.state = PadState {
                 direction: self.calc_direction(),
                 buttons: self.calc_buttons(),
+                axes: None,
             };
         }
     }
@@ -239,60 +300,220 @@ impl Pad for GamePad {
             self.state = PadState {
                 direction: self.calc_direction(),
                 buttons: self.calc_buttons(),
+                axes: None,
             };
         }
     }
 
     fn get_state(&self) -> PadState {
-        if let Some((joystick_subsystem, _)) = &self.joystick {
-            joystick_subsystem.update();
+        if self.rebinding.is_some() {
+            return PadState::default();
         }
         let direction = self.get_direction();
         let buttons = self.get_buttons();
-        PadState { direction, buttons }
+        let axes = if self.analog {
+            let axes = self
+                .controller
+                .as_ref()
+                .map_or_else(PadAxes::default, Controller::axes);
+            Some(axes.with_direction(direction))
+        } else {
+            None
+        };
+        PadState {
+            direction,
+            buttons,
+            axes,
+        }
     }
 
     fn record_state(&mut self) -> PadState {
         let state = self.get_state();
-        self.record.push(state);
+        self.recorder.push(state);
         state
     }
 
     fn replay_state(&mut self) -> Option<PadState> {
-        let sub_index = self.record_run_sub_index;
-        if let Some((state, eor)) = self
-            .record
-            .runs()
-            .nth(self.record_run_index)
-            .and_then(|run| {
-                if sub_index + 1 < run.len {
-                    Some((*run.value, false))
-                } else {
-                    Some((*run.value, true))
-                }
-            })
-        {
-            if !eor {
-                self.record_run_sub_index += 1;
-            } else {
-                self.record_run_index += 1;
-                self.record_run_sub_index = 0;
+        self.recorder.replay_state()
+    }
+
+    fn pause_pressed(&self) -> bool {
+        self.rebinding.is_none()
+            && (self.key_bound(Action::Pause) || self.pad_button_bound(Action::Pause))
+    }
+
+    fn esc_pressed(&self) -> bool {
+        self.rebinding.is_none()
+            && (self.key_bound(Action::Esc) || self.pad_button_bound(Action::Esc))
+    }
+
+    fn get_record(&mut self) -> RleVec<PadState> {
+        self.recorder.take_record()
+    }
+
+    fn replay_position(&self) -> ReplayPosition {
+        self.recorder.position
+    }
+
+    fn set_replay_position(&mut self, position: ReplayPosition) {
+        self.recorder.position = position;
+    }
+
+    fn poll_devices(&mut self) {
+        if let Some(c) = &mut self.controller {
+            c.update();
+        }
+    }
+
+    fn start_rebinding(&mut self) {
+        self.rebinding = Some(Rebinding::new(self.bindings.clone()));
+        // The buttons held from before do not count.
+        self.pad_pressed = self.pressed_pad_buttons();
+        self.keys.clear();
+        self.state = PadState::default();
+    }
+
+    fn rebinding_action(&self) -> Option<Action> {
+        self.rebinding.as_ref().map(Rebinding::action)
+    }
+
+    fn update_rebinding(&mut self) -> Option<Bindings> {
+        self.rebinding.as_ref()?;
+        let pressed = self.pressed_pad_buttons();
+        let rebinding = self.rebinding.as_mut().unwrap();
+        if !rebinding.is_done() {
+            if let Some(button) = pressed.difference(&self.pad_pressed).min() {
+                rebinding.pad_button_pressed(button.clone());
             }
-            Some(state)
+        }
+        self.pad_pressed = pressed;
+        if rebinding.is_done() {
+            self.bindings = rebinding.bindings().clone();
+            self.rebinding = None;
+            Some(self.bindings.clone())
         } else {
             None
         }
     }
+}
+
+#[allow(clippy::new_without_default)]
+impl VirtualPad {
+    pub fn new() -> Self {
+        VirtualPad {
+            state: PadState::default(),
+            recorder: PadRecorder::new(),
+        }
+    }
+
+    pub fn set_state(&mut self, state: PadState) {
+        self.state = state;
+    }
+
+    pub fn record(&self) -> &RleVec<PadState> {
+        &self.recorder.record
+    }
+
+    // Recording then goes on after the given states.
+    pub fn continue_record(&mut self, record: RleVec<PadState>) {
+        self.recorder.record = record;
+    }
+}
+
+impl Pad for VirtualPad {
+    fn start_record(&mut self) {
+        self.recorder.start_record();
+    }
+
+    fn start_replay(&mut self, record: RleVec<PadState>) {
+        self.recorder.start_replay(record);
+    }
+
+    fn handle_button_event(&mut self, _button_args: &ButtonArgs) {}
+
+    fn handle_focus_event(&mut self, _focus: bool) {}
+
+    fn get_state(&self) -> PadState {
+        self.state
+    }
+
+    fn record_state(&mut self) -> PadState {
+        self.recorder.push(self.state);
+        self.state
+    }
+
+    fn replay_state(&mut self) -> Option<PadState> {
+        self.recorder.replay_state()
+    }
 
     fn pause_pressed(&self) -> bool {
-        self.keys.contains(&Key::P)
+        false
     }
 
     fn esc_pressed(&self) -> bool {
-        self.keys.contains(&Key::Escape)
+        false
     }
 
     fn get_record(&mut self) -> RleVec<PadState> {
+        self.recorder.take_record()
+    }
+
+    fn replay_position(&self) -> ReplayPosition {
+        self.recorder.position
+    }
+
+    fn set_replay_position(&mut self, position: ReplayPosition) {
+        self.recorder.position = position;
+    }
+}
+
+impl PadRecorder {
+    fn new() -> Self {
+        PadRecorder {
+            record: RleVec::new(),
+            position: ReplayPosition::default(),
+        }
+    }
+
+    fn start_record(&mut self) {
+        self.record.clear();
+        self.position = ReplayPosition::default();
+    }
+
+    fn start_replay(&mut self, record: RleVec<PadState>) {
+        self.record = record;
+        self.position = ReplayPosition::default();
+    }
+
+    fn push(&mut self, state: PadState) {
+        self.record.push(state);
+    }
+
+    fn replay_state(&mut self) -> Option<PadState> {
+        let ReplayPosition {
+            run_index,
+            run_sub_index: sub_index,
+        } = self.position;
+        if let Some((state, eor)) = self.record.runs().nth(run_index).and_then(|run| {
+            if sub_index + 1 < run.len {
+                Some((*run.value, false))
+            } else {
+                Some((*run.value, true))
+            }
+        }) {
+            if !eor {
+                self.position.run_sub_index += 1;
+            } else {
+                self.position.run_index += 1;
+                self.position.run_sub_index = 0;
+            }
+            Some(state)
+        } else {
+            None
+        }
+    }
+
+    fn take_record(&mut self) -> RleVec<PadState> {
         std::mem::replace(&mut self.record, RleVec::new())
     }
 }
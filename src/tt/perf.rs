@@ -0,0 +1,162 @@
+use std::time::{Duration, Instant};
+
+use crate::tt::letter::{Direction, Letter};
+use crate::tt::{GeneralParams, MoreParams};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Timing {
+    Ship,
+    Stage,
+    Enemies,
+    Shots,
+    Bullets,
+    Particles,
+    DrawLuminous,
+    Draw,
+    DrawFront,
+}
+
+const TIMING_NUM: usize = 9;
+const TIMING_NAME: [&str; TIMING_NUM] = [
+    "SHIP",
+    "STAGE",
+    "ENEMIES",
+    "SHOTS",
+    "BULLETS",
+    "PARTICLES",
+    "LUMINOUS",
+    "DRAW",
+    "FRONT",
+];
+
+// The overlay shows the average of each timing over the last second, which is readable unlike
+// the values of a single frame.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct PerfStats {
+    enabled: bool,
+    timings: [Duration; TIMING_NUM],
+    frames: u32,
+    ticks: u64,
+    last_frame_ticks: u64,
+    refresh_start: Instant,
+    fps: u32,
+    frame_timings: [Duration; TIMING_NUM],
+}
+
+#[allow(clippy::new_without_default)]
+impl PerfStats {
+    pub fn new() -> Self {
+        PerfStats {
+            enabled: false,
+            timings: [Duration::default(); TIMING_NUM],
+            frames: 0,
+            ticks: 0,
+            last_frame_ticks: 0,
+            refresh_start: Instant::now(),
+            fps: 0,
+            frame_timings: [Duration::default(); TIMING_NUM],
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.timings = [Duration::default(); TIMING_NUM];
+        self.frames = 0;
+        self.refresh_start = Instant::now();
+    }
+
+    // Nothing is measured while the overlay is hidden.
+    pub fn start(&self) -> Option<Instant> {
+        if self.enabled {
+            Some(Instant::now())
+        } else {
+            None
+        }
+    }
+
+    pub fn stop(&mut self, timing: Timing, start: Option<Instant>) {
+        if let Some(start) = start {
+            self.timings[timing as usize] += start.elapsed();
+        }
+    }
+
+    pub fn add_ticks(&mut self, ticks: u64) {
+        self.ticks += ticks;
+    }
+
+    pub fn end_frame(&mut self) {
+        self.last_frame_ticks = self.ticks;
+        self.ticks = 0;
+        if !self.enabled {
+            return;
+        }
+        self.frames += 1;
+        let elapsed = self.refresh_start.elapsed();
+        if elapsed >= REFRESH_INTERVAL {
+            self.fps = (f64::from(self.frames) / elapsed.as_secs_f64()).round() as u32;
+            for (frame_timing, timing) in self.frame_timings.iter_mut().zip(&mut self.timings) {
+                *frame_timing = *timing / self.frames;
+                *timing = Duration::default();
+            }
+            self.frames = 0;
+            self.refresh_start = Instant::now();
+        }
+    }
+}
+
+pub fn draw(params: &GeneralParams, more_params: &MoreParams) {
+    let perf = &params.perf;
+    let letter = params.letter;
+    let (_, o_height) = params.screen.ortho_size();
+    let mut y = o_height as f32 / 2. - 170.;
+    draw_line(letter, "FPS", &perf.fps.to_string(), &mut y);
+    draw_line(letter, "TICKS", &perf.last_frame_ticks.to_string(), &mut y);
+    y += 8.;
+    for (name, timing) in TIMING_NAME.iter().zip(&perf.frame_timings) {
+        // In milliseconds.
+        let value = format!("{:.2}", timing.as_secs_f64() * 1000.);
+        draw_line(letter, name, &value, &mut y);
+    }
+    y += 8.;
+    let pools = [
+        (
+            "ENEMIES",
+            more_params.enemies.get_num(),
+            more_params.enemies.get_capacity(),
+        ),
+        (
+            "SHOTS",
+            more_params.shots.get_num(),
+            more_params.shots.get_capacity(),
+        ),
+        (
+            "BULLETS",
+            more_params.bullets.get_num(),
+            more_params.bullets.get_capacity(),
+        ),
+        (
+            "PARTICLES",
+            more_params.particles.get_num(),
+            more_params.particles.get_capacity(),
+        ),
+        (
+            "LETTERS",
+            more_params.float_letters.get_num(),
+            more_params.float_letters.get_capacity(),
+        ),
+    ];
+    for (name, num, capacity) in &pools {
+        draw_line(letter, name, &format!("{}/{}", num, capacity), &mut y);
+    }
+}
+
+fn draw_line(letter: &Letter, name: &str, value: &str, y: &mut f32) {
+    letter.draw_string_color(name, 10., *y, 5., Direction::ToRight, 1);
+    letter.draw_string(value, 130., *y, 5.);
+    *y += 16.;
+}
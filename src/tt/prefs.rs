@@ -1,7 +1,10 @@
 use preferences::{AppInfo, Preferences};
 
+use crate::tt::bindings::Bindings;
+use crate::tt::controller;
 use crate::tt::errors::GameError;
 use crate::tt::ship;
+use crate::tt::theme;
 
 pub struct PrefManager {
     prefs: GamePreferences,
@@ -15,6 +18,13 @@ impl PrefManager {
         PrefManager { prefs }
     }
 
+    // Preferences which are neither loaded nor saved, for headless runs.
+    pub fn new_transient() -> Self {
+        PrefManager {
+            prefs: GamePreferences::default(),
+        }
+    }
+
     pub fn save(&self) -> Result<(), GameError> {
         save_prefs_file(&self.prefs, "prefs")?;
         Ok(())
@@ -36,6 +46,30 @@ impl PrefManager {
         self.prefs.selected_level
     }
 
+    pub fn theme(&self) -> &str {
+        &self.prefs.theme
+    }
+
+    pub fn set_theme(&mut self, theme: &str) {
+        self.prefs.theme = theme.to_string();
+    }
+
+    pub fn deadzone(&self) -> u32 {
+        self.prefs.deadzone
+    }
+
+    pub fn set_deadzone(&mut self, deadzone: u32) {
+        self.prefs.deadzone = deadzone;
+    }
+
+    pub fn bindings(&self) -> &Bindings {
+        &self.prefs.bindings
+    }
+
+    pub fn set_bindings(&mut self, bindings: Bindings) {
+        self.prefs.bindings = bindings;
+    }
+
     pub fn record_start_game(&mut self, gd: u32, lv: u32) {
         self.prefs.selected_grade = gd;
         self.prefs.selected_level = lv;
@@ -60,6 +94,21 @@ struct GamePreferences {
     selected_grade: u32,
     selected_level: u32,
     grade_data: [GradeData; ship::GRADE_NUM],
+    // Missing from the preferences saved before themes existed.
+    #[serde(default = "default_theme")]
+    theme: String,
+    #[serde(default)]
+    bindings: Bindings,
+    #[serde(default = "default_deadzone")]
+    deadzone: u32,
+}
+
+fn default_theme() -> String {
+    theme::DEFAULT_THEME_NAME.to_string()
+}
+
+fn default_deadzone() -> u32 {
+    controller::DEFAULT_DEADZONE
 }
 
 impl GamePreferences {
@@ -76,6 +125,9 @@ impl Default for GamePreferences {
             selected_grade: 0,
             selected_level: 1,
             grade_data: [GradeData::new(); ship::GRADE_NUM],
+            theme: default_theme(),
+            bindings: Bindings::default(),
+            deadzone: default_deadzone(),
         }
     }
 }
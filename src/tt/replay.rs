@@ -0,0 +1,333 @@
+use rle_vec::RleVec;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::tt::errors::GameError;
+use crate::tt::pad::{PadButtons, PadDirection, PadState};
+use crate::tt::simulation::Simulation;
+use crate::tt::state::ReplayData;
+use crate::util::hash::fnv1a;
+
+const REPLAY_DIR_NAME: &str = "replay";
+const LAST_REPLAY_FILE_NAME: &str = "last.rpl";
+
+const MAGIC: &[u8; 4] = b"TTRP";
+const FORMAT_VERSION: u32 = 3;
+const GAME_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// The command line options which change the way the game is played.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct GameplayOptions {
+    pub reverse: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayHeader {
+    pub game_version: String,
+    pub options: GameplayOptions,
+    pub timestamp: u64,
+}
+
+#[derive(Error, Debug)]
+pub enum ReplayFileError {
+    #[error("not a replay file, or a replay saved by an older version of the game")]
+    NotAReplay,
+    #[error(
+        "unsupported replay format version {version} (supported: {})",
+        FORMAT_VERSION
+    )]
+    UnsupportedVersion { version: u32 },
+    #[error(
+        "replay recorded with version {recorded} of the game, this is version {}",
+        GAME_VERSION
+    )]
+    GameVersionMismatch { recorded: String },
+    #[error("corrupted replay file")]
+    Corrupted,
+}
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[error(
+    "replay desync at frame {frame}, the state hash is {state_hash:016x} instead of the recorded \
+     {recorded:016x}"
+)]
+pub struct ReplayDesync {
+    pub frame: u32,
+    pub recorded: u64,
+    pub state_hash: u64,
+}
+
+// Replay files are made of:
+// - the magic number and the format version, both readable whatever the version,
+// - the header and the replay data, serialized according to the format version,
+// - a hash of everything that precedes it.
+#[derive(Debug, Clone)]
+pub struct ReplayFile {
+    pub header: ReplayHeader,
+    pub replay_data: ReplayData,
+}
+
+impl ReplayFile {
+    pub fn new(replay_data: ReplayData, options: GameplayOptions) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        ReplayFile {
+            header: ReplayHeader {
+                game_version: GAME_VERSION.to_string(),
+                options,
+                timestamp,
+            },
+            replay_data,
+        }
+    }
+
+    pub fn last_replay_path() -> PathBuf {
+        Path::new(REPLAY_DIR_NAME).join(LAST_REPLAY_FILE_NAME)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, GameError> {
+        let mut bytes = Vec::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .map_err(Box::from)
+            .map_err(GameError::new_replay)?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), GameError> {
+        let bytes = self.to_bytes()?;
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(Box::from)
+                .map_err(GameError::new_replay)?;
+        }
+        File::create(path)
+            .and_then(|mut file| file.write_all(&bytes))
+            .map_err(Box::from)
+            .map_err(GameError::new_replay)
+    }
+
+    // Saves in the directory under a name made of the timestamp when the path is a directory.
+    pub fn save_in<P: AsRef<Path>>(&self, path: P) -> Result<(), GameError> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            self.save(path.join(format!("{}.rpl", self.header.timestamp)))
+        } else {
+            self.save(path)
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, GameError> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        let payload = bincode::serialize(&(&self.header, &self.replay_data))
+            .map_err(Box::from)
+            .map_err(GameError::new_replay)?;
+        bytes.extend_from_slice(&payload);
+        let hash = fnv1a(&bytes);
+        bytes.extend_from_slice(&hash.to_le_bytes());
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, GameError> {
+        let error = |err: ReplayFileError| GameError::new_replay(Box::new(err));
+        if bytes.len() < MAGIC.len() + 4 || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(error(ReplayFileError::NotAReplay));
+        }
+        let mut version = [0; 4];
+        version.copy_from_slice(&bytes[MAGIC.len()..MAGIC.len() + 4]);
+        let version = u32::from_le_bytes(version);
+        if version == 0 || version > FORMAT_VERSION {
+            return Err(error(ReplayFileError::UnsupportedVersion { version }));
+        }
+        if bytes.len() < MAGIC.len() + 4 + 8 {
+            return Err(error(ReplayFileError::Corrupted));
+        }
+        let (content, hash) = bytes.split_at(bytes.len() - 8);
+        let mut expected_hash = [0; 8];
+        expected_hash.copy_from_slice(hash);
+        if fnv1a(content) != u64::from_le_bytes(expected_hash) {
+            return Err(error(ReplayFileError::Corrupted));
+        }
+        let payload = &content[MAGIC.len() + 4..];
+        let (header, replay_data) = if version == 1 {
+            let (header, replay_data): (ReplayHeader, ReplayDataV1) =
+                bincode::deserialize(payload).map_err(|_| error(ReplayFileError::Corrupted))?;
+            (header, replay_data.migrate())
+        } else if version == 2 {
+            let (header, replay_data): (ReplayHeader, ReplayDataV2) =
+                bincode::deserialize(payload).map_err(|_| error(ReplayFileError::Corrupted))?;
+            (header, replay_data.migrate())
+        } else {
+            bincode::deserialize(payload).map_err(|_| error(ReplayFileError::Corrupted))?
+        };
+        if header.game_version != GAME_VERSION {
+            return Err(error(ReplayFileError::GameVersionMismatch {
+                recorded: header.game_version,
+            }));
+        }
+        Ok(ReplayFile {
+            header,
+            replay_data,
+        })
+    }
+}
+
+// Version 1 replays have no state hashes, they play without desync detection.
+#[derive(Deserialize)]
+struct ReplayDataV1 {
+    grade: u32,
+    level: f32,
+    seed: u64,
+    pad_record: RleVec<PadStateV2>,
+}
+
+impl ReplayDataV1 {
+    fn migrate(self) -> ReplayData {
+        ReplayData::default()
+            .grade(self.grade)
+            .level(self.level)
+            .seed(self.seed)
+            .pad_record(migrate_pad_record(&self.pad_record))
+    }
+}
+
+// Version 2 replays have no analog axes, they were all played with the digital directions.
+#[derive(Deserialize)]
+struct ReplayDataV2 {
+    grade: u32,
+    level: f32,
+    seed: u64,
+    pad_record: RleVec<PadStateV2>,
+    state_hashes: Vec<u64>,
+}
+
+impl ReplayDataV2 {
+    fn migrate(self) -> ReplayData {
+        ReplayData::default()
+            .grade(self.grade)
+            .level(self.level)
+            .seed(self.seed)
+            .pad_record(migrate_pad_record(&self.pad_record))
+            .state_hashes(self.state_hashes)
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Deserialize)]
+struct PadStateV2 {
+    direction: PadDirection,
+    buttons: PadButtons,
+}
+
+fn migrate_pad_record(pad_record: &RleVec<PadStateV2>) -> RleVec<PadState> {
+    let mut migrated = RleVec::new();
+    for state in pad_record.iter() {
+        migrated.push(PadState {
+            direction: state.direction,
+            buttons: state.buttons,
+            axes: None,
+        });
+    }
+    migrated
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReplayOutcome {
+    pub score: u32,
+    pub level: u32,
+    pub frame_count: u32,
+    pub first_desync: Option<ReplayDesync>,
+    // Of the world at the end of the game, which tells more changes apart than the score.
+    pub state_hash: u64,
+}
+
+impl fmt::Display for ReplayOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "SCORE: {}", self.score)?;
+        writeln!(f, "LEVEL: {}", self.level)?;
+        writeln!(f, "FRAMES: {}", self.frame_count)?;
+        writeln!(f, "HASH: {:016x}", self.state_hash)?;
+        if let Some(desync) = self.first_desync {
+            writeln!(f, "DESYNC: {}", desync.frame)?;
+        }
+        Ok(())
+    }
+}
+
+// Plays the replay the same way as the title screen does, but without any window nor sound,
+// until the ship is game over.
+pub fn verify_replay(replay_data: &ReplayData) -> Result<ReplayOutcome, GameError> {
+    let mut simulation = Simulation::new()?;
+    simulation.start_replay(replay_data);
+    while !simulation.is_game_over() {
+        simulation.step(PadState::default());
+    }
+
+    Ok(ReplayOutcome {
+        score: simulation.score(),
+        level: simulation.level() as u32,
+        frame_count: simulation.frame(),
+        first_desync: simulation.first_desync(),
+        state_hash: simulation.state_hash(),
+    })
+}
+
+#[test]
+fn should_reject_damaged_replay_files() {
+    let replay_data = ReplayData::default().grade(1).level(3.).seed(42);
+    let mut bytes = ReplayFile::new(replay_data, GameplayOptions { reverse: true })
+        .to_bytes()
+        .unwrap();
+    let replay_file = ReplayFile::from_bytes(&bytes).unwrap();
+    assert_eq!(replay_file.replay_data.start_params(), (1, 3., 42));
+    assert!(replay_file.header.options.reverse);
+
+    let last = bytes.len() - 9;
+    bytes[last] ^= 1;
+    assert!(ReplayFile::from_bytes(&bytes).is_err());
+    assert!(ReplayFile::from_bytes(b"{\"grade\":0}").is_err());
+}
+
+// Every replay of the golden directory comes with a `.expected` file holding the output of
+// `verify-replay`. Run the test with UPDATE_GOLDEN_REPLAYS set to rewrite them after an intended
+// gameplay change.
+#[test]
+fn golden_replays_should_keep_their_outcome() {
+    const GOLDEN_REPLAY_DIR: &str = "tests/replays";
+
+    let update = std::env::var_os("UPDATE_GOLDEN_REPLAYS").is_some();
+    let mut paths = fs::read_dir(GOLDEN_REPLAY_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "rpl"))
+        .collect::<Vec<PathBuf>>();
+    paths.sort();
+    assert!(
+        !paths.is_empty(),
+        "no golden replay in {}, nothing would be checked",
+        GOLDEN_REPLAY_DIR
+    );
+    let mut changed = Vec::new();
+    for path in paths {
+        let replay_file = ReplayFile::load(&path).unwrap();
+        let outcome = verify_replay(&replay_file.replay_data).unwrap().to_string();
+        let expected_path = path.with_extension("expected");
+        if update {
+            fs::write(&expected_path, &outcome).unwrap();
+        } else if fs::read_to_string(&expected_path).ok().as_ref() != Some(&outcome) {
+            changed.push(format!("{}:\n{}", path.display(), outcome));
+        }
+    }
+    assert!(
+        changed.is_empty(),
+        "golden replay outcomes changed:\n{}",
+        changed.join("\n")
+    );
+}
@@ -0,0 +1,106 @@
+use std::fmt;
+
+use crate::tt::actor::bullet::BulletPool;
+use crate::tt::actor::enemy::EnemyPool;
+use crate::tt::actor::float_letter::FloatLetterPool;
+use crate::tt::actor::particle::ParticlePool;
+use crate::tt::actor::shot::ShotPool;
+use crate::tt::camera::Camera;
+use crate::tt::errors::GameError;
+use crate::tt::manager::stage::StageManager;
+use crate::tt::pad::ReplayPosition;
+use crate::tt::ship::Ship;
+use crate::tt::sound::BgmState;
+use crate::tt::state::shared::SharedState;
+use crate::tt::tunnel::Tunnel;
+use crate::tt::{GeneralParams, MoreParams};
+
+type SavedWorld = (
+    ReplayPosition,
+    SharedState<'static>,
+    StageManager,
+    BgmState,
+    Camera,
+    Tunnel,
+    Ship,
+    ShotPool,
+    BulletPool,
+    EnemyPool,
+    ParticlePool,
+    FloatLetterPool,
+);
+
+// Everything the game logic changes while it runs, the random generators, the pools and the
+// runners of the bullets included, so that a game goes on from a save state exactly as it went on
+// from where the state was saved. What is loaded once and never changes, like the bullet patterns
+// and the sounds, is left out: the actors only hold handles on it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SaveState {
+    bytes: Vec<u8>,
+}
+
+impl SaveState {
+    pub fn save(params: &GeneralParams, more_params: &MoreParams) -> Result<Self, GameError> {
+        let bytes = bincode::serialize(&(
+            params.pad.replay_position(),
+            &*params.shared_state,
+            &*params.stage_manager,
+            params.sound_manager.bgm_state(),
+            &*params.camera,
+            &*params.tunnel,
+            &*more_params.ship,
+            &*more_params.shots,
+            &*more_params.bullets,
+            &*more_params.enemies,
+            &*more_params.particles,
+            &*more_params.float_letters,
+        ))
+        .map_err(Box::from)
+        .map_err(GameError::new_snapshot)?;
+        Ok(SaveState { bytes })
+    }
+
+    pub fn restore(
+        &self,
+        params: &mut GeneralParams,
+        more_params: &mut MoreParams,
+    ) -> Result<(), GameError> {
+        let (
+            replay_position,
+            shared_state,
+            stage_manager,
+            bgm_state,
+            camera,
+            tunnel,
+            ship,
+            shots,
+            bullets,
+            enemies,
+            particles,
+            float_letters,
+        ): SavedWorld = bincode::deserialize(&self.bytes)
+            .map_err(Box::from)
+            .map_err(GameError::new_snapshot)?;
+        params.pad.set_replay_position(replay_position);
+        *params.shared_state = shared_state;
+        *params.stage_manager = stage_manager;
+        params.sound_manager.set_bgm_state(bgm_state);
+        *params.camera = camera;
+        *params.tunnel = tunnel;
+        *more_params.ship = ship;
+        *more_params.shots = shots;
+        *more_params.bullets = bullets;
+        *more_params.enemies = enemies;
+        *more_params.particles = particles;
+        *more_params.float_letters = float_letters;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for SaveState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SaveState")
+            .field("len", &self.bytes.len())
+            .finish()
+    }
+}
@@ -1,13 +1,21 @@
 #[cfg(feature = "glutin_backend")]
 use glutin_window::GlutinWindow;
+use image::RgbaImage;
 use opengl_graphics::OpenGL;
 use piston::window::{OpenGLWindow, Size, WindowSettings};
 #[cfg(feature = "sdl_backend")]
 use sdl2_window::Sdl2Window;
+use std::rc::Rc;
 
 use crate::gl;
 use crate::tt::errors::GameError;
+use crate::tt::theme::Theme;
 use crate::util::color::{AlphaColor, Color};
+use crate::util::renderer::core_profile::CoreProfileRenderer;
+use crate::util::renderer::cpu::CpuRenderer;
+use crate::util::renderer::fixed_function::FixedFunctionRenderer;
+use crate::util::renderer::{MatrixMode, Primitive, Renderer};
+use crate::util::texture::Texture;
 
 #[cfg(feature = "glutin_backend")]
 type Window = GlutinWindow;
@@ -22,7 +30,10 @@ pub struct Screen {
     near_plane: f32,
     far_plane: f32,
     window: Option<Window>,
+    opengl: OpenGL,
+    renderer: Rc<dyn Renderer>,
     luminous_screen: Option<LuminousScreen>,
+    theme: Theme,
 }
 
 impl Screen {
@@ -35,11 +46,14 @@ impl Screen {
             near_plane: 0.1,
             far_plane: 1000.,
             window: None,
+            opengl: OpenGL::V2_1,
+            renderer: Rc::new(FixedFunctionRenderer),
             luminous_screen: if luminosity > 0. {
                 Some(LuminousScreen::new(luminosity))
             } else {
                 None
             },
+            theme: Theme::default(),
         }
     }
 
@@ -74,6 +88,26 @@ impl Screen {
         self.window.as_mut()
     }
 
+    pub fn renderer(&self) -> &dyn Renderer {
+        &*self.renderer
+    }
+
+    // For render resources which keep the renderer to draw or to be deleted later.
+    pub fn shared_renderer(&self) -> &Rc<dyn Renderer> {
+        &self.renderer
+    }
+
+    // The renderer has to be chosen before anything is drawn.
+    pub fn set_renderer(&mut self, renderer: Rc<dyn Renderer>) {
+        self.renderer = renderer;
+    }
+
+    // Has to be called before the window is created.
+    pub fn set_core_profile(&mut self) {
+        self.opengl = OpenGL::V3_3;
+        self.set_renderer(Rc::new(CoreProfileRenderer::new()));
+    }
+
     // Screen3D
 
     pub fn init_opengl(&mut self) -> Result<(), GameError> {
@@ -95,10 +129,21 @@ impl Screen {
         self.init_opengl_internal(window)
     }
 
+    // Draws in memory with the software rasterizer instead of in a window, e.g. to render
+    // replays on a machine without any GPU.
+    pub fn init_cpu(&mut self) -> Result<(), GameError> {
+        self.set_renderer(Rc::new(CpuRenderer::new(
+            self.size.width as usize,
+            self.size.height as usize,
+        )));
+        let size = self.size;
+        self.resized(size);
+        self.init()
+    }
+
     fn window_settings(&self) -> WindowSettings {
-        let opengl = OpenGL::V2_1;
         WindowSettings::new("Torus Trooper", self.size)
-            .graphics_api(opengl)
+            .graphics_api(self.opengl)
             .vsync(true)
             .fullscreen(self.fullscreen)
             .exit_on_esc(false)
@@ -110,8 +155,7 @@ impl Screen {
         self.window = Some(window);
         let size = self.size;
         self.resized(size);
-        self.init();
-        Ok(())
+        self.init()
     }
 
     fn physical_size_to_ortho_size(physical_size: Size) -> Size {
@@ -132,36 +176,35 @@ impl Screen {
 
     fn screen_resized(&self) {
         let (p_width, p_height) = self.physical_size();
-        unsafe {
-            gl::Viewport(0, 0, p_width as i32, p_height as i32);
-            gl::MatrixMode(gl::GL_PROJECTION);
-            gl::LoadIdentity();
-            //gluPerspective(45.0f, cast(GLfloat) width / cast(GLfloat) height, nearPlane, farPlane);
-            let ratio_threshold = 480. / 640.;
-            let screen_ratio = p_height / p_width;
-            if screen_ratio >= ratio_threshold {
-                gl::Frustum(
-                    f64::from(-self.near_plane),
-                    f64::from(self.near_plane),
-                    -f64::from(self.near_plane) * screen_ratio,
-                    f64::from(self.near_plane) * screen_ratio,
-                    0.1,
-                    f64::from(self.far_plane),
-                );
-            } else {
-                // This allows to see at least what can be seen horizontally and vertically
-                // with the default ratio -- arnodb
-                gl::Frustum(
-                    -f64::from(self.near_plane) * ratio_threshold / screen_ratio,
-                    f64::from(self.near_plane) * ratio_threshold / screen_ratio,
-                    -f64::from(self.near_plane) * ratio_threshold,
-                    f64::from(self.near_plane) * ratio_threshold,
-                    0.1,
-                    f64::from(self.far_plane),
-                );
-            }
-            gl::MatrixMode(gl::GL_MODELVIEW);
+        let renderer = self.renderer();
+        renderer.viewport(0, 0, p_width as i32, p_height as i32);
+        renderer.matrix_mode(MatrixMode::Projection);
+        renderer.load_identity();
+        //gluPerspective(45.0f, cast(GLfloat) width / cast(GLfloat) height, nearPlane, farPlane);
+        let ratio_threshold = 480. / 640.;
+        let screen_ratio = (p_height / p_width) as f32;
+        if screen_ratio >= ratio_threshold {
+            renderer.frustum(
+                -self.near_plane,
+                self.near_plane,
+                -self.near_plane * screen_ratio,
+                self.near_plane * screen_ratio,
+                0.1,
+                self.far_plane,
+            );
+        } else {
+            // This allows to see at least what can be seen horizontally and vertically
+            // with the default ratio -- arnodb
+            renderer.frustum(
+                -self.near_plane * ratio_threshold / screen_ratio,
+                self.near_plane * ratio_threshold / screen_ratio,
+                -self.near_plane * ratio_threshold,
+                self.near_plane * ratio_threshold,
+                0.1,
+                self.far_plane,
+            );
         }
+        renderer.matrix_mode(MatrixMode::ModelView);
     }
 
     pub fn resized<S: Into<Size>>(&mut self, size: S) {
@@ -170,93 +213,104 @@ impl Screen {
         self.screen_resized();
     }
 
+    // To be set before anything is drawn, the colours being baked in the display lists.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    pub fn brightness(&self) -> f32 {
+        self.brightness
+    }
+
     pub fn set_color<C: Into<Color>>(&self, color: C) {
         self.set_alpha_color(color.into())
     }
     pub fn set_alpha_color<C: Into<AlphaColor>>(&self, color: C) {
+        Screen::set_alpha_color_with_brightness(self.renderer(), color, self.brightness)
+    }
+
+    // For render resources compiled lazily, i.e. when the screen is not at hand any more.
+    pub fn set_alpha_color_with_brightness<C: Into<AlphaColor>>(
+        renderer: &dyn Renderer,
+        color: C,
+        brightness: f32,
+    ) {
         let color = color.into();
-        unsafe {
-            gl::Color4f(
-                color.red * self.brightness,
-                color.green * self.brightness,
-                color.blue * self.brightness,
-                color.alpha,
-            );
-        }
+        renderer.set_color(
+            color.red * brightness,
+            color.green * brightness,
+            color.blue * brightness,
+            color.alpha,
+        );
     }
 
     pub fn set_clear_color_rgb(&self, r: f32, g: f32, b: f32) {
         self.set_clear_color_rgba(r, g, b, 1.)
     }
     pub fn set_clear_color_rgba(&self, r: f32, g: f32, b: f32, a: f32) {
-        unsafe {
-            gl::ClearColor(
-                r * self.brightness,
-                g * self.brightness,
-                b * self.brightness,
-                a,
-            );
-        }
+        self.renderer.set_clear_color(
+            r * self.brightness,
+            g * self.brightness,
+            b * self.brightness,
+            a,
+        );
     }
 
     // Screen
 
-    pub fn clear() {
-        unsafe {
-            gl::Clear(gl::GL_COLOR_BUFFER_BIT);
-        }
+    pub fn clear(&self) {
+        self.renderer.clear();
     }
 
-    fn init(&mut self) {
-        unsafe {
-            gl::LineWidth(1.);
-            gl::BlendFunc(gl::GL_SRC_ALPHA, gl::GL_ONE);
-            gl::Enable(gl::GL_LINE_SMOOTH);
-            gl::Enable(gl::GL_BLEND);
-            gl::Disable(gl::GL_COLOR_MATERIAL);
-            gl::Disable(gl::GL_CULL_FACE);
-            gl::Disable(gl::GL_DEPTH_TEST);
-            gl::Disable(gl::GL_LIGHTING);
-            gl::Disable(gl::GL_TEXTURE_2D);
-        }
+    // What has been drawn since the screen was cleared.
+    pub fn capture(&self) -> RgbaImage {
+        let (p_width, p_height) = self.physical_size();
+        self.renderer.read_pixels(p_width as u32, p_height as u32)
+    }
+
+    fn init(&mut self) -> Result<(), GameError> {
+        self.renderer.init()?;
         self.set_clear_color_rgba(0., 0., 0., 1.);
         if let Some(luminous_screen) = &mut self.luminous_screen {
-            luminous_screen.init();
+            luminous_screen.init(&self.renderer);
         }
         self.far_plane = 10000.;
         self.screen_resized();
+        Ok(())
     }
 
     pub fn view_ortho_fixed(&self) {
-        Screen::view_ortho(self.ortho_size.width as f32, self.ortho_size.height as f32);
-    }
-
-    pub fn view_ortho(width: f32, height: f32) {
-        unsafe {
-            gl::MatrixMode(gl::GL_PROJECTION);
-            gl::PushMatrix();
-            gl::LoadIdentity();
-            gl::Ortho(0., f64::from(width), f64::from(height), 0., -1., 1.);
-            gl::MatrixMode(gl::GL_MODELVIEW);
-            gl::PushMatrix();
-            gl::LoadIdentity();
-        }
+        self.view_ortho(self.ortho_size.width as f32, self.ortho_size.height as f32);
     }
 
-    pub fn view_perspective() {
-        unsafe {
-            gl::MatrixMode(gl::GL_PROJECTION);
-            gl::PopMatrix();
-            gl::MatrixMode(gl::GL_MODELVIEW);
-            gl::PopMatrix();
-        }
+    pub fn view_ortho(&self, width: f32, height: f32) {
+        let renderer = self.renderer();
+        renderer.matrix_mode(MatrixMode::Projection);
+        renderer.push_matrix();
+        renderer.load_identity();
+        renderer.ortho(0., width, height, 0., -1., 1.);
+        renderer.matrix_mode(MatrixMode::ModelView);
+        renderer.push_matrix();
+        renderer.load_identity();
+    }
+
+    pub fn view_perspective(&self) {
+        let renderer = self.renderer();
+        renderer.matrix_mode(MatrixMode::Projection);
+        renderer.pop_matrix();
+        renderer.matrix_mode(MatrixMode::ModelView);
+        renderer.pop_matrix();
     }
 
     // Luminous
 
     pub fn start_render_to_luminous_screen(&self) -> bool {
         if let Some(luminous_screen) = &self.luminous_screen {
-            luminous_screen.start_render();
+            luminous_screen.start_render(self);
             true
         } else {
             false
@@ -283,119 +337,84 @@ const LM_OFS: [(f32, f32); 2] = [(-2., -1.), (2., 1.)];
 const LM_OFS_BS: f32 = 3.;
 
 pub struct LuminousScreen {
-    luminous_texture: u32,
-    td: Box<[u32; LUMINOUS_TEXTURE_WIDTH * LUMINOUS_TEXTURE_HEIGHT * 4]>,
+    // The texture is not created when running headless.
+    luminous_texture: Option<Texture>,
     luminosity: f32,
 }
 
 impl LuminousScreen {
     fn new(luminosity: f32) -> Self {
         LuminousScreen {
-            luminous_texture: 0,
-            td: Box::new([0 as u32; LUMINOUS_TEXTURE_WIDTH * LUMINOUS_TEXTURE_HEIGHT * 4]),
+            luminous_texture: None,
             luminosity,
         }
     }
 
-    fn init(&mut self) {
-        self.make_kuminous_texture();
-    }
-
-    fn make_kuminous_texture(&mut self) {
-        unsafe {
-            gl::GenTextures(1, &mut self.luminous_texture);
-            gl::BindTexture(gl::GL_TEXTURE_2D, self.luminous_texture);
-            gl::TexImage2D(
-                gl::GL_TEXTURE_2D,
-                0,
-                4,
-                LUMINOUS_TEXTURE_WIDTH as i32,
-                LUMINOUS_TEXTURE_HEIGHT as i32,
-                0,
-                gl::GL_RGBA,
-                gl::GL_UNSIGNED_BYTE,
-                self.td.as_ptr() as *const std::ffi::c_void,
-            );
-            gl::TexParameteri(
-                gl::GL_TEXTURE_2D,
-                gl::GL_TEXTURE_MIN_FILTER,
-                gl::GL_LINEAR as i32,
-            );
-            gl::TexParameteri(
-                gl::GL_TEXTURE_2D,
-                gl::GL_TEXTURE_MAG_FILTER,
-                gl::GL_LINEAR as i32,
-            );
-        }
+    fn init(&mut self, renderer: &Rc<dyn Renderer>) {
+        self.make_luminous_texture(renderer);
+    }
+
+    fn make_luminous_texture(&mut self, renderer: &Rc<dyn Renderer>) {
+        let td = vec![0; LUMINOUS_TEXTURE_WIDTH * LUMINOUS_TEXTURE_HEIGHT * 4];
+        self.luminous_texture = Some(Texture::new(
+            renderer,
+            LUMINOUS_TEXTURE_WIDTH as u32,
+            LUMINOUS_TEXTURE_HEIGHT as u32,
+            &td,
+        ));
     }
 
-    fn start_render(&self) {
-        unsafe {
-            gl::Viewport(
-                0,
-                0,
-                LUMINOUS_TEXTURE_WIDTH as i32,
-                LUMINOUS_TEXTURE_HEIGHT as i32,
+    fn start_render(&self, screen: &Screen) {
+        if let Some(luminous_texture) = &self.luminous_texture {
+            screen.renderer().start_render_to_texture(
+                luminous_texture.num(),
+                LUMINOUS_TEXTURE_WIDTH as u32,
+                LUMINOUS_TEXTURE_HEIGHT as u32,
             );
         }
     }
 
     fn end_render(&self, screen: &Screen) {
-        unsafe {
-            gl::BindTexture(gl::GL_TEXTURE_2D, self.luminous_texture);
-            gl::CopyTexImage2D(
-                gl::GL_TEXTURE_2D,
-                0,
-                gl::GL_RGBA,
-                0,
-                0,
-                LUMINOUS_TEXTURE_WIDTH as i32,
-                LUMINOUS_TEXTURE_HEIGHT as i32,
-                0,
+        let renderer = screen.renderer();
+        if let Some(luminous_texture) = &self.luminous_texture {
+            renderer.end_render_to_texture(
+                luminous_texture.num(),
+                LUMINOUS_TEXTURE_WIDTH as u32,
+                LUMINOUS_TEXTURE_HEIGHT as u32,
             );
         }
         let (p_width, p_height) = screen.physical_size();
-        unsafe {
-            gl::Viewport(0, 0, p_width as i32, p_height as i32);
-        }
+        renderer.viewport(0, 0, p_width as i32, p_height as i32);
     }
 
     fn draw(&self, screen: &Screen) {
-        unsafe {
-            gl::Enable(gl::GL_TEXTURE_2D);
-            gl::BindTexture(gl::GL_TEXTURE_2D, self.luminous_texture);
-        }
+        let luminous_texture = match &self.luminous_texture {
+            Some(luminous_texture) => luminous_texture,
+            None => return,
+        };
+        let renderer = screen.renderer();
+        luminous_texture.bind();
         let (p_width, p_height) = screen.physical_size();
-        Screen::view_ortho(p_width as f32, p_height as f32);
-        unsafe {
-            gl::Color4f(1., 0.8, 0.9, self.luminosity);
-            gl::Begin(gl::GL_QUADS);
-            for lm_ofs in &LM_OFS {
-                gl::TexCoord2f(0., 1.);
-                gl::Vertex2f(lm_ofs.0 * LM_OFS_BS, lm_ofs.1 * LM_OFS_BS);
-                gl::TexCoord2f(0., 0.);
-                gl::Vertex2f(lm_ofs.0 * LM_OFS_BS, p_height as f32 + lm_ofs.1 * LM_OFS_BS);
-                gl::TexCoord2f(1., 0.);
-                gl::Vertex2f(
-                    p_width as f32 + lm_ofs.0 * LM_OFS_BS,
-                    p_height as f32 + lm_ofs.1 * LM_OFS_BS,
-                );
-                gl::TexCoord2f(1., 1.);
-                gl::Vertex2f(p_width as f32 + lm_ofs.0 * LM_OFS_BS, lm_ofs.1 * LM_OFS_BS);
-            }
-            gl::End();
-        }
-        Screen::view_perspective();
-        unsafe {
-            gl::Disable(gl::GL_TEXTURE_2D);
-        }
-    }
-}
-
-impl Drop for LuminousScreen {
-    fn drop(&mut self) {
-        unsafe {
-            gl::DeleteTextures(1, &self.luminous_texture);
+        let (p_width, p_height) = (p_width as f32, p_height as f32);
+        screen.view_ortho(p_width, p_height);
+        renderer.set_color(1., 0.8, 0.9, self.luminosity);
+        renderer.begin(Primitive::Quads);
+        for lm_ofs in &LM_OFS {
+            renderer.tex_coord(0., 1.);
+            renderer.vertex(lm_ofs.0 * LM_OFS_BS, lm_ofs.1 * LM_OFS_BS, 0.);
+            renderer.tex_coord(0., 0.);
+            renderer.vertex(lm_ofs.0 * LM_OFS_BS, p_height + lm_ofs.1 * LM_OFS_BS, 0.);
+            renderer.tex_coord(1., 0.);
+            renderer.vertex(
+                p_width + lm_ofs.0 * LM_OFS_BS,
+                p_height + lm_ofs.1 * LM_OFS_BS,
+                0.,
+            );
+            renderer.tex_coord(1., 1.);
+            renderer.vertex(p_width + lm_ofs.0 * LM_OFS_BS, lm_ofs.1 * LM_OFS_BS, 0.);
         }
+        renderer.end();
+        screen.view_perspective();
+        renderer.bind_texture(None);
     }
 }
@@ -1,60 +1,67 @@
-use crate::gl;
-
 use crate::tt::screen::Screen;
-use crate::util::display_list::DisplayList;
+use crate::util::display_list::LazyDisplayList;
+use crate::util::renderer::Primitive;
 
 use super::Drawable;
 
 const COLOR_RGB: (f32, f32, f32) = (1., 0.9, 0.5);
 
+#[derive(Serialize, Deserialize)]
 pub struct BitShape {
-    display_list: DisplayList,
+    display_list: LazyDisplayList,
 }
 
+#[allow(clippy::new_without_default)]
 impl BitShape {
-    pub fn new(screen: &Screen) -> BitShape {
-        let mut display_list = DisplayList::new(1);
-        display_list.new_list();
+    pub fn new() -> BitShape {
+        BitShape {
+            display_list: LazyDisplayList::new(1),
+        }
+    }
+
+    fn create_display_list(screen: &Screen) {
+        let renderer = screen.renderer();
         for i in 0..4 {
             let mut d = i as f32 * std::f32::consts::PI / 2. + std::f32::consts::PI / 4.;
-            unsafe {
-                screen.set_color(COLOR_RGB);
-                gl::Begin(gl::GL_LINE_LOOP);
-                gl::Vertex3f(f32::sin(d - 0.3), -0.8, f32::cos(d - 0.3));
-                gl::Vertex3f(f32::sin(d + 0.3), -0.8, f32::cos(d + 0.3));
-                gl::Vertex3f(f32::sin(d + 0.3), 0.8, f32::cos(d + 0.3));
-                gl::Vertex3f(f32::sin(d - 0.3), 0.8, f32::cos(d - 0.3));
-                gl::End();
-                d += std::f32::consts::PI / 4.;
-                gl::Begin(gl::GL_LINE_LOOP);
-                gl::Vertex3f(f32::sin(d - 0.3) * 2., -0.2, f32::cos(d - 0.3) * 2.);
-                gl::Vertex3f(f32::sin(d + 0.3) * 2., -0.2, f32::cos(d + 0.3) * 2.);
-                gl::Vertex3f(f32::sin(d + 0.3) * 2., 0.2, f32::cos(d + 0.3) * 2.);
-                gl::Vertex3f(f32::sin(d - 0.3) * 2., 0.2, f32::cos(d - 0.3) * 2.);
-                gl::End();
-                d -= std::f32::consts::PI / 4.;
-                gl::Begin(gl::GL_TRIANGLE_FAN);
-                gl::Vertex3f(f32::sin(d - 0.3), -0.8, f32::cos(d - 0.3));
-                gl::Vertex3f(f32::sin(d + 0.3), -0.8, f32::cos(d + 0.3));
-                gl::Vertex3f(f32::sin(d + 0.3), 0.8, f32::cos(d + 0.3));
-                gl::Vertex3f(f32::sin(d - 0.3), 0.8, f32::cos(d - 0.3));
-                gl::End();
-                d += std::f32::consts::PI / 4.;
-                gl::Begin(gl::GL_TRIANGLE_FAN);
-                gl::Vertex3f(f32::sin(d - 0.3) * 2., -0.2, f32::cos(d - 0.3) * 2.);
-                gl::Vertex3f(f32::sin(d + 0.3) * 2., -0.2, f32::cos(d + 0.3) * 2.);
-                gl::Vertex3f(f32::sin(d + 0.3) * 2., 0.2, f32::cos(d + 0.3) * 2.);
-                gl::Vertex3f(f32::sin(d - 0.3) * 2., 0.2, f32::cos(d - 0.3) * 2.);
-                gl::End();
-            }
+            screen.set_color(COLOR_RGB);
+            renderer.begin(Primitive::LineLoop);
+            renderer.vertex(f32::sin(d - 0.3), -0.8, f32::cos(d - 0.3));
+            renderer.vertex(f32::sin(d + 0.3), -0.8, f32::cos(d + 0.3));
+            renderer.vertex(f32::sin(d + 0.3), 0.8, f32::cos(d + 0.3));
+            renderer.vertex(f32::sin(d - 0.3), 0.8, f32::cos(d - 0.3));
+            renderer.end();
+            d += std::f32::consts::PI / 4.;
+            renderer.begin(Primitive::LineLoop);
+            renderer.vertex(f32::sin(d - 0.3) * 2., -0.2, f32::cos(d - 0.3) * 2.);
+            renderer.vertex(f32::sin(d + 0.3) * 2., -0.2, f32::cos(d + 0.3) * 2.);
+            renderer.vertex(f32::sin(d + 0.3) * 2., 0.2, f32::cos(d + 0.3) * 2.);
+            renderer.vertex(f32::sin(d - 0.3) * 2., 0.2, f32::cos(d - 0.3) * 2.);
+            renderer.end();
+            d -= std::f32::consts::PI / 4.;
+            renderer.begin(Primitive::TriangleFan);
+            renderer.vertex(f32::sin(d - 0.3), -0.8, f32::cos(d - 0.3));
+            renderer.vertex(f32::sin(d + 0.3), -0.8, f32::cos(d + 0.3));
+            renderer.vertex(f32::sin(d + 0.3), 0.8, f32::cos(d + 0.3));
+            renderer.vertex(f32::sin(d - 0.3), 0.8, f32::cos(d - 0.3));
+            renderer.end();
+            d += std::f32::consts::PI / 4.;
+            renderer.begin(Primitive::TriangleFan);
+            renderer.vertex(f32::sin(d - 0.3) * 2., -0.2, f32::cos(d - 0.3) * 2.);
+            renderer.vertex(f32::sin(d + 0.3) * 2., -0.2, f32::cos(d + 0.3) * 2.);
+            renderer.vertex(f32::sin(d + 0.3) * 2., 0.2, f32::cos(d + 0.3) * 2.);
+            renderer.vertex(f32::sin(d - 0.3) * 2., 0.2, f32::cos(d - 0.3) * 2.);
+            renderer.end();
         }
-        display_list.end_list();
-        BitShape { display_list }
     }
 }
 
 impl Drawable for BitShape {
-    fn draw(&self) {
-        self.display_list.call(0);
+    fn draw(&self, screen: &Screen) {
+        self.display_list
+            .call(screen.shared_renderer(), 0, |display_list| {
+                display_list.new_list();
+                BitShape::create_display_list(screen);
+                display_list.end_list();
+            });
     }
 }
@@ -1,14 +1,11 @@
-use crate::gl;
-
 use crate::tt::screen::Screen;
 use crate::util::color::Color;
-use crate::util::display_list::DisplayList;
+use crate::util::display_list::LazyDisplayList;
+use crate::util::renderer::Primitive;
 use crate::util::vector::Vector3;
 
 use super::Drawable;
 
-const COLOR_RGB: (f32, f32, f32) = (1., 0.7, 0.8);
-
 const SQUARE_POINT_DAT: [[(f32, f32, f32); 4]; 6] = [
     [(-1., -1., 1.), (1., -1., 1.), (1., 1., 1.), (-1., 1., 1.)],
     [
@@ -52,13 +49,35 @@ const BAR_POINT_DAT: [[(f32, f32, f32); 4]; 5] = [
 ];
 
 pub struct BulletShape {
-    display_list: DisplayList,
+    wire_shape: bool,
+    create: fn(bool, &Screen),
+    display_list: LazyDisplayList,
 }
 
 impl BulletShape {
-    pub fn new_square(wire_shape: bool, screen: &Screen) -> Self {
-        let mut display_list = DisplayList::new(1);
-        display_list.new_list();
+    fn new(wire_shape: bool, create: fn(bool, &Screen)) -> Self {
+        BulletShape {
+            wire_shape,
+            create,
+            display_list: LazyDisplayList::new(1),
+        }
+    }
+
+    pub fn new_square(wire_shape: bool) -> Self {
+        BulletShape::new(wire_shape, BulletShape::create_square)
+    }
+
+    pub fn new_triangle(wire_shape: bool) -> Self {
+        BulletShape::new(wire_shape, BulletShape::create_triangle)
+    }
+
+    pub fn new_bar(wire_shape: bool) -> Self {
+        BulletShape::new(wire_shape, BulletShape::create_bar)
+    }
+
+    fn create_square(wire_shape: bool, screen: &Screen) {
+        let renderer = screen.renderer();
+        let color_rgb = screen.theme().bullet;
         let mut p = [Vector3::default(); 4];
         let mut np = [Vector3::default(); 4];
         for square_point_dat in &SQUARE_POINT_DAT {
@@ -72,39 +91,29 @@ impl BulletShape {
                 np[j] = Vector3::blend(p[j], cp, 0.6);
             }
             if !wire_shape {
-                screen.set_color(COLOR_RGB);
+                screen.set_color(color_rgb);
             } else {
-                screen.set_color((COLOR_RGB.0 * 0.6, COLOR_RGB.1, COLOR_RGB.2));
-            }
-            unsafe {
-                gl::Begin(gl::GL_LINE_LOOP);
+                screen.set_color((color_rgb.0 * 0.6, color_rgb.1, color_rgb.2));
             }
+            renderer.begin(Primitive::LineLoop);
             for np_item in &np {
-                np_item.gl_vertex();
-            }
-            unsafe {
-                gl::End();
+                np_item.vertex(renderer);
             }
+            renderer.end();
             if !wire_shape {
-                unsafe {
-                    gl::Begin(gl::GL_TRIANGLE_FAN);
-                }
-                screen.set_color(Color::from(COLOR_RGB) * 0.7);
+                renderer.begin(Primitive::TriangleFan);
+                screen.set_color(Color::from(color_rgb) * 0.7);
                 for np_item in &np {
-                    np_item.gl_vertex();
-                }
-                unsafe {
-                    gl::End();
+                    np_item.vertex(renderer);
                 }
+                renderer.end();
             }
         }
-        display_list.end_list();
-        BulletShape { display_list }
     }
 
-    pub fn new_triangle(wire_shape: bool, screen: &Screen) -> Self {
-        let mut display_list = DisplayList::new(1);
-        display_list.new_list();
+    fn create_triangle(wire_shape: bool, screen: &Screen) {
+        let renderer = screen.renderer();
+        let color_rgb = screen.theme().bullet;
         for i in 0..3 {
             let d = std::f32::consts::PI * 2. / 3. * i as f32;
             let p1 = Vector3::new_at(0., 0., 2.5);
@@ -121,40 +130,30 @@ impl BulletShape {
             let np2 = Vector3::blend(p2, cp, 0.6);
             let np3 = Vector3::blend(p3, cp, 0.6);
             if !wire_shape {
-                screen.set_color(COLOR_RGB);
+                screen.set_color(color_rgb);
             } else {
-                screen.set_color((COLOR_RGB.0 * 0.6, COLOR_RGB.1, COLOR_RGB.2));
-            }
-            unsafe {
-                gl::Begin(gl::GL_LINE_LOOP);
-            }
-            np1.gl_vertex();
-            np2.gl_vertex();
-            np3.gl_vertex();
-            unsafe {
-                gl::End();
+                screen.set_color((color_rgb.0 * 0.6, color_rgb.1, color_rgb.2));
             }
+            renderer.begin(Primitive::LineLoop);
+            np1.vertex(renderer);
+            np2.vertex(renderer);
+            np3.vertex(renderer);
+            renderer.end();
             if !wire_shape {
-                unsafe {
-                    gl::Begin(gl::GL_TRIANGLE_FAN);
-                }
-                screen.set_color(Color::from(COLOR_RGB) * 0.7);
-                np1.gl_vertex();
-                screen.set_color(Color::from(COLOR_RGB) * 0.4);
-                np2.gl_vertex();
-                np3.gl_vertex();
-                unsafe {
-                    gl::End();
-                }
+                renderer.begin(Primitive::TriangleFan);
+                screen.set_color(Color::from(color_rgb) * 0.7);
+                np1.vertex(renderer);
+                screen.set_color(Color::from(color_rgb) * 0.4);
+                np2.vertex(renderer);
+                np3.vertex(renderer);
+                renderer.end();
             }
         }
-        display_list.end_list();
-        BulletShape { display_list }
     }
 
-    pub fn new_bar(wire_shape: bool, screen: &Screen) -> Self {
-        let mut display_list = DisplayList::new(1);
-        display_list.new_list();
+    fn create_bar(wire_shape: bool, screen: &Screen) {
+        let renderer = screen.renderer();
+        let color_rgb = screen.theme().bullet;
         let mut p = [Vector3::default(); 4];
         let mut np = [Vector3::default(); 4];
         for bar_point_dat in &BAR_POINT_DAT {
@@ -169,39 +168,34 @@ impl BulletShape {
                 np[j] = Vector3::blend(p[j], cp, 0.6);
             }
             if !wire_shape {
-                screen.set_color(COLOR_RGB);
+                screen.set_color(color_rgb);
             } else {
-                screen.set_color((COLOR_RGB.0 * 0.6, COLOR_RGB.1, COLOR_RGB.2));
-            }
-            unsafe {
-                gl::Begin(gl::GL_LINE_LOOP);
+                screen.set_color((color_rgb.0 * 0.6, color_rgb.1, color_rgb.2));
             }
+            renderer.begin(Primitive::LineLoop);
             for np_item in &np {
-                np_item.gl_vertex();
-            }
-            unsafe {
-                gl::End();
+                np_item.vertex(renderer);
             }
+            renderer.end();
             if !wire_shape {
-                unsafe {
-                    gl::Begin(gl::GL_TRIANGLE_FAN);
-                }
-                screen.set_color(Color::from(COLOR_RGB) * 0.7);
+                renderer.begin(Primitive::TriangleFan);
+                screen.set_color(Color::from(color_rgb) * 0.7);
                 for np_item in &np {
-                    np_item.gl_vertex();
-                }
-                unsafe {
-                    gl::End();
+                    np_item.vertex(renderer);
                 }
+                renderer.end();
             }
         }
-        display_list.end_list();
-        BulletShape { display_list }
     }
 }
 
 impl Drawable for BulletShape {
-    fn draw(&self) {
-        self.display_list.call(0);
+    fn draw(&self, screen: &Screen) {
+        self.display_list
+            .call(screen.shared_renderer(), 0, |display_list| {
+                display_list.new_list();
+                (self.create)(self.wire_shape, screen);
+                display_list.end_list();
+            });
     }
 }
@@ -6,12 +6,11 @@ pub mod structure;
 
 use std::rc::Rc;
 
+use crate::tt::screen::Screen;
 use crate::util::vector::Vector;
 
-use crate::gl;
-
 pub trait Drawable {
-    fn draw(&self);
+    fn draw(&self, screen: &Screen);
 }
 
 pub trait Collidable {
@@ -29,6 +28,7 @@ pub trait Collidable {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ResizableDrawable<T> {
     shape: Rc<T>,
     size: f32,
@@ -48,11 +48,9 @@ impl<T: Drawable> ResizableDrawable<T> {
 }
 
 impl<T: Drawable> Drawable for ResizableDrawable<T> {
-    fn draw(&self) {
-        unsafe {
-            gl::Scalef(self.size, self.size, self.size);
-        }
-        self.shape.draw();
+    fn draw(&self, screen: &Screen) {
+        screen.renderer().scale(self.size, self.size, self.size);
+        self.shape.draw(screen);
     }
 }
 
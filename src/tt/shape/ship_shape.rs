@@ -3,22 +3,24 @@ use std::vec::Vec;
 use crate::tt::actor::particle::{ParticlePool, ParticleSpec};
 use crate::tt::screen::Screen;
 use crate::tt::tunnel::Tunnel;
-use crate::util::display_list::DisplayList;
+use crate::util::display_list::LazyDisplayList;
 use crate::util::rand::Rand;
 use crate::util::vector::Vector;
 
 use super::structure::{self, Structure};
 use super::{Collidable, Drawable};
 
+#[derive(Serialize, Deserialize)]
 pub struct ShipShape {
     collision: Vector,
-    display_list: DisplayList,
+    structure: Vec<Structure>,
+    display_list: LazyDisplayList,
     rocket_x: Vec<f32>,
     color: usize,
 }
 
 impl ShipShape {
-    pub fn new_small(damaged: bool, screen: &Screen, seed: u64) -> Self {
+    pub fn new_small(damaged: bool, seed: u64) -> Self {
         let mut rand = Rand::new(seed);
         let mut collision = Vector::default();
         let shaft_num = 1 + rand.gen_usize(2);
@@ -99,16 +101,16 @@ impl ShipShape {
         }
         collision.x *= 0.1;
         collision.y *= 1.2;
-        let display_list = ShipShape::create_display_list(&structure, screen);
         ShipShape {
             collision,
-            display_list,
+            structure,
+            display_list: LazyDisplayList::new(1),
             rocket_x,
             color: cl,
         }
     }
 
-    pub fn new_medium(damaged: bool, screen: &Screen, seed: u64) -> Self {
+    pub fn new_medium(damaged: bool, seed: u64) -> Self {
         let mut rand = Rand::new(seed);
         let mut collision = Vector::default();
         let shaft_num = 3 + rand.gen_usize(2);
@@ -262,16 +264,16 @@ impl ShipShape {
         }
         collision.x *= 0.1;
         collision.y *= 1.2;
-        let display_list = ShipShape::create_display_list(&structure, screen);
         ShipShape {
             collision,
-            display_list,
+            structure,
+            display_list: LazyDisplayList::new(1),
             rocket_x,
             color: cl,
         }
     }
 
-    pub fn new_large(damaged: bool, screen: &Screen, seed: u64) -> Self {
+    pub fn new_large(damaged: bool, seed: u64) -> Self {
         let mut rand = Rand::new(seed);
         let mut collision = Vector::default();
         let shaft_num = 5 + rand.gen_usize(2);
@@ -497,25 +499,15 @@ impl ShipShape {
         }
         collision.x *= 0.1;
         collision.y *= 1.2;
-        let display_list = ShipShape::create_display_list(&structure, screen);
         ShipShape {
             collision,
-            display_list,
+            structure,
+            display_list: LazyDisplayList::new(1),
             rocket_x,
             color: cl,
         }
     }
 
-    fn create_display_list(structure: &[Structure], screen: &Screen) -> DisplayList {
-        let mut display_list = DisplayList::new(1);
-        display_list.new_list();
-        for st in structure {
-            st.create_display_list(screen);
-        }
-        display_list.end_list();
-        display_list
-    }
-
     fn create_shaft(
         rand: &mut Rand,
         ox: f32,
@@ -647,8 +639,15 @@ impl ShipShape {
 }
 
 impl Drawable for ShipShape {
-    fn draw(&self) {
-        self.display_list.call(0);
+    fn draw(&self, screen: &Screen) {
+        self.display_list
+            .call(screen.shared_renderer(), 0, |display_list| {
+                display_list.new_list();
+                for st in &self.structure {
+                    st.create_display_list(screen);
+                }
+                display_list.end_list();
+            });
     }
 }
 
@@ -1,7 +1,6 @@
-use crate::gl;
-
 use crate::tt::screen::Screen;
-use crate::util::display_list::DisplayList;
+use crate::util::display_list::LazyDisplayList;
+use crate::util::renderer::Primitive;
 use crate::util::vector::Vector;
 
 use super::{Collidable, Drawable};
@@ -9,65 +8,70 @@ use crate::util::color::Color;
 
 const COLOR_RGB: (f32, f32, f32) = (0.8, 1., 0.7);
 
+#[derive(Serialize, Deserialize)]
 pub struct ShotShape {
+    charge: bool,
     collision: Vector,
-    display_list: DisplayList,
+    display_list: LazyDisplayList,
 }
 
 impl ShotShape {
-    pub fn new(charge: bool, screen: &Screen) -> ShotShape {
-        let mut display_list = DisplayList::new(1);
-        display_list.new_list();
+    pub fn new(charge: bool) -> ShotShape {
+        ShotShape {
+            charge,
+            collision: Vector::new_at(0.15, 0.3),
+            display_list: LazyDisplayList::new(1),
+        }
+    }
+
+    fn create_display_list(charge: bool, screen: &Screen) {
+        let renderer = screen.renderer();
         if charge {
             for i in 0..8 {
                 let d = i as f32 * std::f32::consts::PI / 4.;
-                unsafe {
-                    gl::Begin(gl::GL_TRIANGLES);
-                    screen.set_color(COLOR_RGB);
-                    gl::Vertex3f(f32::sin(d) * 0.1, f32::cos(d) * 0.1, 0.2);
-                    gl::Vertex3f(f32::sin(d) * 0.5, f32::cos(d) * 0.5, 0.5);
-                    screen.set_color(Color::from(COLOR_RGB) * 0.2);
-                    gl::Vertex3f(f32::sin(d) * 1.0, f32::cos(d) * 1.0, -0.7);
-                    gl::End();
-                    screen.set_color(COLOR_RGB);
-                    gl::Begin(gl::GL_LINE_LOOP);
-                    gl::Vertex3f(f32::sin(d) * 0.1, f32::cos(d) * 0.1, 0.2);
-                    gl::Vertex3f(f32::sin(d) * 0.5, f32::cos(d) * 0.5, 0.5);
-                    gl::Vertex3f(f32::sin(d) * 1.0, f32::cos(d) * 1.0, -0.7);
-                    gl::End();
-                }
+                renderer.begin(Primitive::Triangles);
+                screen.set_color(COLOR_RGB);
+                renderer.vertex(f32::sin(d) * 0.1, f32::cos(d) * 0.1, 0.2);
+                renderer.vertex(f32::sin(d) * 0.5, f32::cos(d) * 0.5, 0.5);
+                screen.set_color(Color::from(COLOR_RGB) * 0.2);
+                renderer.vertex(f32::sin(d) * 1.0, f32::cos(d) * 1.0, -0.7);
+                renderer.end();
+                screen.set_color(COLOR_RGB);
+                renderer.begin(Primitive::LineLoop);
+                renderer.vertex(f32::sin(d) * 0.1, f32::cos(d) * 0.1, 0.2);
+                renderer.vertex(f32::sin(d) * 0.5, f32::cos(d) * 0.5, 0.5);
+                renderer.vertex(f32::sin(d) * 1.0, f32::cos(d) * 1.0, -0.7);
+                renderer.end();
             }
         } else {
             for i in 0..4 {
                 let d = i as f32 * std::f32::consts::PI / 2.;
-                unsafe {
-                    gl::Begin(gl::GL_TRIANGLES);
-                    screen.set_color(COLOR_RGB);
-                    gl::Vertex3f(f32::sin(d) * 0.1, f32::cos(d) * 0.1, 0.4);
-                    gl::Vertex3f(f32::sin(d) * 0.3, f32::cos(d) * 0.3, 1.0);
-                    screen.set_color(Color::from(COLOR_RGB) * 0.2);
-                    gl::Vertex3f(f32::sin(d) * 0.5, f32::cos(d) * 0.5, -1.4);
-                    gl::End();
-                    screen.set_color(COLOR_RGB);
-                    gl::Begin(gl::GL_LINE_LOOP);
-                    gl::Vertex3f(f32::sin(d) * 0.1, f32::cos(d) * 0.1, 0.4);
-                    gl::Vertex3f(f32::sin(d) * 0.3, f32::cos(d) * 0.3, 1.0);
-                    gl::Vertex3f(f32::sin(d) * 0.5, f32::cos(d) * 0.5, -1.4);
-                    gl::End();
-                }
+                renderer.begin(Primitive::Triangles);
+                screen.set_color(COLOR_RGB);
+                renderer.vertex(f32::sin(d) * 0.1, f32::cos(d) * 0.1, 0.4);
+                renderer.vertex(f32::sin(d) * 0.3, f32::cos(d) * 0.3, 1.0);
+                screen.set_color(Color::from(COLOR_RGB) * 0.2);
+                renderer.vertex(f32::sin(d) * 0.5, f32::cos(d) * 0.5, -1.4);
+                renderer.end();
+                screen.set_color(COLOR_RGB);
+                renderer.begin(Primitive::LineLoop);
+                renderer.vertex(f32::sin(d) * 0.1, f32::cos(d) * 0.1, 0.4);
+                renderer.vertex(f32::sin(d) * 0.3, f32::cos(d) * 0.3, 1.0);
+                renderer.vertex(f32::sin(d) * 0.5, f32::cos(d) * 0.5, -1.4);
+                renderer.end();
             }
         }
-        display_list.end_list();
-        ShotShape {
-            collision: Vector::new_at(0.15, 0.3),
-            display_list,
-        }
     }
 }
 
 impl Drawable for ShotShape {
-    fn draw(&self) {
-        self.display_list.call(0);
+    fn draw(&self, screen: &Screen) {
+        self.display_list
+            .call(screen.shared_renderer(), 0, |display_list| {
+                display_list.new_list();
+                ShotShape::create_display_list(self.charge, screen);
+                display_list.end_list();
+            });
     }
 }
 
@@ -1,9 +1,9 @@
-use crate::gl;
-
 use crate::tt::screen::Screen;
 use crate::util::color::Color;
+use crate::util::renderer::Primitive;
 use crate::util::vector::Vector;
 
+#[derive(Serialize, Deserialize)]
 pub struct Structure {
     pos: Vector,
     d1: f32,
@@ -42,135 +42,134 @@ impl Structure {
     }
 
     pub fn create_display_list(&self, screen: &Screen) {
-        unsafe {
-            gl::PushMatrix();
-            gl::Translatef(self.pos.x, self.pos.y, 0.);
-            gl::Rotatef(-self.d2, 1., 0., 0.);
-            gl::Rotatef(self.d1, 0., 0., 1.);
-            if self.shape == Shape::Rocket {
-                gl::Scalef(self.width, self.width, self.height);
-            } else {
-                gl::Scalef(self.width, self.height, 1.);
-            }
-            gl::Scalef(self.shape_x_reverse, 1., 1.);
-            let alp = match self.color {
-                0 => 1.,
-                _ => 0.5,
-            };
-            screen.set_color(COLOR_RGB[self.color]);
-            match self.shape {
-                Shape::Square => {
-                    for i in 0..self.div_num {
-                        let x11 = -0.5 + (1.0 / self.div_num as f32) * i as f32;
-                        let x12 = x11 + (1.0 / self.div_num as f32) * 0.8;
-                        let x21 = -0.5 + (0.8 / self.div_num as f32) * i as f32;
-                        let x22 = x21 + (0.8 / self.div_num as f32) * 0.8;
-                        gl::Begin(gl::GL_LINE_LOOP);
-                        gl::Vertex3f(x21, 0., -0.5);
-                        gl::Vertex3f(x22, 0., -0.5);
-                        gl::Vertex3f(x12, 0., 0.5);
-                        gl::Vertex3f(x11, 0., 0.5);
-                        gl::End();
-                        gl::Begin(gl::GL_LINE_LOOP);
-                        gl::Vertex3f(x21, 0.1, -0.5);
-                        gl::Vertex3f(x22, 0.1, -0.5);
-                        gl::Vertex3f(x12, 0.1, 0.5);
-                        gl::Vertex3f(x11, 0.1, 0.5);
-                        gl::End();
-                        screen.set_alpha_color((Color::from(COLOR_RGB[self.color]), alp));
-                        gl::Begin(gl::GL_TRIANGLE_FAN);
-                        gl::Vertex3f(x21, 0., -0.5);
-                        gl::Vertex3f(x22, 0., -0.5);
-                        gl::Vertex3f(x12, 0., 0.5);
-                        gl::Vertex3f(x11, 0., 0.5);
-                        gl::End();
-                    }
+        let renderer = screen.renderer();
+        renderer.push_matrix();
+        renderer.translate(self.pos.x, self.pos.y, 0.);
+        renderer.rotate(-self.d2, 1., 0., 0.);
+        renderer.rotate(self.d1, 0., 0., 1.);
+        if self.shape == Shape::Rocket {
+            renderer.scale(self.width, self.width, self.height);
+        } else {
+            renderer.scale(self.width, self.height, 1.);
+        }
+        renderer.scale(self.shape_x_reverse, 1., 1.);
+        let alp = match self.color {
+            0 => 1.,
+            _ => 0.5,
+        };
+        screen.set_color(COLOR_RGB[self.color]);
+        match self.shape {
+            Shape::Square => {
+                for i in 0..self.div_num {
+                    let x11 = -0.5 + (1.0 / self.div_num as f32) * i as f32;
+                    let x12 = x11 + (1.0 / self.div_num as f32) * 0.8;
+                    let x21 = -0.5 + (0.8 / self.div_num as f32) * i as f32;
+                    let x22 = x21 + (0.8 / self.div_num as f32) * 0.8;
+                    renderer.begin(Primitive::LineLoop);
+                    renderer.vertex(x21, 0., -0.5);
+                    renderer.vertex(x22, 0., -0.5);
+                    renderer.vertex(x12, 0., 0.5);
+                    renderer.vertex(x11, 0., 0.5);
+                    renderer.end();
+                    renderer.begin(Primitive::LineLoop);
+                    renderer.vertex(x21, 0.1, -0.5);
+                    renderer.vertex(x22, 0.1, -0.5);
+                    renderer.vertex(x12, 0.1, 0.5);
+                    renderer.vertex(x11, 0.1, 0.5);
+                    renderer.end();
+                    screen.set_alpha_color((Color::from(COLOR_RGB[self.color]), alp));
+                    renderer.begin(Primitive::TriangleFan);
+                    renderer.vertex(x21, 0., -0.5);
+                    renderer.vertex(x22, 0., -0.5);
+                    renderer.vertex(x12, 0., 0.5);
+                    renderer.vertex(x11, 0., 0.5);
+                    renderer.end();
                 }
-                Shape::Wing => {
-                    for i in 0..self.div_num {
-                        let x1 = -0.5 + (1.0 / self.div_num as f32) * i as f32;
-                        let x2 = x1 + (1.0 / self.div_num as f32) * 0.8;
-                        let y1 = x1;
-                        let y2 = x2;
-                        gl::Begin(gl::GL_LINE_LOOP);
-                        gl::Vertex3f(x1, 0., y1);
-                        gl::Vertex3f(x2, 0., y2);
-                        gl::Vertex3f(x2, 0., 0.5);
-                        gl::Vertex3f(x1, 0., 0.5);
-                        gl::End();
-                        gl::Begin(gl::GL_LINE_LOOP);
-                        gl::Vertex3f(x1, 0.1, y1);
-                        gl::Vertex3f(x2, 0.1, y2);
-                        gl::Vertex3f(x2, 0.1, 0.5);
-                        gl::Vertex3f(x1, 0.1, 0.5);
-                        gl::End();
-                        screen.set_alpha_color((Color::from(COLOR_RGB[self.color]), alp));
-                        gl::Begin(gl::GL_TRIANGLE_FAN);
-                        gl::Vertex3f(x1, 0., y1);
-                        gl::Vertex3f(x2, 0., y2);
-                        gl::Vertex3f(x2, 0., 0.5);
-                        gl::Vertex3f(x1, 0., 0.5);
-                        gl::End();
-                    }
+            }
+            Shape::Wing => {
+                for i in 0..self.div_num {
+                    let x1 = -0.5 + (1.0 / self.div_num as f32) * i as f32;
+                    let x2 = x1 + (1.0 / self.div_num as f32) * 0.8;
+                    let y1 = x1;
+                    let y2 = x2;
+                    renderer.begin(Primitive::LineLoop);
+                    renderer.vertex(x1, 0., y1);
+                    renderer.vertex(x2, 0., y2);
+                    renderer.vertex(x2, 0., 0.5);
+                    renderer.vertex(x1, 0., 0.5);
+                    renderer.end();
+                    renderer.begin(Primitive::LineLoop);
+                    renderer.vertex(x1, 0.1, y1);
+                    renderer.vertex(x2, 0.1, y2);
+                    renderer.vertex(x2, 0.1, 0.5);
+                    renderer.vertex(x1, 0.1, 0.5);
+                    renderer.end();
+                    screen.set_alpha_color((Color::from(COLOR_RGB[self.color]), alp));
+                    renderer.begin(Primitive::TriangleFan);
+                    renderer.vertex(x1, 0., y1);
+                    renderer.vertex(x2, 0., y2);
+                    renderer.vertex(x2, 0., 0.5);
+                    renderer.vertex(x1, 0., 0.5);
+                    renderer.end();
                 }
-                Shape::Triangle => {
-                    for i in 0..self.div_num {
-                        let x1 = -0.5 + (1.0 / self.div_num as f32) * i as f32;
-                        let x2 = x1 + (1.0 / self.div_num as f32) * 0.8;
-                        let y1 = -0.5
-                            + (1.0 / self.div_num as f32)
-                                * f32::abs(i as f32 - self.div_num as f32 / 2.)
-                                * 2.;
-                        let y2 = -0.5
-                            + (1.0 / self.div_num as f32)
-                                * f32::abs(i as f32 + 0.8 - self.div_num as f32 / 2.)
-                                * 2.;
-                        gl::Begin(gl::GL_LINE_LOOP);
-                        gl::Vertex3f(x1, 0., y1);
-                        gl::Vertex3f(x2, 0., y2);
-                        gl::Vertex3f(x2, 0., 0.5);
-                        gl::Vertex3f(x1, 0., 0.5);
-                        gl::End();
-                        gl::Begin(gl::GL_LINE_LOOP);
-                        gl::Vertex3f(x1, 0.1, y1);
-                        gl::Vertex3f(x2, 0.1, y2);
-                        gl::Vertex3f(x2, 0.1, 0.5);
-                        gl::Vertex3f(x1, 0.1, 0.5);
-                        gl::End();
-                        screen.set_alpha_color((Color::from(COLOR_RGB[self.color]), alp));
-                        gl::Begin(gl::GL_TRIANGLE_FAN);
-                        gl::Vertex3f(x1, 0., y1);
-                        gl::Vertex3f(x2, 0., y2);
-                        gl::Vertex3f(x2, 0., 0.5);
-                        gl::Vertex3f(x1, 0., 0.5);
-                        gl::End();
-                    }
+            }
+            Shape::Triangle => {
+                for i in 0..self.div_num {
+                    let x1 = -0.5 + (1.0 / self.div_num as f32) * i as f32;
+                    let x2 = x1 + (1.0 / self.div_num as f32) * 0.8;
+                    let y1 = -0.5
+                        + (1.0 / self.div_num as f32)
+                            * f32::abs(i as f32 - self.div_num as f32 / 2.)
+                            * 2.;
+                    let y2 = -0.5
+                        + (1.0 / self.div_num as f32)
+                            * f32::abs(i as f32 + 0.8 - self.div_num as f32 / 2.)
+                            * 2.;
+                    renderer.begin(Primitive::LineLoop);
+                    renderer.vertex(x1, 0., y1);
+                    renderer.vertex(x2, 0., y2);
+                    renderer.vertex(x2, 0., 0.5);
+                    renderer.vertex(x1, 0., 0.5);
+                    renderer.end();
+                    renderer.begin(Primitive::LineLoop);
+                    renderer.vertex(x1, 0.1, y1);
+                    renderer.vertex(x2, 0.1, y2);
+                    renderer.vertex(x2, 0.1, 0.5);
+                    renderer.vertex(x1, 0.1, 0.5);
+                    renderer.end();
+                    screen.set_alpha_color((Color::from(COLOR_RGB[self.color]), alp));
+                    renderer.begin(Primitive::TriangleFan);
+                    renderer.vertex(x1, 0., y1);
+                    renderer.vertex(x2, 0., y2);
+                    renderer.vertex(x2, 0., 0.5);
+                    renderer.vertex(x1, 0., 0.5);
+                    renderer.end();
                 }
-                Shape::Rocket => {
-                    for i in 0..4 {
-                        let d = i as f32 * std::f32::consts::PI / 2. + std::f32::consts::PI / 4.;
-                        gl::Begin(gl::GL_LINE_LOOP);
-                        gl::Vertex3f(f32::sin(d - 0.3), f32::cos(d - 0.3), -0.5);
-                        gl::Vertex3f(f32::sin(d + 0.3), f32::cos(d + 0.3), -0.5);
-                        gl::Vertex3f(f32::sin(d + 0.3), f32::cos(d + 0.3), 0.5);
-                        gl::Vertex3f(f32::sin(d - 0.3), f32::cos(d - 0.3), 0.5);
-                        gl::End();
-                        screen.set_alpha_color((Color::from(COLOR_RGB[self.color]), alp));
-                        gl::Begin(gl::GL_TRIANGLE_FAN);
-                        gl::Vertex3f(f32::sin(d - 0.3), f32::cos(d - 0.3), -0.5);
-                        gl::Vertex3f(f32::sin(d + 0.3), f32::cos(d + 0.3), -0.5);
-                        gl::Vertex3f(f32::sin(d + 0.3), f32::cos(d + 0.3), 0.5);
-                        gl::Vertex3f(f32::sin(d - 0.3), f32::cos(d - 0.3), 0.5);
-                        gl::End();
-                    }
+            }
+            Shape::Rocket => {
+                for i in 0..4 {
+                    let d = i as f32 * std::f32::consts::PI / 2. + std::f32::consts::PI / 4.;
+                    renderer.begin(Primitive::LineLoop);
+                    renderer.vertex(f32::sin(d - 0.3), f32::cos(d - 0.3), -0.5);
+                    renderer.vertex(f32::sin(d + 0.3), f32::cos(d + 0.3), -0.5);
+                    renderer.vertex(f32::sin(d + 0.3), f32::cos(d + 0.3), 0.5);
+                    renderer.vertex(f32::sin(d - 0.3), f32::cos(d - 0.3), 0.5);
+                    renderer.end();
+                    screen.set_alpha_color((Color::from(COLOR_RGB[self.color]), alp));
+                    renderer.begin(Primitive::TriangleFan);
+                    renderer.vertex(f32::sin(d - 0.3), f32::cos(d - 0.3), -0.5);
+                    renderer.vertex(f32::sin(d + 0.3), f32::cos(d + 0.3), -0.5);
+                    renderer.vertex(f32::sin(d + 0.3), f32::cos(d + 0.3), 0.5);
+                    renderer.vertex(f32::sin(d - 0.3), f32::cos(d - 0.3), 0.5);
+                    renderer.end();
                 }
             }
-            gl::PopMatrix();
         }
+        renderer.pop_matrix();
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum Shape {
     Square,
     Wing,
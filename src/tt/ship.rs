@@ -1,7 +1,9 @@
-use crate::gl;
+use std::hash::Hasher;
+
 use crate::glu;
 
 use crate::util::rand::Rand;
+use crate::util::renderer::MatrixMode;
 use crate::util::vector::{Vector, Vector3};
 
 use crate::tt::actor::bullet::BulletPool;
@@ -9,10 +11,11 @@ use crate::tt::actor::particle::{ParticlePool, ParticleSpec};
 use crate::tt::actor::pool::PoolActorRef;
 use crate::tt::actor::shot::ShotPool;
 use crate::tt::camera::Camera;
-use crate::tt::pad::{PadButtons, PadDirection};
+use crate::tt::hitbox;
+use crate::tt::pad::{PadButtons, PadDirection, PadState};
 use crate::tt::screen::Screen;
 use crate::tt::shape::ship_shape::ShipShape;
-use crate::tt::shape::Drawable;
+use crate::tt::shape::{Collidable, Drawable};
 use crate::tt::tunnel::{InCourseSliceCheck, Tunnel, DEFAULT_RAD};
 use crate::tt::GeneralParams;
 
@@ -26,7 +29,7 @@ pub const RELPOS_MAX_Y: f32 = 10.;
 const RESTART_CNT: i32 = 268;
 const INVINCIBLE_CNT: i32 = 228;
 
-const HIT_WIDTH: f32 = 0.00025;
+pub const HIT_WIDTH: f32 = 0.00025;
 
 const SPEED_DEFAULT: [f32; GRADE_NUM] = [0.4, 0.6, 0.8];
 const SPEED_MAX: [f32; GRADE_NUM] = [0.8, 1.2, 1.6];
@@ -43,6 +46,7 @@ const GUNPOINT_WIDTH: f32 = 0.05;
 
 const MAX_BOSS_APP_RANK: u32 = 9_999_999;
 
+#[derive(Serialize, Deserialize)]
 pub struct Ship {
     replay_mode: bool,
     camera_mode: bool,
@@ -92,11 +96,13 @@ pub struct Ship {
     screen_shake_intense: f32,
 
     btn_pressed: bool,
+    // As read on the last tick, for the input display.
+    pad_state: PadState,
 }
 
 impl Ship {
     // TODO split what is memorized and what is always reset
-    pub fn new(screen: &Screen, seed: u64) -> Self {
+    pub fn new(seed: u64) -> Self {
         Ship {
             replay_mode: false,
             camera_mode: true,
@@ -124,7 +130,7 @@ impl Ship {
             bank_max: BANK_MAX_DEFAULT[0],
             tunnel_ofs: 0.,
             pos3: Vector3::default(),
-            shape: ShipShape::new_small(false, screen, seed),
+            shape: ShipShape::new_small(false, seed),
 
             charging_shot: None,
             regenerative_charge: 0.,
@@ -146,6 +152,7 @@ impl Ship {
             screen_shake_intense: 0.,
 
             btn_pressed: true,
+            pad_state: PadState::default(),
         }
     }
 
@@ -185,6 +192,7 @@ impl Ship {
             camera.start();
         }
         self.btn_pressed = true;
+        self.pad_state = PadState::default();
     }
 
     fn restart(&mut self, shots: &mut ShotPool) {
@@ -206,19 +214,23 @@ impl Ship {
         particles: &mut ParticlePool,
     ) {
         self.cnt += 1;
-        let (mut btn, mut dir) = if !self.replay_mode {
-            let ps = params.pad.record_state();
-            (ps.buttons, ps.direction)
+        self.pad_state = if !self.replay_mode {
+            params.pad.record_state()
         } else {
             let ps = params.pad.replay_state();
             if let Some(ps) = ps {
-                (ps.buttons, ps.direction)
+                ps
             } else {
                 record_event_end!(false);
                 self.is_game_over = true;
-                (PadButtons::NONE, PadDirection::NONE)
+                PadState::default()
             }
         };
+        let PadState {
+            buttons: mut btn,
+            direction: mut dir,
+            mut axes,
+        } = self.pad_state;
         if self.btn_pressed {
             if btn != PadButtons::NONE {
                 btn = PadButtons::NONE;
@@ -229,6 +241,7 @@ impl Ship {
         if self.is_game_over {
             btn = PadButtons::NONE;
             dir = PadDirection::NONE;
+            axes = None;
             self.speed *= 0.9;
             bullets.clear_visible();
             if self.cnt < -INVINCIBLE_CNT {
@@ -237,6 +250,7 @@ impl Ship {
         } else if self.cnt < -INVINCIBLE_CNT {
             btn = PadButtons::NONE;
             dir = PadDirection::NONE;
+            axes = None;
             self.rel_pos.y *= 0.99;
             bullets.clear_visible();
         }
@@ -273,28 +287,52 @@ impl Ship {
         params.tunnel.set_slices_backward();
         self.pos3 = params.tunnel.get_pos_v(self.rel_pos);
 
-        if dir & PadDirection::RIGHT != PadDirection::NONE {
-            self.bank += (-self.bank_max - self.bank) * 0.1;
-        }
-        if dir & PadDirection::LEFT != PadDirection::NONE {
-            self.bank += (self.bank_max - self.bank) * 0.1;
-        }
         let mut over_accel = false;
-        if dir & PadDirection::UP != PadDirection::NONE {
-            if self.rel_pos.y < RELPOS_MAX_Y {
-                self.rel_pos.y += RELPOS_Y_MOVE;
-            } else {
-                self.target_speed += ACCEL_RATIO[self.grade as usize];
-                if btn & PadButtons::B == PadButtons::NONE
-                    && !self.in_boss_mode
-                    && !self.is_boss_mode_end
-                {
-                    over_accel = true;
+        if let Some(axes) = axes {
+            // The stick sets how far to bank and how fast to move forward or backward.
+            let steering = axes.steering();
+            if steering != 0. {
+                self.bank += (-self.bank_max * steering - self.bank) * 0.1;
+            }
+            let throttle = axes.throttle();
+            if throttle > 0. {
+                if self.rel_pos.y < RELPOS_MAX_Y {
+                    self.rel_pos.y += RELPOS_Y_MOVE * throttle;
+                } else {
+                    self.target_speed += ACCEL_RATIO[self.grade as usize] * throttle;
+                    if btn & PadButtons::B == PadButtons::NONE
+                        && !self.in_boss_mode
+                        && !self.is_boss_mode_end
+                    {
+                        over_accel = true;
+                    }
                 }
+            } else if throttle < 0. && self.rel_pos.y > 0. {
+                self.rel_pos.y += RELPOS_Y_MOVE * throttle;
+            }
+        } else {
+            if dir & PadDirection::RIGHT != PadDirection::NONE {
+                self.bank += (-self.bank_max - self.bank) * 0.1;
+            }
+            if dir & PadDirection::LEFT != PadDirection::NONE {
+                self.bank += (self.bank_max - self.bank) * 0.1;
+            }
+            if dir & PadDirection::UP != PadDirection::NONE {
+                if self.rel_pos.y < RELPOS_MAX_Y {
+                    self.rel_pos.y += RELPOS_Y_MOVE;
+                } else {
+                    self.target_speed += ACCEL_RATIO[self.grade as usize];
+                    if btn & PadButtons::B == PadButtons::NONE
+                        && !self.in_boss_mode
+                        && !self.is_boss_mode_end
+                    {
+                        over_accel = true;
+                    }
+                }
+            }
+            if dir & PadDirection::DOWN != PadDirection::NONE && self.rel_pos.y > 0. {
+                self.rel_pos.y -= RELPOS_Y_MOVE;
             }
-        }
-        if dir & PadDirection::DOWN != PadDirection::NONE && self.rel_pos.y > 0. {
-            self.rel_pos.y -= RELPOS_Y_MOVE;
         }
         let acc = self.rel_pos.y
             * (SPEED_MAX[self.grade as usize] - SPEED_DEFAULT[self.grade as usize])
@@ -388,6 +426,11 @@ impl Ship {
                             + GUNPOINT_WIDTH * ((self.fire_shot_cnt as f32 % 2.) * 2. - 1.);
                         self.gunpoint_pos.y = self.rel_pos.y;
                         shot.update(self.gunpoint_pos);
+                        record_event!(ShotFired {
+                            x: shot.pos.x,
+                            y: shot.pos.y,
+                            charge: false,
+                        });
                         self.fire_shot_cnt += 1;
                     });
                 }
@@ -413,6 +456,11 @@ impl Ship {
                             + GUNPOINT_WIDTH * ((self.fire_shot_cnt as f32 % 2.) * 2. - 1.);
                         self.gunpoint_pos.y = self.rel_pos.y;
                         shot.update(self.gunpoint_pos);
+                        record_event!(ShotFired {
+                            x: shot.pos.x,
+                            y: shot.pos.y,
+                            charge: false,
+                        });
                         self.side_fire_shot_cnt += 1;
                     });
                 }
@@ -499,21 +547,21 @@ impl Ship {
             e = tunnel.get_pos_v3(camera.camera_pos());
             l = tunnel.get_pos_v3(camera.look_at_pos());
             deg = camera.deg();
-            unsafe {
-                gl::MatrixMode(gl::GL_PROJECTION);
-                gl::LoadIdentity();
-                let np = screen.near_plane() * camera.zoom();
-                let (p_width, p_height) = screen.physical_size();
-                gl::Frustum(
-                    -f64::from(np),
-                    f64::from(np),
-                    -f64::from(np) * p_height / p_width,
-                    f64::from(np) * p_height / p_width,
-                    0.1,
-                    f64::from(screen.far_plane()),
-                );
-                gl::MatrixMode(gl::GL_MODELVIEW);
-            }
+            let renderer = screen.renderer();
+            renderer.matrix_mode(MatrixMode::Projection);
+            renderer.load_identity();
+            let np = screen.near_plane() * camera.zoom();
+            let (p_width, p_height) = screen.physical_size();
+            let screen_ratio = (p_height / p_width) as f32;
+            renderer.frustum(
+                -np,
+                np,
+                -np * screen_ratio,
+                np * screen_ratio,
+                0.1,
+                screen.far_plane(),
+            );
+            renderer.matrix_mode(MatrixMode::ModelView);
         }
         if self.screen_shake_cnt > 0 {
             let mx = self
@@ -530,6 +578,7 @@ impl Ship {
             l += m;
         }
         glu::look_at(
+            screen.renderer(),
             f64::from(e.x),
             f64::from(e.y),
             f64::from(e.z),
@@ -593,6 +642,7 @@ impl Ship {
         if self.cnt <= 0 {
             return;
         }
+        record_event!(ShipDestroyed);
         for _ in 0..256 {
             particles.get_instance_forced_and(|pt, rand| {
                 pt.set(
@@ -619,6 +669,10 @@ impl Ship {
         self.cnt > -INVINCIBLE_CNT
     }
 
+    pub fn is_restarting(&self) -> bool {
+        self.cnt < -INVINCIBLE_CNT
+    }
+
     pub fn rank_up(&mut self, is_boss: bool) -> bool {
         if (self.in_boss_mode && !is_boss) || self.is_game_over {
             return false;
@@ -667,26 +721,34 @@ impl Ship {
         self.in_boss_mode = false;
     }
 
-    pub fn draw(&self) {
+    pub fn draw(&self, screen: &Screen) {
+        self.draw_at(self.pos3, screen);
+    }
+
+    // Draws the ship somewhere else than where it is in its own tunnel.
+    pub fn draw_at(&self, pos3: Vector3, screen: &Screen) {
+        let renderer = screen.renderer();
         if self.cnt < -INVINCIBLE_CNT || (self.cnt < 0 && (-self.cnt % 32) < 16) {
             return;
         }
-        unsafe {
-            gl::PushMatrix();
-            gl::Translatef(self.pos3.x, self.pos3.y, self.pos3.z);
-            gl::Rotatef(
-                (self.pos.x - self.bank) * 180. / std::f32::consts::PI,
-                0.,
-                0.,
-                1.,
-            );
-            gl::Rotatef(self.d1 * 180. / std::f32::consts::PI, 0., 1., 0.);
-            gl::Rotatef(self.d2 * 180. / std::f32::consts::PI, 1., 0., 0.);
-        }
-        self.shape.draw();
-        unsafe {
-            gl::PopMatrix();
-        }
+        renderer.push_matrix();
+        renderer.translate(pos3.x, pos3.y, pos3.z);
+        renderer.rotate(
+            (self.pos.x - self.bank) * 180. / std::f32::consts::PI,
+            0.,
+            0.,
+            1.,
+        );
+        renderer.rotate(self.d1 * 180. / std::f32::consts::PI, 0., 1., 0.);
+        renderer.rotate(self.d2 * 180. / std::f32::consts::PI, 1., 0., 0.);
+        self.shape.draw(screen);
+        renderer.pop_matrix();
+    }
+
+    pub fn draw_hitbox(&self, tunnel: &Tunnel, screen: &Screen) {
+        let renderer = screen.renderer();
+        hitbox::draw_box(self.rel_pos, self.shape.collision(), tunnel, renderer);
+        hitbox::draw_hit_width(self.rel_pos, tunnel, renderer);
     }
 
     pub fn draw_front(&self, params: &GeneralParams) {
@@ -714,6 +776,10 @@ impl Ship {
         Letter.Direction.TO_RIGHT, 0, 6);*/
     }
 
+    pub fn pad_state(&self) -> PadState {
+        self.pad_state
+    }
+
     pub fn is_replay_mode(&self) -> bool {
         self.replay_mode
     }
@@ -739,10 +805,23 @@ impl Ship {
         self.is_game_over = true;
     }
 
+    pub fn hash_state<H: Hasher>(&self, state: &mut H) {
+        state.write_u32(self.pos.x.to_bits());
+        state.write_u32(self.pos.y.to_bits());
+        state.write_u32(self.speed.to_bits());
+        state.write_u32(self.rank);
+        self.rand.hash_state(state);
+    }
+
     pub fn pos(&self) -> Vector {
         self.pos
     }
 
+    // The distance run since the start, laps included.
+    pub fn distance(&self, tunnel: &Tunnel) -> f32 {
+        (self.lap - 1) as f32 * tunnel.get_torus_length() as f32 + self.pos.y
+    }
+
     pub fn rel_pos(&self) -> Vector {
         self.rel_pos
     }
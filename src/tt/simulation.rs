@@ -0,0 +1,454 @@
+use std::time::Instant;
+
+use crate::tt::actor::bullet::BulletPool;
+use crate::tt::actor::enemy::EnemyPool;
+use crate::tt::actor::float_letter::FloatLetterPool;
+use crate::tt::actor::particle::ParticlePool;
+use crate::tt::actor::shot::ShotPool;
+use crate::tt::barrage::BarrageManager;
+use crate::tt::camera::Camera;
+use crate::tt::errors::GameError;
+use crate::tt::letter::Letter;
+use crate::tt::manager::stage::StageManager;
+use crate::tt::pad::{Pad, PadState, VirtualPad};
+use crate::tt::perf::PerfStats;
+use crate::tt::prefs::PrefManager;
+use crate::tt::replay::ReplayDesync;
+use crate::tt::save_state::SaveState;
+use crate::tt::screen::Screen;
+use crate::tt::ship::Ship;
+use crate::tt::sound::SoundManager;
+use crate::tt::state::shared::SharedState;
+use crate::tt::state::{in_game, state_hash, title, ReplayData, STATE_HASH_INTERVAL};
+use crate::tt::tunnel::{Torus, Tunnel};
+use crate::tt::{GeneralParams, MoreParams};
+
+pub const TICKS_PER_SECOND: u64 = 60;
+
+// When the game cannot keep up, it slows down instead of running more ticks per frame.
+const MAX_TICKS_PER_FRAME: u64 = 5;
+
+// Everything the game logic runs on, owned in one place so that the params can be borrowed
+// from it whenever needed.
+pub struct World<P: Pad> {
+    pub pref_manager: PrefManager,
+    pub screen: Screen,
+    pub letter: Letter,
+    pub pad: P,
+    pub shared_state: SharedState<'static>,
+    pub stage_manager: StageManager,
+    pub sound_manager: SoundManager<'static>,
+    pub camera: Camera,
+    pub tunnel: Tunnel,
+    pub barrage_manager: BarrageManager,
+    pub perf: PerfStats,
+    pub ship: Ship,
+    pub shots: ShotPool,
+    pub bullets: BulletPool,
+    pub enemies: EnemyPool,
+    pub particles: ParticlePool,
+    pub float_letters: FloatLetterPool,
+    #[cfg(feature = "game_recorder")]
+    next_recorder_id: usize,
+}
+
+impl<P: Pad> World<P> {
+    pub fn new(
+        pref_manager: PrefManager,
+        screen: Screen,
+        pad: P,
+        sound_manager: SoundManager<'static>,
+        seed: u64,
+    ) -> Result<Self, GameError> {
+        let letter = Letter::new(&screen);
+        let stage_manager = StageManager::new(seed, screen.theme());
+        Ok(World {
+            pref_manager,
+            screen,
+            letter,
+            pad,
+            shared_state: SharedState::new(),
+            stage_manager,
+            sound_manager,
+            camera: Camera::new(),
+            tunnel: Tunnel::new(Torus::new(seed)),
+            barrage_manager: BarrageManager::load()?,
+            perf: PerfStats::new(),
+            ship: Ship::new(seed),
+            shots: ShotPool::new(64),
+            bullets: BulletPool::new(512, seed),
+            enemies: EnemyPool::new(64, seed),
+            particles: ParticlePool::new(1024, seed),
+            float_letters: FloatLetterPool::new(16),
+            #[cfg(feature = "game_recorder")]
+            next_recorder_id: record_next_id!(),
+        })
+    }
+
+    pub fn params(&mut self) -> (GeneralParams<'_, 'static, 'static>, MoreParams<'_>) {
+        (
+            GeneralParams {
+                pref_manager: &mut self.pref_manager,
+                screen: &mut self.screen,
+                letter: &self.letter,
+                pad: &mut self.pad,
+                shared_state: &mut self.shared_state,
+                stage_manager: &mut self.stage_manager,
+                sound_manager: &mut self.sound_manager,
+                camera: &mut self.camera,
+                tunnel: &mut self.tunnel,
+                barrage_manager: &mut self.barrage_manager,
+                perf: &mut self.perf,
+                #[cfg(feature = "game_recorder")]
+                next_recorder_id: self.next_recorder_id,
+            },
+            MoreParams {
+                ship: &mut self.ship,
+                shots: &mut self.shots,
+                bullets: &mut self.bullets,
+                enemies: &mut self.enemies,
+                particles: &mut self.particles,
+                float_letters: &mut self.float_letters,
+            },
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SimulationEvent {
+    ScoreChanged { score: u32, delta: u32 },
+    ShipDestroyed,
+    ZoneChanged { level: f32 },
+    GameOver,
+}
+
+// The game without any window nor sound. Each call to `step` runs exactly one tick, the game
+// being designed to run `TICKS_PER_SECOND` ticks per second.
+pub struct Simulation {
+    world: World<VirtualPad>,
+    replay_data: ReplayData,
+    frame: u32,
+    state_hashes: Vec<u64>,
+    first_desync: Option<ReplayDesync>,
+}
+
+impl Simulation {
+    pub fn new() -> Result<Self, GameError> {
+        let screen = Screen::new(1., 0., false, [640_u32, 480_u32].into());
+        Ok(Simulation {
+            world: World::new(
+                PrefManager::new_transient(),
+                screen,
+                VirtualPad::new(),
+                SoundManager::new(true),
+                0,
+            )?,
+            replay_data: ReplayData::default(),
+            frame: 0,
+            state_hashes: Vec::new(),
+            first_desync: None,
+        })
+    }
+
+    pub fn start(&mut self, grade: u32, level: f32, seed: u64) {
+        self.replay_data = ReplayData::default().grade(grade).level(level).seed(seed);
+        self.frame = 0;
+        self.state_hashes.clear();
+        self.first_desync = None;
+        let (mut params, mut more_params) = self.world.params();
+        in_game::start_in_game(grade, level, seed, &mut params, &mut more_params);
+    }
+
+    // In replay mode, the ship is driven by the recorded pad states and the pad states passed
+    // to `step` are left to the viewer's controls.
+    pub fn start_replay(&mut self, replay_data: &ReplayData) {
+        self.replay_data = replay_data.clone();
+        self.frame = 0;
+        self.state_hashes.clear();
+        self.first_desync = None;
+        let (mut params, mut more_params) = self.world.params();
+        title::start_replay(replay_data, &mut params, &mut more_params);
+    }
+
+    #[allow(clippy::float_cmp)]
+    pub fn step(&mut self, pad_state: PadState) -> Vec<SimulationEvent> {
+        self.world.pad.set_state(pad_state);
+        let score = self.world.shared_state.score();
+        let level = self.world.stage_manager.level();
+        let restarting = self.world.ship.is_restarting();
+        let game_over = self.world.ship.is_game_over();
+        self.frame += 1;
+        {
+            let (mut params, mut more_params) = self.world.params();
+            if more_params.ship.is_replay_mode() {
+                title::move_replay(&mut params, &mut more_params);
+                if self.first_desync.is_none() {
+                    let state_hash = state_hash(&params, &more_params);
+                    match self.replay_data.recorded_state_hash(self.frame) {
+                        Some(recorded) if recorded != state_hash => {
+                            self.first_desync = Some(ReplayDesync {
+                                frame: self.frame,
+                                recorded,
+                                state_hash,
+                            });
+                        }
+                        _ => (),
+                    }
+                }
+            } else {
+                in_game::move_in_game(&mut params, &mut more_params);
+                if self.frame % STATE_HASH_INTERVAL == 0 {
+                    self.state_hashes.push(state_hash(&params, &more_params));
+                }
+                in_game::check_time(&mut params, &mut more_params);
+            }
+        }
+
+        let mut events = Vec::new();
+        let new_score = self.world.shared_state.score();
+        if new_score != score {
+            events.push(SimulationEvent::ScoreChanged {
+                score: new_score,
+                delta: new_score - score,
+            });
+        }
+        if !restarting && self.world.ship.is_restarting() {
+            events.push(SimulationEvent::ShipDestroyed);
+        }
+        let new_level = self.world.stage_manager.level();
+        if new_level != level {
+            events.push(SimulationEvent::ZoneChanged { level: new_level });
+        }
+        if !game_over && self.world.ship.is_game_over() {
+            events.push(SimulationEvent::GameOver);
+        }
+        events
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn score(&self) -> u32 {
+        self.world.shared_state.score()
+    }
+
+    pub fn level(&self) -> f32 {
+        self.world.stage_manager.level()
+    }
+
+    // The first frame which state does not match the replay being played.
+    pub fn first_desync(&self) -> Option<ReplayDesync> {
+        self.first_desync
+    }
+
+    pub fn state_hash(&mut self) -> u64 {
+        let (params, more_params) = self.world.params();
+        state_hash(&params, &more_params)
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.world.ship.is_game_over()
+    }
+
+    pub fn world(&self) -> &World<VirtualPad> {
+        &self.world
+    }
+
+    pub fn snapshot(&mut self) -> Result<Snapshot, GameError> {
+        let replay_mode = self.world.ship.is_replay_mode();
+        let replay_data = if replay_mode {
+            self.replay_data.clone()
+        } else {
+            self.replay_data
+                .clone()
+                .pad_record(self.world.pad.record().clone())
+                .state_hashes(self.state_hashes.clone())
+        };
+        let (params, more_params) = self.world.params();
+        Ok(Snapshot {
+            replay_data,
+            replay_mode,
+            frame: self.frame,
+            first_desync: self.first_desync,
+            save_state: SaveState::save(&params, &more_params)?,
+        })
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), GameError> {
+        self.replay_data = snapshot.replay_data.clone();
+        if snapshot.replay_mode {
+            self.world
+                .pad
+                .start_replay(snapshot.replay_data.recorded_pad_states().clone());
+            self.state_hashes.clear();
+        } else {
+            self.world
+                .pad
+                .continue_record(snapshot.replay_data.recorded_pad_states().clone());
+            self.state_hashes = snapshot.replay_data.recorded_state_hashes().to_vec();
+        }
+        self.frame = snapshot.frame;
+        self.first_desync = snapshot.first_desync;
+        let (mut params, mut more_params) = self.world.params();
+        snapshot.save_state.restore(&mut params, &mut more_params)
+    }
+
+    // The replay of the game played since `start`.
+    pub fn replay_data(&mut self) -> ReplayData {
+        self.replay_data
+            .clone()
+            .pad_record(self.world.pad.get_record())
+            .state_hashes(std::mem::replace(&mut self.state_hashes, Vec::new()))
+    }
+}
+
+// A save state of the world along with the replay it is part of, restoring it gives back the
+// simulation as it was without running any tick again. The replay lets a game being recorded go
+// on recording, and a replay being played go on checking its state hashes.
+//
+// Worlds of different builds are compared with the state hashes of the replays instead, which
+// tell the first interval where they diverge.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    replay_data: ReplayData,
+    replay_mode: bool,
+    frame: u32,
+    first_desync: Option<ReplayDesync>,
+    save_state: SaveState,
+}
+
+impl Snapshot {
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, GameError> {
+        bincode::serialize(self)
+            .map_err(Box::from)
+            .map_err(GameError::new_snapshot)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, GameError> {
+        bincode::deserialize(bytes)
+            .map_err(Box::from)
+            .map_err(GameError::new_snapshot)
+    }
+}
+
+// Tells how many ticks are due according to the wall clock, so that the game runs at
+// `TICKS_PER_SECOND` whatever the frame rate.
+pub struct FixedTimestep {
+    start_time: Instant,
+    ticks: u64,
+}
+
+#[allow(clippy::new_without_default)]
+impl FixedTimestep {
+    pub fn new() -> Self {
+        FixedTimestep {
+            start_time: Instant::now(),
+            ticks: 0,
+        }
+    }
+
+    pub fn due_ticks(&mut self) -> u64 {
+        let duration = Instant::now().duration_since(self.start_time);
+        let now_millis = duration.as_secs() * 1000 + u64::from(duration.subsec_millis());
+        let target_ticks = now_millis * TICKS_PER_SECOND / 1000;
+        let due = target_ticks - self.ticks;
+        self.ticks = target_ticks;
+        due.min(MAX_TICKS_PER_FRAME)
+    }
+}
+
+#[test]
+fn simulations_with_the_same_inputs_should_not_diverge() {
+    use crate::tt::pad::{PadButtons, PadDirection};
+
+    let run = || {
+        let mut simulation = Simulation::new().unwrap();
+        simulation.start(0, 1., 42);
+        let mut events = Vec::new();
+        for i in 0..1200 {
+            let direction = if (i / 60) % 2 == 0 {
+                PadDirection::LEFT
+            } else {
+                PadDirection::RIGHT | PadDirection::UP
+            };
+            events.extend(simulation.step(PadState {
+                direction,
+                buttons: PadButtons::A,
+                axes: None,
+            }));
+        }
+        (events, simulation.score(), simulation.frame())
+    };
+    assert_eq!(run(), run());
+}
+
+#[test]
+#[allow(clippy::float_cmp)]
+fn restored_snapshot_should_continue_like_the_original() {
+    use crate::tt::pad::{PadButtons, PadDirection};
+
+    let pad_state = |i: u32| PadState {
+        direction: if (i / 45) % 2 == 0 {
+            PadDirection::RIGHT
+        } else {
+            PadDirection::LEFT | PadDirection::UP
+        },
+        buttons: PadButtons::A,
+        axes: None,
+    };
+    let mut simulation = Simulation::new().unwrap();
+    // As ghosts may start, between two levels.
+    simulation.start(0, 2.5, 7);
+    for i in 0..600 {
+        simulation.step(pad_state(i));
+    }
+    let bytes = simulation.snapshot().unwrap().to_bytes().unwrap();
+    let mut restored = Simulation::new().unwrap();
+    restored
+        .restore(&Snapshot::from_bytes(&bytes).unwrap())
+        .unwrap();
+    assert_eq!(restored.frame(), 600);
+    assert_eq!(restored.level(), simulation.level());
+    assert_eq!(restored.state_hash(), simulation.state_hash());
+    for i in 600..1200 {
+        assert_eq!(simulation.step(pad_state(i)), restored.step(pad_state(i)));
+    }
+    assert_eq!(simulation.score(), restored.score());
+    assert_eq!(restored.state_hash(), simulation.state_hash());
+    let (replay_data, restored_replay_data) = (simulation.replay_data(), restored.replay_data());
+    assert_eq!(
+        restored_replay_data.recorded_pad_states(),
+        replay_data.recorded_pad_states()
+    );
+    assert_eq!(
+        restored_replay_data.recorded_state_hashes(),
+        replay_data.recorded_state_hashes()
+    );
+}
+
+#[test]
+fn restored_replay_snapshot_should_play_on_like_the_original() {
+    let (_, replay_data) = crate::tt::autopilot::play(0, 3, Some(1500)).unwrap();
+    let mut simulation = Simulation::new().unwrap();
+    simulation.start_replay(&replay_data);
+    for _ in 0..700 {
+        simulation.step(PadState::default());
+    }
+    let snapshot = simulation.snapshot().unwrap();
+    for _ in 700..1500 {
+        simulation.step(PadState::default());
+    }
+    let mut restored = Simulation::new().unwrap();
+    restored.restore(&snapshot).unwrap();
+    for _ in 700..1500 {
+        restored.step(PadState::default());
+    }
+    assert_eq!(restored.state_hash(), simulation.state_hash());
+    assert_eq!(restored.score(), simulation.score());
+    assert_eq!(restored.first_desync(), None);
+}
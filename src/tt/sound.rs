@@ -12,6 +12,12 @@ pub struct SoundManager<'a> {
     se_disabled: bool,
     bgm: Vec<Music<'a>>,
     se: BTreeMap<String, (Chunk, i32)>,
+    bgm_state: BgmState,
+}
+
+// What picks the next music, saved along with the world.
+#[derive(Serialize, Deserialize)]
+pub struct BgmState {
     prev_bgm_idx: usize,
     next_idx_mv: isize,
     rand: Rand,
@@ -29,9 +35,11 @@ impl<'a> SoundManager<'a> {
             se_disabled: false,
             bgm: Vec::new(),
             se: BTreeMap::new(),
-            prev_bgm_idx: 0,
-            next_idx_mv: 0,
-            rand: Rand::new(Rand::rand_seed()),
+            bgm_state: BgmState {
+                prev_bgm_idx: 0,
+                next_idx_mv: 0,
+                rand: Rand::new(Rand::rand_seed()),
+            },
         }
     }
 
@@ -48,7 +56,7 @@ impl<'a> SoundManager<'a> {
 
         self.bgm = SoundManager::load_musics()?;
         self.se = SoundManager::load_chunks()?;
-        self.prev_bgm_idx = self.bgm.len();
+        self.bgm_state.prev_bgm_idx = self.bgm.len();
 
         Ok(())
     }
@@ -106,22 +114,31 @@ impl<'a> SoundManager<'a> {
     }
 
     pub fn set_rand_seed(&mut self, seed: u64) {
-        self.rand.set_seed(seed)
+        self.bgm_state.rand.set_seed(seed)
+    }
+
+    pub fn bgm_state(&self) -> &BgmState {
+        &self.bgm_state
+    }
+
+    pub fn set_bgm_state(&mut self, bgm_state: BgmState) {
+        self.bgm_state = bgm_state;
     }
 
     pub fn play_bgm(&mut self) {
         if self.no_sound {
             return;
         }
-        let mut bgm_idx = self.rand.gen_usize(self.bgm.len());
-        self.next_idx_mv = self.rand.gen_usize(2) as isize * 2 - 1;
-        if bgm_idx == self.prev_bgm_idx {
+        let state = &mut self.bgm_state;
+        let mut bgm_idx = state.rand.gen_usize(self.bgm.len());
+        state.next_idx_mv = state.rand.gen_usize(2) as isize * 2 - 1;
+        if bgm_idx == state.prev_bgm_idx {
             bgm_idx += 1;
             if bgm_idx >= self.bgm.len() {
                 bgm_idx = 0;
             }
         }
-        self.prev_bgm_idx = bgm_idx;
+        state.prev_bgm_idx = bgm_idx;
         unwrap_sdl_error(self.bgm[bgm_idx].play(-1));
     }
 
@@ -129,13 +146,14 @@ impl<'a> SoundManager<'a> {
         if self.no_sound {
             return;
         }
-        let mut bgm_idx = self.prev_bgm_idx as isize + self.next_idx_mv;
+        let state = &mut self.bgm_state;
+        let mut bgm_idx = state.prev_bgm_idx as isize + state.next_idx_mv;
         if bgm_idx < 0 {
             bgm_idx = self.bgm.len() as isize - 1;
         } else if bgm_idx >= self.bgm.len() as isize {
             bgm_idx = 0;
         }
-        self.prev_bgm_idx = bgm_idx as usize;
+        state.prev_bgm_idx = bgm_idx as usize;
         unwrap_sdl_error(self.bgm[bgm_idx as usize].play(-1));
     }
 
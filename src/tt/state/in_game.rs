@@ -1,11 +1,13 @@
 use piston::input::RenderArgs;
 
-use crate::gl;
-
+use crate::tt::ghost::Ghost;
+use crate::tt::hitbox;
 use crate::tt::manager::MoveAction;
 use crate::tt::pad::PadButtons;
-use crate::tt::state::ReplayData;
+use crate::tt::perf::Timing;
+use crate::tt::state::{state_hash, ReplayData, STATE_HASH_INTERVAL};
 use crate::tt::{GeneralParams, MoreParams};
+use crate::util::renderer::BlendFunc;
 
 use super::State;
 
@@ -15,6 +17,10 @@ pub struct InGameState {
     pause_cnt: u32,
     pause_pressed: bool,
     replay_data: ReplayData,
+    frame: u32,
+    state_hashes: Vec<u64>,
+    ghost: Option<Ghost>,
+    show_hitboxes: bool,
 }
 
 #[allow(clippy::new_without_default)]
@@ -26,64 +32,49 @@ impl InGameState {
             pause_cnt: 0,
             pause_pressed: false,
             replay_data: ReplayData::default(),
+            frame: 0,
+            state_hashes: Vec::new(),
+            ghost: None,
+            show_hitboxes: false,
         }
     }
 
+    pub fn set_ghost(&mut self, ghost: Option<Ghost>) {
+        self.ghost = ghost;
+    }
+
+    pub fn ghost(&self) -> Option<&Ghost> {
+        self.ghost.as_ref()
+    }
+
+    pub fn set_show_hitboxes(&mut self, show_hitboxes: bool) {
+        self.show_hitboxes = show_hitboxes;
+    }
+
     pub fn start(
         &mut self,
         grade: u32,
-        level: u32,
+        level: f32,
         seed: u64,
         params: &mut GeneralParams,
         more_params: &mut MoreParams,
     ) {
-        record_start!(params.next_recorder_id);
-        record_event_start!();
-        more_params.shots.clear();
-        more_params.bullets.clear();
-        more_params.enemies.clear_shallow();
-        more_params.particles.clear();
-        more_params.float_letters.clear();
-        params.pad.start_record();
-        self.replay_data = ReplayData::default()
-            .grade(grade)
-            .level(level as f32)
-            .seed(seed);
-        more_params.bullets.set_seed(seed);
-        more_params.enemies.set_seed(seed);
-        more_params.float_letters.set_seed(seed);
-        more_params.particles.set_seed(seed);
-        more_params.shots.set_seed(seed);
-        params.sound_manager.set_rand_seed(seed);
-        more_params
-            .ship
-            .start(false, grade, seed, params.camera, more_params.shots);
-        params.stage_manager.start(
-            level as f32,
-            grade,
-            seed,
-            params.screen,
-            params.tunnel,
-            params.barrage_manager,
-            more_params,
-        );
-        params.shared_state.init_game_state(
-            params.stage_manager,
-            params.sound_manager,
-            more_params.bullets,
-        );
-        params.sound_manager.play_bgm();
-        params.shared_state.start_bgm_clear();
-        more_params.ship.set_screen_shake(0, 0.);
+        self.replay_data = ReplayData::default().grade(grade).level(level).seed(seed);
+        start_in_game(grade, level, seed, params, more_params);
         self.game_over_cnt = 0;
         self.pause_cnt = 0;
-        params.tunnel.set_ship_pos(0., 0.);
-        params.tunnel.set_slices();
-        params.sound_manager.enable_se();
+        self.frame = 0;
+        self.state_hashes.clear();
+        if let Some(ghost) = &mut self.ghost {
+            ghost.start();
+        }
     }
 
     pub fn replay_data(&mut self, params: &mut GeneralParams) -> ReplayData {
-        self.replay_data.clone().pad_record(params.pad.get_record())
+        self.replay_data
+            .clone()
+            .pad_record(params.pad.get_record())
+            .state_hashes(std::mem::replace(&mut self.state_hashes, Vec::new()))
     }
 }
 
@@ -105,62 +96,19 @@ impl State for InGameState {
             self.pause_cnt += 1;
             return MoveAction::None;
         }
-        params.shared_state.start_bgm_tick(params.sound_manager);
-        more_params.ship.mov(
-            params,
-            more_params.shots,
-            more_params.bullets,
-            more_params.particles,
-        );
-        params.stage_manager.mov(
-            params.screen,
-            params.tunnel,
-            params.barrage_manager,
-            more_params,
-        );
-        if more_params.enemies.mov(
-            params.tunnel,
-            more_params.ship,
-            more_params.bullets,
-            more_params.particles,
-        ) {
-            params.shared_state.goto_next_zone(
-                false,
-                params.stage_manager,
-                params.sound_manager,
-                more_params.bullets,
-            );
+        move_in_game(params, more_params);
+        if let Some(ghost) = &mut self.ghost {
+            ghost.mov();
         }
-        more_params.shots.mov(
-            params,
-            more_params.ship,
-            more_params.bullets,
-            more_params.enemies,
-            more_params.particles,
-            more_params.float_letters,
-        );
-        more_params.bullets.mov(
-            params,
-            more_params.ship,
-            more_params.shots,
-            more_params.particles,
-        );
-        more_params
-            .particles
-            .mov(more_params.ship.speed(), params.tunnel);
-        more_params.float_letters.mov();
-        params.shared_state.decrement_time(more_params.ship);
+        self.frame += 1;
+        if self.frame % STATE_HASH_INTERVAL == 0 {
+            self.state_hashes.push(state_hash(params, more_params));
+        }
+        let was_game_over = more_params.ship.is_game_over();
         let mut action = MoveAction::None;
-        if params.shared_state.check_time_overflow() {
-            if !more_params.ship.is_game_over() {
-                more_params.ship.game_over();
+        if check_time(params, more_params) {
+            if !was_game_over {
                 self.btn_pressed = true;
-                params.sound_manager.fade_bgm();
-                params.sound_manager.disable_se();
-                params.pref_manager.record_result(
-                    params.stage_manager.level() as u32,
-                    params.shared_state.score(),
-                );
             }
             self.game_over_cnt += 1;
             let btn = params.pad.get_state().buttons;
@@ -175,8 +123,6 @@ impl State for InGameState {
             if self.game_over_cnt > 1200 {
                 action = MoveAction::StartTitle(false);
             }
-        } else if params.shared_state.check_beep_time() {
-            params.sound_manager.play_se("timeup_beep.wav");
         }
         action
     }
@@ -187,31 +133,31 @@ impl State for InGameState {
         more_params: &mut MoreParams,
         _render_args: &RenderArgs,
     ) {
-        unsafe {
-            gl::Enable(gl::GL_CULL_FACE);
-        }
+        params.screen.renderer().set_cull_face(true);
         params
             .tunnel
             .draw(&params.stage_manager.slice_draw_state(), params.screen);
-        unsafe {
-            gl::Disable(gl::GL_CULL_FACE);
-        }
+        params.screen.renderer().set_cull_face(false);
         more_params.particles.draw(params.screen);
-        more_params.enemies.draw(params.tunnel, more_params.bullets);
-        more_params.ship.draw();
-        unsafe {
-            gl::BlendFunc(gl::GL_SRC_ALPHA, gl::GL_ONE_MINUS_SRC_ALPHA);
+        more_params
+            .enemies
+            .draw(params.tunnel, more_params.bullets, params.screen);
+        more_params.ship.draw(params.screen);
+        if let Some(ghost) = &self.ghost {
+            ghost.draw(more_params.ship, params.tunnel, params.screen);
         }
+        params.screen.renderer().set_blend_func(BlendFunc::Alpha);
         more_params.float_letters.draw(params);
-        unsafe {
-            gl::BlendFunc(gl::GL_SRC_ALPHA, gl::GL_ONE);
-            gl::Disable(gl::GL_BLEND);
-        }
-        more_params.bullets.draw(params.tunnel);
-        unsafe {
-            gl::Enable(gl::GL_BLEND);
+        params.screen.renderer().set_blend_func(BlendFunc::Additive);
+        params.screen.renderer().set_blend(false);
+        more_params
+            .bullets
+            .draw(params.tunnel, params.barrage_manager, params.screen);
+        params.screen.renderer().set_blend(true);
+        more_params.shots.draw(params.tunnel, params.screen);
+        if self.show_hitboxes {
+            hitbox::draw(params, more_params);
         }
-        more_params.shots.draw(params.tunnel);
     }
 
     fn draw_luminous(
@@ -232,6 +178,9 @@ impl State for InGameState {
         params
             .shared_state
             .draw_front(params, more_params, render_args);
+        if let Some(ghost) = &self.ghost {
+            ghost.draw_front(params, more_params.ship);
+        }
         let (o_width, o_height) = params.screen.ortho_size();
         if self.pause_cnt > 0 && (self.pause_cnt % 64) < 32 {
             params.letter.draw_string(
@@ -243,3 +192,136 @@ impl State for InGameState {
         }
     }
 }
+
+// The game sequence is shared with the simulation so that the game and everything driving the
+// simulation (replays, bots, tests) run exactly the same ticks.
+
+pub fn start_in_game(
+    grade: u32,
+    level: f32,
+    seed: u64,
+    params: &mut GeneralParams,
+    more_params: &mut MoreParams,
+) {
+    record_start!(params.next_recorder_id);
+    record_event_start!();
+    more_params.shots.clear();
+    more_params.bullets.clear();
+    more_params.enemies.clear_shallow();
+    more_params.particles.clear();
+    more_params.float_letters.clear();
+    params.pad.start_record();
+    more_params.bullets.set_seed(seed);
+    more_params.enemies.set_seed(seed);
+    more_params.float_letters.set_seed(seed);
+    more_params.particles.set_seed(seed);
+    more_params.shots.set_seed(seed);
+    params.sound_manager.set_rand_seed(seed);
+    more_params
+        .ship
+        .start(false, grade, seed, params.camera, more_params.shots);
+    params.stage_manager.start(
+        level,
+        grade,
+        seed,
+        params.tunnel,
+        params.barrage_manager,
+        more_params,
+    );
+    params.shared_state.init_game_state(
+        params.stage_manager,
+        params.sound_manager,
+        more_params.bullets,
+    );
+    params.sound_manager.play_bgm();
+    params.shared_state.start_bgm_clear();
+    more_params.ship.set_screen_shake(0, 0.);
+    params.tunnel.set_ship_pos(0., 0.);
+    params.tunnel.set_slices();
+    params.sound_manager.enable_se();
+}
+
+pub fn move_in_game(params: &mut GeneralParams, more_params: &mut MoreParams) {
+    record_next_frame!();
+    params.shared_state.start_bgm_tick(params.sound_manager);
+    params
+        .pad
+        .observe(more_params.ship, params.tunnel, more_params.bullets);
+    let timer = params.perf.start();
+    more_params.ship.mov(
+        params,
+        more_params.shots,
+        more_params.bullets,
+        more_params.particles,
+    );
+    params.perf.stop(Timing::Ship, timer);
+    let timer = params.perf.start();
+    params
+        .stage_manager
+        .mov(params.tunnel, params.barrage_manager, more_params);
+    params.perf.stop(Timing::Stage, timer);
+    let timer = params.perf.start();
+    if more_params.enemies.mov(
+        params.tunnel,
+        more_params.ship,
+        more_params.bullets,
+        more_params.particles,
+        params.barrage_manager,
+    ) {
+        params.shared_state.goto_next_zone(
+            false,
+            params.stage_manager,
+            params.sound_manager,
+            more_params.bullets,
+        );
+    }
+    params.perf.stop(Timing::Enemies, timer);
+    let timer = params.perf.start();
+    more_params.shots.mov(
+        params,
+        more_params.ship,
+        more_params.bullets,
+        more_params.enemies,
+        more_params.particles,
+        more_params.float_letters,
+    );
+    params.perf.stop(Timing::Shots, timer);
+    let timer = params.perf.start();
+    more_params.bullets.mov(
+        params,
+        more_params.ship,
+        more_params.shots,
+        more_params.particles,
+    );
+    params.perf.stop(Timing::Bullets, timer);
+    let timer = params.perf.start();
+    more_params
+        .particles
+        .mov(more_params.ship.speed(), params.tunnel);
+    params.perf.stop(Timing::Particles, timer);
+    more_params.float_letters.mov();
+    params.shared_state.decrement_time(more_params.ship);
+    #[cfg(feature = "game_recorder")]
+    more_params.bullets.record_created_count();
+}
+
+// Returns true once the time is over, in which case the ship is game over.
+pub fn check_time(params: &mut GeneralParams, more_params: &mut MoreParams) -> bool {
+    if params.shared_state.check_time_overflow() {
+        if !more_params.ship.is_game_over() {
+            more_params.ship.game_over();
+            params.sound_manager.fade_bgm();
+            params.sound_manager.disable_se();
+            params.pref_manager.record_result(
+                params.stage_manager.level() as u32,
+                params.shared_state.score(),
+            );
+        }
+        true
+    } else {
+        if params.shared_state.check_beep_time() {
+            params.sound_manager.play_se("timeup_beep.wav");
+        }
+        false
+    }
+}
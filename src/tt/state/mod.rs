@@ -4,10 +4,14 @@ pub mod title;
 
 use piston::input::RenderArgs;
 use rle_vec::RleVec;
+use std::hash::Hasher;
 
 use crate::tt::manager::MoveAction;
 use crate::tt::pad::PadState;
 use crate::tt::{GeneralParams, MoreParams};
+use crate::util::hash::Fnv1a;
+
+pub const STATE_HASH_INTERVAL: u32 = 60;
 
 pub trait State {
     fn mov(&mut self, params: &mut GeneralParams, more_params: &mut MoreParams) -> MoveAction;
@@ -37,6 +41,7 @@ pub struct ReplayData {
     level: f32,
     seed: u64,
     pad_record: RleVec<PadState>,
+    state_hashes: Vec<u64>,
 }
 
 impl ReplayData {
@@ -59,6 +64,46 @@ impl ReplayData {
         self.pad_record = pad_record;
         self
     }
+
+    pub fn state_hashes(mut self, state_hashes: Vec<u64>) -> Self {
+        self.state_hashes = state_hashes;
+        self
+    }
+
+    // Tells whether the hash matches the one recorded for this frame, if any.
+    pub fn check_state_hash(&self, frame: u32, hash: u64) -> bool {
+        self.recorded_state_hash(frame)
+            .is_none_or(|recorded| recorded == hash)
+    }
+
+    pub fn recorded_state_hash(&self, frame: u32) -> Option<u64> {
+        if frame == 0 || !frame.is_multiple_of(STATE_HASH_INTERVAL) {
+            return None;
+        }
+        self.state_hashes
+            .get((frame / STATE_HASH_INTERVAL - 1) as usize)
+            .cloned()
+    }
+
+    pub fn start_params(&self) -> (u32, f32, u64) {
+        (self.grade, self.level, self.seed)
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        self.pad_record.len() as u32
+    }
+
+    pub fn pad_states<'a>(&'a self) -> impl Iterator<Item = PadState> + 'a {
+        self.pad_record.iter().cloned()
+    }
+
+    pub fn recorded_pad_states(&self) -> &RleVec<PadState> {
+        &self.pad_record
+    }
+
+    pub fn recorded_state_hashes(&self) -> &[u64] {
+        &self.state_hashes
+    }
 }
 
 impl Default for ReplayData {
@@ -68,6 +113,19 @@ impl Default for ReplayData {
             level: 1.,
             seed: 0,
             pad_record: RleVec::new(),
+            state_hashes: Vec::new(),
         }
     }
 }
+
+// A hash of the key parts of the game state, recorded in replays every `STATE_HASH_INTERVAL`
+// frames so that a replay diverging from the recorded game can be detected.
+pub fn state_hash(params: &GeneralParams, more_params: &MoreParams) -> u64 {
+    let mut hasher = Fnv1a::default();
+    more_params.ship.hash_state(&mut hasher);
+    hasher.write_u32(params.shared_state.score());
+    params.stage_manager.hash_state(&mut hasher);
+    more_params.enemies.hash_state(&mut hasher);
+    more_params.bullets.hash_state(&mut hasher);
+    hasher.finish()
+}
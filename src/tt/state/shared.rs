@@ -1,4 +1,5 @@
 use piston::input::RenderArgs;
+use std::borrow::Cow;
 
 use crate::tt::actor::bullet::BulletPool;
 use crate::tt::letter::Direction;
@@ -26,12 +27,14 @@ const NEXT_LEVEL_ADDITION_TIME_MSG: &str = "+45 SEC.";
 
 const BEEP_START_TIME: i32 = 15000;
 
+#[derive(Serialize, Deserialize)]
 pub struct SharedState<'a> {
     score: u32,
     next_extend: u32,
     time: i32,
     next_beep_time: i32,
-    time_changed_msg: &'a str,
+    // Borrowed from the constants above, owned once restored from a save state.
+    time_changed_msg: Cow<'a, str>,
     time_changed_show_cnt: i32,
     start_bgm_cnt: i32,
 }
@@ -44,7 +47,7 @@ impl<'a> SharedState<'a> {
             next_extend: 0,
             time: 0,
             next_beep_time: 0,
-            time_changed_msg: "",
+            time_changed_msg: Cow::Borrowed(""),
             time_changed_show_cnt: -1,
             start_bgm_cnt: -1,
         }
@@ -94,6 +97,10 @@ impl<'a> SharedState<'a> {
     ) {
         if !game_over {
             self.score += score;
+            record_event!(Score {
+                delta: score,
+                score: self.score,
+            });
             while self.score > self.next_extend {
                 self.set_next_extend(level);
                 self.extend_ship(sound_manager);
@@ -103,6 +110,9 @@ impl<'a> SharedState<'a> {
 
     fn extend_ship(&mut self, sound_manager: &SoundManager) {
         self.change_time(EXTEND_TIME, EXTEND_TIME_MSG);
+        record_event!(Extend {
+            next_extend: self.next_extend,
+        });
         sound_manager.play_se("extend.wav");
     }
 
@@ -117,7 +127,7 @@ impl<'a> SharedState<'a> {
         self.time = i32::min(self.time + ct, MAX_TIME);
         self.next_beep_time = i32::min((self.time / 1000) * 1000, BEEP_START_TIME);
         self.time_changed_show_cnt = 240;
-        self.time_changed_msg = msg;
+        self.time_changed_msg = Cow::Borrowed(msg);
     }
 
     pub fn decrement_time(&mut self, ship: &mut Ship) {
@@ -176,7 +186,7 @@ impl<'a> SharedState<'a> {
         }
         if self.time_changed_show_cnt >= 0 && (self.time_changed_show_cnt % 64) > 32 {
             letter.draw_string_color(
-                self.time_changed_msg,
+                &self.time_changed_msg,
                 o_width as f32 / 2. - 70.,
                 24.,
                 7.,
@@ -1,20 +1,40 @@
 use piston::input::RenderArgs;
-
-use crate::gl;
+use std::time::{Duration, Instant};
 
 use crate::tt::errors::GameError;
-use crate::tt::manager::title::TitleManager;
+use crate::tt::hitbox;
+use crate::tt::letter::LETTER_WIDTH;
+use crate::tt::manager::title::{ReplayCommand, TitleManager};
 use crate::tt::manager::{Manager, MoveAction};
+use crate::tt::save_state::SaveState;
 use crate::tt::screen::Screen;
-use crate::tt::state::ReplayData;
+use crate::tt::simulation::TICKS_PER_SECOND;
+use crate::tt::state::{state_hash, ReplayData};
 use crate::tt::{GeneralParams, MoreParams};
+use crate::util::renderer::{BlendFunc, MatrixMode, Primitive};
 
 use super::State;
 
+const FAST_FORWARD_TICKS: u32 = 8;
+// Seeking runs as many ticks as fit in this time, so that the title stays responsive.
+const SEEK_TIME_PER_MOVE: Duration = Duration::from_millis(12);
+const PROGRESS_BAR_WIDTH: f32 = 140.;
+// Seeking restores the latest snapshot before the frame, so it never runs more ticks than this.
+const SNAPSHOT_INTERVAL: u32 = 10 * TICKS_PER_SECOND as u32;
+
 pub struct TitleState {
     manager: TitleManager,
     replay_data: Option<ReplayData>,
     game_over_cnt: u32,
+    replay_frame: u32,
+    replay_paused: bool,
+    seek_frame: Option<u32>,
+    // The first frame which state did not match the recorded game.
+    desync_frame: Option<u32>,
+    replay_ended: bool,
+    show_hitboxes: bool,
+    // Taken while the replay plays, by increasing frame.
+    snapshots: Vec<(u32, SaveState)>,
 }
 
 impl TitleState {
@@ -23,6 +43,13 @@ impl TitleState {
             manager: TitleManager::new(screen)?,
             replay_data: None,
             game_over_cnt: 0,
+            replay_frame: 0,
+            replay_paused: false,
+            seek_frame: None,
+            desync_frame: None,
+            replay_ended: false,
+            show_hitboxes: false,
+            snapshots: Vec::new(),
         })
     }
 
@@ -36,6 +63,8 @@ impl TitleState {
         self.manager.start(params, more_params)?;
         self.clear_all(more_params);
         self.start_replay(params, more_params);
+        self.replay_paused = false;
+        self.seek_frame = None;
         Ok(())
     }
 
@@ -49,48 +78,91 @@ impl TitleState {
 
     pub fn set_replay_data(&mut self, replay_data: ReplayData) {
         self.replay_data = Some(replay_data);
+        self.desync_frame = None;
+        self.replay_ended = false;
+        self.snapshots.clear();
+    }
+
+    // Whether the replay has been played to its end at least once.
+    pub fn replay_ended(&self) -> bool {
+        self.replay_ended
+    }
+
+    pub fn set_show_hitboxes(&mut self, show_hitboxes: bool) {
+        self.show_hitboxes = show_hitboxes;
     }
 
     fn start_replay(&mut self, params: &mut GeneralParams, more_params: &mut MoreParams) {
         if let Some(replay_data) = &self.replay_data {
-            record_replay!(params.next_recorder_id);
-            record_event_start!();
-            params.pad.start_replay(replay_data.pad_record.clone());
-            more_params.bullets.set_seed(replay_data.seed);
-            more_params.enemies.set_seed(replay_data.seed);
-            more_params.float_letters.set_seed(replay_data.seed);
-            more_params.particles.set_seed(replay_data.seed);
-            more_params.shots.set_seed(replay_data.seed);
-            params.sound_manager.set_rand_seed(replay_data.seed);
-            more_params.ship.start(
-                true,
-                replay_data.grade,
-                replay_data.seed,
-                params.camera,
-                more_params.shots,
-            );
-            params.stage_manager.start(
-                replay_data.level,
-                replay_data.grade,
-                replay_data.seed,
-                params.screen,
-                params.tunnel,
-                params.barrage_manager,
-                more_params,
-            );
-            params.shared_state.init_game_state(
-                params.stage_manager,
-                params.sound_manager,
-                more_params.bullets,
-            );
-            more_params.ship.set_screen_shake(0, 0.);
+            start_replay(replay_data, params, more_params);
             self.game_over_cnt = 0;
-            params.tunnel.set_ship_pos(0., 0.);
-            params.tunnel.set_slices();
-            params.tunnel.set_slices_backward();
+            self.replay_frame = 0;
+        }
+    }
+
+    // Seeking restores the latest snapshot taken at or before the frame, unless the replay is
+    // already closer to it, then runs the replay as fast as possible until the frame is reached.
+    // Without any snapshot to get back to, seeking backward restarts the replay.
+    pub fn seek(&mut self, frame: u32, params: &mut GeneralParams, more_params: &mut MoreParams) {
+        let snapshot = self
+            .snapshots
+            .iter()
+            .rev()
+            .find(|(snapshot_frame, _)| *snapshot_frame <= frame);
+        let restored = match snapshot {
+            Some((snapshot_frame, save_state))
+                if frame < self.replay_frame || *snapshot_frame > self.replay_frame =>
+            {
+                match save_state.restore(params, more_params) {
+                    Ok(()) => {
+                        self.game_over_cnt = 0;
+                        self.replay_frame = *snapshot_frame;
+                        true
+                    }
+                    Err(_) => false,
+                }
+            }
+            _ => false,
+        };
+        if !restored && frame < self.replay_frame {
+            self.clear_all(more_params);
+            self.start_replay(params, more_params);
+        }
+        self.seek_frame = Some(frame);
+    }
+
+    fn tick_replay(&mut self, params: &mut GeneralParams, more_params: &mut MoreParams) {
+        self.take_snapshot(params, more_params);
+        move_replay(params, more_params);
+        self.replay_frame += 1;
+        if let Some(replay_data) = &self.replay_data {
+            if self.desync_frame.is_none()
+                && !replay_data.check_state_hash(self.replay_frame, state_hash(params, more_params))
+            {
+                self.desync_frame = Some(self.replay_frame);
+            }
+        }
+    }
+
+    fn take_snapshot(&mut self, params: &GeneralParams, more_params: &MoreParams) {
+        if !self.replay_frame.is_multiple_of(SNAPSHOT_INTERVAL)
+            || more_params.ship.is_game_over()
+            || self
+                .snapshots
+                .last()
+                .is_some_and(|(frame, _)| *frame >= self.replay_frame)
+        {
+            return;
+        }
+        if let Ok(save_state) = SaveState::save(params, more_params) {
+            self.snapshots.push((self.replay_frame, save_state));
         }
     }
 
+    pub fn enter_replay_mode(&mut self) {
+        self.manager.enter_replay_mode();
+    }
+
     pub fn replay_change_ratio(&self) -> f32 {
         self.manager.replay_change_ratio()
     }
@@ -98,70 +170,61 @@ impl TitleState {
 
 impl State for TitleState {
     fn mov(&mut self, params: &mut GeneralParams, more_params: &mut MoreParams) -> MoveAction {
+        if self.replay_data.is_none() {
+            return self.manager.mov(false, params, more_params);
+        }
+        let action = self.manager.mov(true, params, more_params);
+        let command = self.manager.replay_command();
+        match command {
+            ReplayCommand::TogglePause => self.replay_paused = !self.replay_paused,
+            ReplayCommand::Seek(ticks) => {
+                let frame = i64::max(i64::from(self.replay_frame) + i64::from(ticks), 0);
+                self.seek(frame as u32, params, more_params);
+            }
+            ReplayCommand::SeekNextTenth => {
+                let frame_count = self.replay_data.as_ref().map_or(0, ReplayData::frame_count);
+                let tenth = u32::max(frame_count / 10, 1);
+                let frame = (self.replay_frame / tenth + 1) * tenth;
+                let frame = if frame < frame_count { frame } else { 0 };
+                self.seek(frame, params, more_params);
+            }
+            ReplayCommand::None | ReplayCommand::FrameStep | ReplayCommand::FastForward => (),
+        }
+        if let Some(seek_frame) = self.seek_frame {
+            let seek_start = Instant::now();
+            while self.replay_frame < seek_frame
+                && !more_params.ship.is_game_over()
+                && seek_start.elapsed() < SEEK_TIME_PER_MOVE
+            {
+                self.tick_replay(params, more_params);
+            }
+            if self.replay_frame >= seek_frame || more_params.ship.is_game_over() {
+                self.seek_frame = None;
+            }
+            return action;
+        }
+        if self.replay_paused && command != ReplayCommand::FrameStep {
+            return action;
+        }
         if more_params.ship.is_game_over() {
             self.game_over_cnt += 1;
             if self.game_over_cnt > 120 {
                 record_compare_replay!();
+                self.replay_ended = true;
                 self.clear_all(more_params);
                 self.start_replay(params, more_params);
-                return MoveAction::None;
+                return action;
             }
         }
-        if self.replay_data.is_some() {
-            more_params.ship.mov(
-                params,
-                more_params.shots,
-                more_params.bullets,
-                more_params.particles,
-            );
-            params.stage_manager.mov(
-                params.screen,
-                params.tunnel,
-                params.barrage_manager,
-                more_params,
-            );
-            if more_params.enemies.mov(
-                params.tunnel,
-                more_params.ship,
-                more_params.bullets,
-                more_params.particles,
-            ) {
-                params.shared_state.goto_next_zone(
-                    false,
-                    params.stage_manager,
-                    params.sound_manager,
-                    more_params.bullets,
-                );
-            }
-            more_params.shots.mov(
-                params,
-                more_params.ship,
-                more_params.bullets,
-                more_params.enemies,
-                more_params.particles,
-                more_params.float_letters,
-            );
-            more_params.bullets.mov(
-                params,
-                more_params.ship,
-                more_params.shots,
-                more_params.particles,
-            );
-            more_params
-                .particles
-                .mov(more_params.ship.speed(), params.tunnel);
-            more_params.float_letters.mov();
-            more_params.enemies.mov_passed(
-                params.tunnel,
-                more_params.ship,
-                more_params.bullets,
-                more_params.particles,
-            );
-            params.shared_state.decrement_time(more_params.ship);
-            self.manager.mov(true, params, more_params)
+        let ticks = if command == ReplayCommand::FastForward {
+            FAST_FORWARD_TICKS
         } else {
-            self.manager.mov(false, params, more_params)
+            1
+        };
+        for _ in 0..ticks {
+            self.tick_replay(params, more_params);
         }
+        action
     }
 
     fn draw(
@@ -172,77 +235,72 @@ impl State for TitleState {
     ) {
         if self.replay_data.is_some() {
             let rcr = f32::min(self.manager.replay_change_ratio() * 2.4, 1.);
-            unsafe {
-                let screen = &params.screen;
-                let (p_width, p_height) = screen.physical_size();
-                gl::Viewport(
-                    0,
-                    0,
-                    (p_width / 4. * (3. + f64::from(rcr))) as i32,
-                    p_height as i32,
-                );
-                gl::Enable(gl::GL_CULL_FACE);
-            }
+            let (p_width, p_height) = params.screen.physical_size();
+            params.screen.renderer().viewport(
+                0,
+                0,
+                (p_width / 4. * (3. + f64::from(rcr))) as i32,
+                p_height as i32,
+            );
+            params.screen.renderer().set_cull_face(true);
             params
                 .tunnel
                 .draw(params.stage_manager.slice_draw_state(), params.screen);
             params
                 .tunnel
                 .draw_backward(params.stage_manager.slice_draw_state(), params.screen);
-            unsafe {
-                gl::Disable(gl::GL_CULL_FACE);
-            }
+            params.screen.renderer().set_cull_face(false);
             more_params.particles.draw(params.screen);
-            more_params.enemies.draw(params.tunnel, more_params.bullets);
             more_params
                 .enemies
-                .draw_passed(params.tunnel, more_params.bullets);
-            more_params.ship.draw();
-            unsafe {
-                gl::BlendFunc(gl::GL_SRC_ALPHA, gl::GL_ONE_MINUS_SRC_ALPHA);
-            }
+                .draw(params.tunnel, more_params.bullets, params.screen);
+            more_params
+                .enemies
+                .draw_passed(params.tunnel, more_params.bullets, params.screen);
+            more_params.ship.draw(params.screen);
+            params.screen.renderer().set_blend_func(BlendFunc::Alpha);
             more_params.float_letters.draw(params);
-            unsafe {
-                gl::BlendFunc(gl::GL_SRC_ALPHA, gl::GL_ONE);
-                gl::Disable(gl::GL_BLEND);
-            }
-            more_params.bullets.draw(params.tunnel);
-            unsafe {
-                gl::Enable(gl::GL_BLEND);
+            params.screen.renderer().set_blend_func(BlendFunc::Additive);
+            params.screen.renderer().set_blend(false);
+            more_params
+                .bullets
+                .draw(params.tunnel, params.barrage_manager, params.screen);
+            params.screen.renderer().set_blend(true);
+            more_params.shots.draw(params.tunnel, params.screen);
+            if self.show_hitboxes {
+                hitbox::draw(params, more_params);
             }
-            more_params.shots.draw(params.tunnel);
         }
-        unsafe {
-            let screen = &params.screen;
-            let (p_width, p_height) = screen.physical_size();
-            gl::Viewport(0, 0, p_width as i32, p_height as i32);
-            gl::MatrixMode(gl::GL_PROJECTION);
-            gl::LoadIdentity();
-            let ratio_threshold = 480. / 640.;
-            let screen_ratio = p_height / p_width;
-            if screen_ratio >= ratio_threshold {
-                gl::Frustum(
-                    -f64::from(screen.near_plane()),
-                    f64::from(screen.near_plane()),
-                    -f64::from(screen.near_plane()) * screen_ratio,
-                    f64::from(screen.near_plane()) * screen_ratio,
-                    0.1,
-                    f64::from(screen.far_plane()),
-                );
-            } else {
-                // This allows to see at least what can be seen horizontally and vertically
-                // with the default ratio -- arnodb
-                gl::Frustum(
-                    -f64::from(screen.near_plane()) * ratio_threshold / screen_ratio,
-                    f64::from(screen.near_plane()) * ratio_threshold / screen_ratio,
-                    -f64::from(screen.near_plane()) * ratio_threshold,
-                    f64::from(screen.near_plane()) * ratio_threshold,
-                    0.1,
-                    f64::from(screen.far_plane()),
-                );
-            }
-            gl::MatrixMode(gl::GL_MODELVIEW);
+        let screen = &params.screen;
+        let renderer = screen.renderer();
+        let (p_width, p_height) = screen.physical_size();
+        renderer.viewport(0, 0, p_width as i32, p_height as i32);
+        renderer.matrix_mode(MatrixMode::Projection);
+        renderer.load_identity();
+        let ratio_threshold = 480. / 640.;
+        let screen_ratio = (p_height / p_width) as f32;
+        if screen_ratio >= ratio_threshold {
+            renderer.frustum(
+                -screen.near_plane(),
+                screen.near_plane(),
+                -screen.near_plane() * screen_ratio,
+                screen.near_plane() * screen_ratio,
+                0.1,
+                screen.far_plane(),
+            );
+        } else {
+            // This allows to see at least what can be seen horizontally and vertically
+            // with the default ratio -- arnodb
+            renderer.frustum(
+                -screen.near_plane() * ratio_threshold / screen_ratio,
+                screen.near_plane() * ratio_threshold / screen_ratio,
+                -screen.near_plane() * ratio_threshold,
+                screen.near_plane() * ratio_threshold,
+                0.1,
+                screen.far_plane(),
+            );
         }
+        renderer.matrix_mode(MatrixMode::ModelView);
         self.manager.draw(params, more_params, render_args)
     }
 
@@ -260,6 +318,176 @@ impl State for TitleState {
         _more_params: &MoreParams,
         render_args: &RenderArgs,
     ) {
+        if let Some(action) = params.pad.rebinding_action() {
+            let (o_width, o_height) = params.screen.ortho_size();
+            let draw_centered = |str: &str, y: f32, scale: f32| {
+                let width = str.len() as f32 * LETTER_WIDTH * scale;
+                params
+                    .letter
+                    .draw_string(str, (o_width as f32 - width) / 2., y, scale);
+            };
+            draw_centered("PRESS A KEY OR BUTTON FOR", o_height as f32 / 2. - 60., 7.);
+            draw_centered(action.name(), o_height as f32 / 2. - 20., 12.);
+            draw_centered("F1 KEEPS THE CURRENT ONES", o_height as f32 / 2. + 40., 5.);
+            return;
+        }
         self.manager.draw_front(params, render_args);
+        if self.replay_data.is_some() && self.manager.replay_change_ratio() >= 1. {
+            let (o_width, o_height) = params.screen.ortho_size();
+            params.letter.draw_time(
+                (u64::from(self.replay_frame) * 1000 / TICKS_PER_SECOND) as isize,
+                o_width as f32 / 2. + 50.,
+                o_height as f32 - 24.,
+                8.,
+            );
+            if let Some(replay_data) = &self.replay_data {
+                let frame = self.seek_frame.unwrap_or(self.replay_frame);
+                draw_progress_bar(
+                    &params.screen,
+                    frame as f32 / u32::max(replay_data.frame_count(), 1) as f32,
+                    // Under the time, which is drawn leftward.
+                    o_width as f32 / 2. + 58. - PROGRESS_BAR_WIDTH,
+                    o_height as f32 - 10.,
+                );
+            }
+            if let Some(desync_frame) = self.desync_frame {
+                params.letter.draw_string(
+                    "DESYNC",
+                    o_width as f32 / 2. + 80.,
+                    o_height as f32 - 34.,
+                    6.,
+                );
+                params.letter.draw_time(
+                    (u64::from(desync_frame) * 1000 / TICKS_PER_SECOND) as isize,
+                    o_width as f32 - 24.,
+                    o_height as f32 - 24.,
+                    6.,
+                );
+            }
+            if self.replay_paused {
+                params.letter.draw_string(
+                    "PAUSE",
+                    o_width as f32 / 2. - 80.,
+                    o_height as f32 / 2. - 55.,
+                    17.,
+                );
+            }
+        }
+    }
+}
+
+fn draw_progress_bar(screen: &Screen, ratio: f32, x: f32, y: f32) {
+    let renderer = screen.renderer();
+    let width = PROGRESS_BAR_WIDTH * f32::min(ratio, 1.);
+    screen.set_color((1., 1., 1.));
+    renderer.begin(Primitive::Quads);
+    renderer.vertex(x, y - 2., 0.);
+    renderer.vertex(x + width, y - 2., 0.);
+    renderer.vertex(x + width, y + 2., 0.);
+    renderer.vertex(x, y + 2., 0.);
+    renderer.end();
+    renderer.begin(Primitive::LineLoop);
+    renderer.vertex(x, y - 2., 0.);
+    renderer.vertex(x + PROGRESS_BAR_WIDTH, y - 2., 0.);
+    renderer.vertex(x + PROGRESS_BAR_WIDTH, y + 2., 0.);
+    renderer.vertex(x, y + 2., 0.);
+    renderer.end();
+}
+
+// The replay sequence is shared with the headless replay verification so that both run exactly
+// the same simulation.
+
+pub fn start_replay(
+    replay_data: &ReplayData,
+    params: &mut GeneralParams,
+    more_params: &mut MoreParams,
+) {
+    record_replay!(params.next_recorder_id);
+    record_event_start!();
+    params.pad.start_replay(replay_data.pad_record.clone());
+    more_params.bullets.set_seed(replay_data.seed);
+    more_params.enemies.set_seed(replay_data.seed);
+    more_params.float_letters.set_seed(replay_data.seed);
+    more_params.particles.set_seed(replay_data.seed);
+    more_params.shots.set_seed(replay_data.seed);
+    params.sound_manager.set_rand_seed(replay_data.seed);
+    more_params.ship.start(
+        true,
+        replay_data.grade,
+        replay_data.seed,
+        params.camera,
+        more_params.shots,
+    );
+    params.stage_manager.start(
+        replay_data.level,
+        replay_data.grade,
+        replay_data.seed,
+        params.tunnel,
+        params.barrage_manager,
+        more_params,
+    );
+    params.shared_state.init_game_state(
+        params.stage_manager,
+        params.sound_manager,
+        more_params.bullets,
+    );
+    more_params.ship.set_screen_shake(0, 0.);
+    params.tunnel.set_ship_pos(0., 0.);
+    params.tunnel.set_slices();
+    params.tunnel.set_slices_backward();
+}
+
+pub fn move_replay(params: &mut GeneralParams, more_params: &mut MoreParams) {
+    record_next_frame!();
+    more_params.ship.mov(
+        params,
+        more_params.shots,
+        more_params.bullets,
+        more_params.particles,
+    );
+    params
+        .stage_manager
+        .mov(params.tunnel, params.barrage_manager, more_params);
+    if more_params.enemies.mov(
+        params.tunnel,
+        more_params.ship,
+        more_params.bullets,
+        more_params.particles,
+        params.barrage_manager,
+    ) {
+        params.shared_state.goto_next_zone(
+            false,
+            params.stage_manager,
+            params.sound_manager,
+            more_params.bullets,
+        );
     }
+    more_params.shots.mov(
+        params,
+        more_params.ship,
+        more_params.bullets,
+        more_params.enemies,
+        more_params.particles,
+        more_params.float_letters,
+    );
+    more_params.bullets.mov(
+        params,
+        more_params.ship,
+        more_params.shots,
+        more_params.particles,
+    );
+    more_params
+        .particles
+        .mov(more_params.ship.speed(), params.tunnel);
+    more_params.float_letters.mov();
+    more_params.enemies.mov_passed(
+        params.tunnel,
+        more_params.ship,
+        more_params.bullets,
+        more_params.particles,
+        params.barrage_manager,
+    );
+    params.shared_state.decrement_time(more_params.ship);
+    #[cfg(feature = "game_recorder")]
+    more_params.bullets.record_created_count();
 }
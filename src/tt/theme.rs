@@ -0,0 +1,180 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::tt::errors::GameError;
+
+// Themes found in this file, if any, come in addition to the presets.
+pub const THEME_FILE_NAME: &str = "themes.json";
+
+pub const DEFAULT_THEME_NAME: &str = "classic";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Theme {
+    pub name: String,
+    // The tunnel cycles through those colours, one zone out of two.
+    pub tunnel_poly: Vec<(f32, f32, f32)>,
+    pub tunnel_line: Vec<(f32, f32, f32)>,
+    pub ring_normal: (f32, f32, f32),
+    pub ring_final: (f32, f32, f32),
+    pub letter: [(f32, f32, f32); 2],
+    pub bullet: (f32, f32, f32),
+}
+
+impl Theme {
+    pub fn classic() -> Self {
+        Theme {
+            name: DEFAULT_THEME_NAME.to_string(),
+            tunnel_poly: vec![
+                (0.7, 0.9, 1.),
+                (0.6, 1., 0.8),
+                (0.9, 0.7, 0.6),
+                (0.8, 0.8, 0.8),
+                (0.5, 0.9, 0.9),
+                (0.7, 0.9, 0.6),
+                (0.8, 0.5, 0.9),
+            ],
+            tunnel_line: vec![
+                (0.6, 0.7, 1.),
+                (0.4, 0.8, 0.6),
+                (0.7, 0.5, 0.6),
+                (0.6, 0.6, 0.6),
+                (0.4, 0.7, 0.7),
+                (0.6, 0.7, 0.5),
+                (0.6, 0.4, 1.),
+            ],
+            ring_normal: (0.5, 1., 0.9),
+            ring_final: (1., 0.9, 0.5),
+            letter: [(1., 1., 1.), (0.9, 0.7, 0.5)],
+            bullet: (1., 0.7, 0.8),
+        }
+    }
+
+    // The presets keep the bullets in a hue which never shows up in the tunnel for the given
+    // colour vision deficiency.
+    pub fn presets() -> Vec<Theme> {
+        vec![
+            Theme::classic(),
+            // Deuteranopia and protanopia: blue and grey tunnel, orange bullets.
+            Theme {
+                name: "red-green".to_string(),
+                tunnel_poly: vec![
+                    (0.6, 0.8, 1.),
+                    (0.8, 0.8, 0.8),
+                    (0.5, 0.7, 0.9),
+                    (0.7, 0.7, 0.9),
+                    (0.6, 0.9, 1.),
+                    (0.75, 0.75, 0.8),
+                ],
+                tunnel_line: vec![
+                    (0.4, 0.6, 1.),
+                    (0.6, 0.6, 0.6),
+                    (0.3, 0.5, 0.8),
+                    (0.5, 0.5, 0.8),
+                    (0.4, 0.7, 0.9),
+                    (0.55, 0.55, 0.65),
+                ],
+                ring_normal: (0.35, 0.7, 0.9),
+                ring_final: (0.95, 0.9, 0.25),
+                letter: [(1., 1., 1.), (0.9, 0.6, 0.)],
+                bullet: (1., 0.6, 0.),
+            },
+            // Tritanopia: teal and grey tunnel, red bullets.
+            Theme {
+                name: "blue-yellow".to_string(),
+                tunnel_poly: vec![
+                    (0.5, 0.9, 0.9),
+                    (0.8, 0.8, 0.8),
+                    (0.6, 0.8, 0.8),
+                    (0.7, 0.9, 0.9),
+                    (0.7, 0.7, 0.7),
+                ],
+                tunnel_line: vec![
+                    (0.3, 0.7, 0.7),
+                    (0.6, 0.6, 0.6),
+                    (0.4, 0.6, 0.6),
+                    (0.5, 0.7, 0.7),
+                    (0.5, 0.5, 0.5),
+                ],
+                ring_normal: (0.5, 1., 0.9),
+                ring_final: (1., 0.6, 0.7),
+                letter: [(1., 1., 1.), (1., 0.5, 0.5)],
+                bullet: (1., 0.3, 0.3),
+            },
+            // Grey tunnel and yellow bullets, for any kind of colour vision.
+            Theme {
+                name: "high-contrast".to_string(),
+                tunnel_poly: vec![(0.6, 0.6, 0.6), (0.5, 0.5, 0.5)],
+                tunnel_line: vec![(0.5, 0.5, 0.5), (0.4, 0.4, 0.4)],
+                ring_normal: (0.8, 0.8, 0.8),
+                ring_final: (1., 1., 1.),
+                letter: [(1., 1., 1.), (1., 1., 0.3)],
+                bullet: (1., 1., 0.2),
+            },
+        ]
+    }
+
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Vec<Theme>, GameError> {
+        let file = File::open(path)
+            .map_err(Box::from)
+            .map_err(GameError::new_theme)?;
+        let themes: Vec<Theme> = serde_json::from_reader(BufReader::new(file))
+            .map_err(Box::from)
+            .map_err(GameError::new_theme)?;
+        for theme in &themes {
+            // The stage starts two colours before the end of the palettes.
+            if theme.tunnel_poly.len() < 2 || theme.tunnel_line.len() < 2 {
+                return Err(GameError::new_theme(Box::from(format!(
+                    "theme {} needs at least two tunnel colours",
+                    theme.name
+                ))));
+            }
+        }
+        Ok(themes)
+    }
+
+    // The presets, then the themes of the theme file in the current directory.
+    pub fn available() -> Result<Vec<Theme>, GameError> {
+        let mut themes = Theme::presets();
+        if Path::new(THEME_FILE_NAME).exists() {
+            themes.extend(Theme::load_file(THEME_FILE_NAME)?);
+        }
+        Ok(themes)
+    }
+
+    pub fn find(themes: Vec<Theme>, name: &str) -> Result<Theme, GameError> {
+        let names = themes
+            .iter()
+            .map(|theme| theme.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        // A theme of the file overrides a preset of the same name.
+        themes
+            .into_iter()
+            .rev()
+            .find(|theme| theme.name == name)
+            .ok_or_else(|| {
+                GameError::new_theme(Box::from(format!(
+                    "unknown theme {}, available themes: {}",
+                    name, names
+                )))
+            })
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::classic()
+    }
+}
+
+#[test]
+fn presets_should_have_unique_names() {
+    let presets = Theme::presets();
+    for (i, theme) in presets.iter().enumerate() {
+        assert!(theme.tunnel_poly.len() >= 2 && theme.tunnel_line.len() >= 2);
+        assert!(presets[i + 1..]
+            .iter()
+            .all(|other| other.name != theme.name));
+    }
+}
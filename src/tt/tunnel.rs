@@ -1,12 +1,12 @@
 use std::fmt;
 
-use crate::gl;
-
 use crate::tt::screen::Screen;
 use crate::tt::ship::Ship;
 use crate::util::color::Color;
-use crate::util::display_list::DisplayList;
+use crate::util::display_list::{DisplayList, LazyDisplayList};
 use crate::util::rand::Rand;
+use crate::util::renderer::{BlendFunc, Primitive, Renderer};
+use crate::util::serde_array;
 use crate::util::vector::{Vector, Vector3};
 
 const DEPTH_NUM: usize = 72;
@@ -16,6 +16,7 @@ const RAD_RATIO: f32 = 1.05;
 const DEPTH_CHANGE_RATIO: f32 = 1.15;
 const DEPTH_RATIO_MAX: f32 = 80.;
 
+#[derive(Serialize, Deserialize)]
 pub struct Tunnel {
     torus: Torus,
     ship_idx: usize,
@@ -28,7 +29,7 @@ pub struct Tunnel {
     rings_state: Vec<u32>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 struct TunnelSlice {
     slice: Slice,
     ring_index: Option<usize>,
@@ -312,9 +313,8 @@ impl Tunnel {
     }
 
     pub fn draw(&mut self, draw_state: &SliceDrawState, screen: &Screen) {
-        unsafe {
-            gl::BlendFunc(gl::GL_SRC_ALPHA, gl::GL_ONE_MINUS_SRC_ALPHA);
-        }
+        let renderer = screen.renderer();
+        renderer.set_blend_func(BlendFunc::Alpha);
         let mut line_bn = 0.4;
         let mut poly_bn = 0.;
         let mut light_bn = 0.5 - draw_state.dark_line_ratio * 0.2;
@@ -361,15 +361,12 @@ impl Tunnel {
                 light_bn *= 1.0 + draw_state.dark_line_ratio * 0.02;
             }
         }
-        unsafe {
-            gl::BlendFunc(gl::GL_SRC_ALPHA, gl::GL_ONE);
-        }
+        renderer.set_blend_func(BlendFunc::Additive);
     }
 
     pub fn draw_backward(&mut self, draw_state: &SliceDrawState, screen: &Screen) {
-        unsafe {
-            gl::BlendFunc(gl::GL_SRC_ALPHA, gl::GL_ONE_MINUS_SRC_ALPHA);
-        }
+        let renderer = screen.renderer();
+        renderer.set_blend_func(BlendFunc::Alpha);
         let mut line_bn = 0.4;
         let mut poly_bn = 0.;
         let mut light_bn = 0.5 - draw_state.dark_line_ratio * 0.2;
@@ -416,9 +413,7 @@ impl Tunnel {
                 light_bn *= 1.0 + draw_state.dark_line_ratio * 0.02;
             }
         }
-        unsafe {
-            gl::BlendFunc(gl::GL_SRC_ALPHA, gl::GL_ONE);
-        }
+        renderer.set_blend_func(BlendFunc::Additive);
     }
 }
 
@@ -432,7 +427,7 @@ pub struct RingToDraw<'a> {
 
 const DEPTH: f32 = 5.;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct SliceDrawState {
     pub dark_line_ratio: f32,
     pub line: Color,
@@ -445,7 +440,7 @@ pub enum InCourseSliceCheck {
     NotInCourse(f32),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Slice {
     state: SliceState,
     d1: f32,
@@ -453,6 +448,7 @@ pub struct Slice {
     point_from: f32,
     center_pos: Vector3,
     point_ratio: f32,
+    #[serde(with = "serde_array")]
     point_pos: [Vector3; MAX_POINT_NUM],
     depth: f32,
 }
@@ -540,6 +536,7 @@ impl Slice {
         draw_state: &SliceDrawState,
         screen: &Screen,
     ) {
+        let renderer = screen.renderer();
         let mut pi = self.point_from;
         let mut width = self.state.course_width;
         let mut prev_pi = 0.;
@@ -553,49 +550,41 @@ impl Slice {
                 let ps_prev_pi = (prev_pi * prev_slice.state.point_num as f32
                     / self.state.point_num as f32) as usize;
                 screen.set_color(draw_state.line * line_bn);
-                unsafe {
-                    gl::Begin(gl::GL_LINE_STRIP);
-                }
-                self.point_pos[pi as usize].gl_vertex();
-                prev_slice.point_pos[ps_pi].gl_vertex();
-                prev_slice.point_pos[ps_prev_pi].gl_vertex();
-                unsafe {
-                    gl::End();
-                }
+                renderer.begin(Primitive::LineStrip);
+                self.point_pos[pi as usize].vertex(renderer);
+                prev_slice.point_pos[ps_pi].vertex(renderer);
+                prev_slice.point_pos[ps_prev_pi].vertex(renderer);
+                renderer.end();
                 if poly_bn > 0. {
                     if round_slice || (!poly_first && width > 0.) {
                         screen.set_alpha_color((draw_state.poly, poly_bn));
-                        unsafe {
-                            gl::Begin(gl::GL_TRIANGLE_FAN);
-                        }
+                        renderer.begin(Primitive::TriangleFan);
                         Vector3::blend(
                             self.point_pos[prev_pi as usize],
                             prev_slice.point_pos[ps_pi],
                             0.9,
                         )
-                        .gl_vertex();
+                        .vertex(renderer);
                         Vector3::blend(
                             self.point_pos[pi as usize],
                             prev_slice.point_pos[ps_prev_pi],
                             0.9,
                         )
-                        .gl_vertex();
+                        .vertex(renderer);
                         screen.set_alpha_color((draw_state.poly, poly_bn / 2.));
                         Vector3::blend(
                             self.point_pos[prev_pi as usize],
                             prev_slice.point_pos[ps_pi],
                             0.1,
                         )
-                        .gl_vertex();
+                        .vertex(renderer);
                         Vector3::blend(
                             self.point_pos[pi as usize],
                             prev_slice.point_pos[ps_prev_pi],
                             0.1,
                         )
-                        .gl_vertex();
-                        unsafe {
-                            gl::End();
-                        }
+                        .vertex(renderer);
+                        renderer.end();
                     } else {
                         poly_first = false;
                     }
@@ -618,14 +607,10 @@ impl Slice {
             let ps_pi =
                 (pi * prev_slice.state.point_num as f32 / self.state.point_num as f32) as usize;
             screen.set_color((line_bn / 3. * 2., line_bn / 3. * 2., line_bn));
-            unsafe {
-                gl::Begin(gl::GL_LINE_STRIP);
-            }
-            self.point_pos[pi as usize].gl_vertex();
-            prev_slice.point_pos[ps_pi].gl_vertex();
-            unsafe {
-                gl::End();
-            }
+            renderer.begin(Primitive::LineStrip);
+            self.point_pos[pi as usize].vertex(renderer);
+            prev_slice.point_pos[ps_pi].vertex(renderer);
+            renderer.end();
         }
         if !round_slice && light_bn > 0.2 {
             let edges = self.get_edges();
@@ -655,25 +640,24 @@ impl Slice {
         let mut rad_ofs = Vector3::new_at(0., self.state.rad, 0.);
         rad_ofs.roll_z(deg).roll_y(self.d1).roll_x(self.d2);
         rad_ofs += self.center_pos;
+        let renderer = screen.renderer();
         screen.set_color(Color::from((1., 1., 0.6)) * light_bn);
-        unsafe {
-            gl::Begin(gl::GL_LINE_LOOP);
-            gl::Vertex3f(rad_ofs.x - 0.5, rad_ofs.y - 0.5, rad_ofs.z);
-            gl::Vertex3f(rad_ofs.x + 0.5, rad_ofs.y - 0.5, rad_ofs.z);
-            gl::Vertex3f(rad_ofs.x + 0.5, rad_ofs.y + 0.5, rad_ofs.z);
-            gl::Vertex3f(rad_ofs.x - 0.5, rad_ofs.y + 0.5, rad_ofs.z);
-            gl::End();
-            gl::Begin(gl::GL_TRIANGLE_FAN);
-            screen.set_color(Color::from((0.5, 0.5, 0.3)) * light_bn);
-            gl::Vertex3f(rad_ofs.x, rad_ofs.y, rad_ofs.z);
-            screen.set_color(Color::from((0.9, 0.9, 0.6)) * light_bn);
-            gl::Vertex3f(rad_ofs.x - 0.5, rad_ofs.y - 0.5, rad_ofs.z);
-            gl::Vertex3f(rad_ofs.x - 0.5, rad_ofs.y + 0.5, rad_ofs.z);
-            gl::Vertex3f(rad_ofs.x + 0.5, rad_ofs.y + 0.5, rad_ofs.z);
-            gl::Vertex3f(rad_ofs.x + 0.5, rad_ofs.y - 0.5, rad_ofs.z);
-            gl::Vertex3f(rad_ofs.x - 0.5, rad_ofs.y - 0.5, rad_ofs.z);
-            gl::End();
-        }
+        renderer.begin(Primitive::LineLoop);
+        renderer.vertex(rad_ofs.x - 0.5, rad_ofs.y - 0.5, rad_ofs.z);
+        renderer.vertex(rad_ofs.x + 0.5, rad_ofs.y - 0.5, rad_ofs.z);
+        renderer.vertex(rad_ofs.x + 0.5, rad_ofs.y + 0.5, rad_ofs.z);
+        renderer.vertex(rad_ofs.x - 0.5, rad_ofs.y + 0.5, rad_ofs.z);
+        renderer.end();
+        renderer.begin(Primitive::TriangleFan);
+        screen.set_color(Color::from((0.5, 0.5, 0.3)) * light_bn);
+        renderer.vertex(rad_ofs.x, rad_ofs.y, rad_ofs.z);
+        screen.set_color(Color::from((0.9, 0.9, 0.6)) * light_bn);
+        renderer.vertex(rad_ofs.x - 0.5, rad_ofs.y - 0.5, rad_ofs.z);
+        renderer.vertex(rad_ofs.x - 0.5, rad_ofs.y + 0.5, rad_ofs.z);
+        renderer.vertex(rad_ofs.x + 0.5, rad_ofs.y + 0.5, rad_ofs.z);
+        renderer.vertex(rad_ofs.x + 0.5, rad_ofs.y - 0.5, rad_ofs.z);
+        renderer.vertex(rad_ofs.x - 0.5, rad_ofs.y - 0.5, rad_ofs.z);
+        renderer.end();
     }
 
     pub fn is_nearly_round(&self) -> bool {
@@ -785,6 +769,7 @@ impl SliceEdges {
 
 const TORUS_LENGTH: isize = 5000;
 
+#[derive(Serialize, Deserialize)]
 pub struct Torus {
     slice_num: usize,
     torus_parts: Vec<TorusPart>,
@@ -866,7 +851,7 @@ impl Torus {
 
 const BLEND_DISTANCE: f32 = 64.;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 struct TorusPart {
     slice_idx_from: usize,
     slice_idx_to: usize,
@@ -923,7 +908,7 @@ const MAX_POINT_NUM: usize = 36;
 const DEFAULT_POINT_NUM: usize = 24;
 pub const DEFAULT_RAD: f32 = 21.;
 
-#[derive(PartialEq, Debug, Clone, Copy)]
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
 struct SliceState {
     md1: f32,
     md2: f32,
@@ -1007,17 +992,15 @@ impl SliceState {
     }
 }
 
-const NORMAL_COLOR: (f32, f32, f32) = (0.5, 1., 0.9);
-const FINAL_COLOR: (f32, f32, f32) = (1., 0.9, 0.5);
-
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 struct Ring {
     idx: usize,
     ring_type: RingType,
-    display_list: DisplayList,
+    slice_rad: f32,
+    display_list: LazyDisplayList,
 }
 
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
 enum RingType {
     Normal,
     Final,
@@ -1028,39 +1011,41 @@ impl Ring {
         Ring {
             idx,
             ring_type,
-            display_list: match ring_type {
-                RingType::Normal => Ring::create_normal_ring(slice_rad),
-                RingType::Final => Ring::create_final_ring(slice_rad),
-            },
+            slice_rad,
+            display_list: LazyDisplayList::new(match ring_type {
+                RingType::Normal => 1,
+                RingType::Final => 2,
+            }),
+        }
+    }
+
+    fn create_display_list(&self, display_list: &mut DisplayList) {
+        match self.ring_type {
+            RingType::Normal => Ring::create_normal_ring(self.slice_rad, display_list),
+            RingType::Final => Ring::create_final_ring(self.slice_rad, display_list),
         }
     }
 
-    fn create_normal_ring(r: f32) -> DisplayList {
-        let mut display_list = DisplayList::new(1);
+    fn create_normal_ring(r: f32, display_list: &mut DisplayList) {
         display_list.new_list();
-        Ring::draw_ring(r, 1.2, 1.4, 16);
+        Ring::draw_ring(display_list.renderer(), r, 1.2, 1.4, 16);
         display_list.end_list();
-        display_list
     }
 
-    fn create_final_ring(r: f32) -> DisplayList {
-        let mut display_list = DisplayList::new(2);
+    fn create_final_ring(r: f32, display_list: &mut DisplayList) {
         display_list.new_list();
-        Ring::draw_ring(r, 1.2, 1.5, 14);
+        Ring::draw_ring(display_list.renderer(), r, 1.2, 1.5, 14);
         display_list.end_list();
         display_list.new_list();
-        Ring::draw_ring(r, 1.6, 1.9, 14);
+        Ring::draw_ring(display_list.renderer(), r, 1.6, 1.9, 14);
         display_list.end_list();
-        display_list
     }
 
-    fn draw_ring(r: f32, rr1: f32, rr2: f32, num: u32) {
+    fn draw_ring(renderer: &dyn Renderer, r: f32, rr1: f32, rr2: f32, num: u32) {
         let mut d = 0.;
         let md = 0.2;
         for _i in 0..num {
-            unsafe {
-                gl::Begin(gl::GL_LINE_LOOP);
-            }
+            renderer.begin(Primitive::LineLoop);
             let p1 = Vector3::new_at(f32::sin(d) * r * rr1, f32::cos(d) * r * rr1, 0.);
             let p2 = Vector3::new_at(f32::sin(d) * r * rr2, f32::cos(d) * r * rr2, 0.);
             let p3 = Vector3::new_at(f32::sin(d + md) * r * rr2, f32::cos(d + md) * r * rr2, 0.);
@@ -1070,44 +1055,45 @@ impl Ring {
             let np2 = Vector3::blend(p2, cp, 0.7);
             let np3 = Vector3::blend(p3, cp, 0.7);
             let np4 = Vector3::blend(p4, cp, 0.7);
-            np1.gl_vertex();
-            np2.gl_vertex();
-            np3.gl_vertex();
-            np4.gl_vertex();
-            unsafe {
-                gl::End();
-            }
+            np1.vertex(renderer);
+            np2.vertex(renderer);
+            np3.vertex(renderer);
+            np4.vertex(renderer);
+            renderer.end();
             d += md;
         }
     }
 
     fn draw(&self, cnt: u32, p: Vector3, d1: f32, d2: f32, a: f32, screen: &Screen) {
-        unsafe {
-            gl::BlendFunc(gl::GL_SRC_ALPHA, gl::GL_ONE);
-        }
+        let renderer = screen.renderer();
+        renderer.set_blend_func(BlendFunc::Additive);
         let color = match self.ring_type {
-            RingType::Normal => NORMAL_COLOR,
-            RingType::Final => FINAL_COLOR,
+            RingType::Normal => screen.theme().ring_normal,
+            RingType::Final => screen.theme().ring_final,
         };
         screen.set_color(Color::from(color) * a);
-        unsafe {
-            gl::PushMatrix();
-            gl::Translatef(p.x, p.y, p.z);
-            gl::Rotatef(cnt as f32 * 1.0, 0., 0., 1.);
-            gl::Rotatef(d1, 0., 1., 0.);
-            gl::Rotatef(d2, 1., 0., 0.);
-            self.display_list.call(0);
-            gl::PopMatrix();
-            if self.ring_type == RingType::Final {
-                gl::PushMatrix();
-                gl::Translatef(p.x, p.y, p.z);
-                gl::Rotatef(cnt as f32 * -1.0, 0., 0., 1.);
-                gl::Rotatef(d1, 0., 1., 0.);
-                gl::Rotatef(d2, 1., 0., 0.);
-                self.display_list.call(1);
-                gl::PopMatrix();
-            }
-            gl::BlendFunc(gl::GL_SRC_ALPHA, gl::GL_ONE_MINUS_SRC_ALPHA);
+        renderer.push_matrix();
+        renderer.translate(p.x, p.y, p.z);
+        renderer.rotate(cnt as f32 * 1.0, 0., 0., 1.);
+        renderer.rotate(d1, 0., 1., 0.);
+        renderer.rotate(d2, 1., 0., 0.);
+        self.display_list
+            .call(screen.shared_renderer(), 0, |display_list| {
+                self.create_display_list(display_list)
+            });
+        renderer.pop_matrix();
+        if self.ring_type == RingType::Final {
+            renderer.push_matrix();
+            renderer.translate(p.x, p.y, p.z);
+            renderer.rotate(cnt as f32 * -1.0, 0., 0., 1.);
+            renderer.rotate(d1, 0., 1., 0.);
+            renderer.rotate(d2, 1., 0., 0.);
+            self.display_list
+                .call(screen.shared_renderer(), 1, |display_list| {
+                    self.create_display_list(display_list)
+                });
+            renderer.pop_matrix();
         }
+        renderer.set_blend_func(BlendFunc::Alpha);
     }
 }
@@ -1,4 +1,4 @@
-#[derive(Debug, Default, PartialEq, Clone, Copy)]
+#[derive(Debug, Default, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub struct Color {
     pub red: f32,
     pub green: f32,
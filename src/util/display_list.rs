@@ -1,38 +1,42 @@
-use crate::gl;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
 
-#[derive(Debug)]
+use crate::util::renderer::Renderer;
+
+// A set of meshes of the renderer, deleted when dropped.
 pub struct DisplayList {
+    renderer: Rc<dyn Renderer>,
     idx: u32,
     num: u32,
     enum_idx: u32,
 }
 
 impl DisplayList {
-    pub fn new(num: u32) -> Self {
-        let idx;
-        unsafe {
-            idx = gl::GenLists(num as i32);
-        }
+    pub fn new(renderer: &Rc<dyn Renderer>, num: u32) -> Self {
+        let idx = renderer.gen_meshes(num);
         DisplayList {
+            renderer: renderer.clone(),
             idx,
             num,
             enum_idx: idx,
         }
     }
 
+    // What the lists are compiled with.
+    pub fn renderer(&self) -> &dyn Renderer {
+        &*self.renderer
+    }
+
     pub fn new_list(&self) {
         if self.enum_idx >= self.idx + self.num {
             panic!("Display list overflow (compile)!");
         }
-        unsafe {
-            gl::NewList(self.enum_idx, gl::GL_COMPILE);
-        }
+        self.renderer.new_mesh(self.enum_idx);
     }
 
     pub fn end_list(&mut self) {
-        unsafe {
-            gl::EndList();
-        }
+        self.renderer.end_mesh();
         self.enum_idx += 1;
     }
 
@@ -40,16 +44,54 @@ impl DisplayList {
         if i >= self.num {
             panic!("Display list overflow (call)!");
         }
-        unsafe {
-            gl::CallList(self.idx + i);
-        }
+        self.renderer.call_mesh(self.idx + i);
+    }
+}
+
+impl fmt::Debug for DisplayList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DisplayList")
+            .field("idx", &self.idx)
+            .field("num", &self.num)
+            .field("enum_idx", &self.enum_idx)
+            .finish()
     }
 }
 
 impl Drop for DisplayList {
     fn drop(&mut self) {
-        unsafe {
-            gl::DeleteLists(self.idx, self.num as i32);
+        self.renderer.delete_meshes(self.idx, self.num);
+    }
+}
+
+// Compiled the first time it is called, so that shapes can be created without any GL context.
+// Likewise, a deserialized one is compiled again.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LazyDisplayList {
+    num: u32,
+    #[serde(skip)]
+    display_list: RefCell<Option<DisplayList>>,
+}
+
+impl LazyDisplayList {
+    pub fn new(num: u32) -> Self {
+        LazyDisplayList {
+            num,
+            display_list: RefCell::new(None),
         }
     }
+
+    pub fn call<C>(&self, renderer: &Rc<dyn Renderer>, i: u32, compile: C)
+    where
+        C: FnOnce(&mut DisplayList),
+    {
+        let mut display_list = self.display_list.borrow_mut();
+        display_list
+            .get_or_insert_with(|| {
+                let mut display_list = DisplayList::new(renderer, self.num);
+                compile(&mut display_list);
+                display_list
+            })
+            .call(i);
+    }
 }
@@ -0,0 +1,40 @@
+// Maths used by the simulation, which replays depend on.
+//
+// The standard library functions call the libm of the platform, the results of which may differ
+// between platforms. These ones are pure Rust and give bit-identical results everywhere.
+//
+// The bulletml crate is vendored so that the expressions of the bullet patterns go through libm
+// as well.
+
+pub fn sin(x: f32) -> f32 {
+    libm::sinf(x)
+}
+
+pub fn cos(x: f32) -> f32 {
+    libm::cosf(x)
+}
+
+pub fn atan2(y: f32, x: f32) -> f32 {
+    libm::atan2f(y, x)
+}
+
+pub fn sqrt(x: f32) -> f32 {
+    libm::sqrtf(x)
+}
+
+// Replays recorded on one platform play on the others as long as these do not change.
+#[test]
+fn should_give_the_same_bits_everywhere() {
+    assert_eq!(sin(0.5).to_bits(), 0x3ef5_7744);
+    assert_eq!(sin(-3.).to_bits(), 0xbe10_81c3);
+    assert_eq!(sin(100.).to_bits(), 0xbf01_a12e);
+    assert_eq!(cos(1.).to_bits(), 0x3f0a_5140);
+    assert_eq!(cos(2.).to_bits(), 0xbed5_1133);
+    assert_eq!(cos(100.).to_bits(), 0x3f5c_c0ee);
+    assert_eq!(atan2(1., 2.).to_bits(), 0x3eed_6338);
+    assert_eq!(atan2(-3., -4.).to_bits(), 0xc01f_e0bc);
+    assert_eq!(atan2(0.5, -0.25).to_bits(), 0x4002_3454);
+    assert_eq!(sqrt(2.).to_bits(), 0x3fb5_04f3);
+    assert_eq!(sqrt(10.).to_bits(), 0x404a_62c2);
+    assert_eq!(sqrt(0.3).to_bits(), 0x3f0c_378c);
+}
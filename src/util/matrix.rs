@@ -0,0 +1,182 @@
+// 4x4 matrices, column-major as in OpenGL, and the matrix stacks of the fixed-function
+// pipeline for the renderers which have to do without it.
+
+pub type Matrix = [f32; 16];
+
+pub const IDENTITY: Matrix = [
+    1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1.,
+];
+
+pub fn mul(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut r = [0.; 16];
+    for col in 0..4 {
+        for row in 0..4 {
+            r[col * 4 + row] = (0..4).map(|k| a[k * 4 + row] * b[col * 4 + k]).sum();
+        }
+    }
+    r
+}
+
+pub fn transform(m: &Matrix, v: [f32; 4]) -> [f32; 4] {
+    let mut r = [0.; 4];
+    for (row, r) in r.iter_mut().enumerate() {
+        *r = (0..4).map(|k| m[k * 4 + row] * v[k]).sum();
+    }
+    r
+}
+
+// glTranslatef
+pub fn translation(x: f32, y: f32, z: f32) -> Matrix {
+    let mut m = IDENTITY;
+    m[12] = x;
+    m[13] = y;
+    m[14] = z;
+    m
+}
+
+// glRotatef, the angle being in degrees.
+pub fn rotation(deg: f32, x: f32, y: f32, z: f32) -> Matrix {
+    let len = f32::sqrt(x * x + y * y + z * z);
+    if len == 0. {
+        return IDENTITY;
+    }
+    let (x, y, z) = (x / len, y / len, z / len);
+    let (s, c) = f32::sin_cos(deg.to_radians());
+    let ic = 1. - c;
+    [
+        x * x * ic + c,
+        y * x * ic + z * s,
+        x * z * ic - y * s,
+        0.,
+        x * y * ic - z * s,
+        y * y * ic + c,
+        y * z * ic + x * s,
+        0.,
+        x * z * ic + y * s,
+        y * z * ic - x * s,
+        z * z * ic + c,
+        0.,
+        0.,
+        0.,
+        0.,
+        1.,
+    ]
+}
+
+// glScalef
+pub fn scaling(x: f32, y: f32, z: f32) -> Matrix {
+    let mut m = IDENTITY;
+    m[0] = x;
+    m[5] = y;
+    m[10] = z;
+    m
+}
+
+// glFrustum
+pub fn frustum(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Matrix {
+    let mut m = [0.; 16];
+    m[0] = 2. * near / (right - left);
+    m[5] = 2. * near / (top - bottom);
+    m[8] = (right + left) / (right - left);
+    m[9] = (top + bottom) / (top - bottom);
+    m[10] = -(far + near) / (far - near);
+    m[11] = -1.;
+    m[14] = -2. * far * near / (far - near);
+    m
+}
+
+// glOrtho
+pub fn ortho(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Matrix {
+    let mut m = IDENTITY;
+    m[0] = 2. / (right - left);
+    m[5] = 2. / (top - bottom);
+    m[10] = -2. / (far - near);
+    m[12] = -(right + left) / (right - left);
+    m[13] = -(top + bottom) / (top - bottom);
+    m[14] = -(far + near) / (far - near);
+    m
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MatrixMode {
+    Projection,
+    ModelView,
+}
+
+pub struct MatrixStacks {
+    matrix_mode: MatrixMode,
+    projection: Vec<Matrix>,
+    model_view: Vec<Matrix>,
+}
+
+#[allow(clippy::new_without_default)]
+impl MatrixStacks {
+    pub fn new() -> Self {
+        MatrixStacks {
+            matrix_mode: MatrixMode::ModelView,
+            projection: vec![IDENTITY],
+            model_view: vec![IDENTITY],
+        }
+    }
+
+    pub fn matrix_mode(&mut self, matrix_mode: MatrixMode) {
+        self.matrix_mode = matrix_mode;
+    }
+
+    fn stack(&mut self) -> &mut Vec<Matrix> {
+        match self.matrix_mode {
+            MatrixMode::Projection => &mut self.projection,
+            MatrixMode::ModelView => &mut self.model_view,
+        }
+    }
+
+    fn current(&mut self) -> &mut Matrix {
+        self.stack().last_mut().unwrap()
+    }
+
+    pub fn load_identity(&mut self) {
+        *self.current() = IDENTITY;
+    }
+
+    pub fn mult(&mut self, m: &Matrix) {
+        let current = self.current();
+        *current = mul(current, m);
+    }
+
+    pub fn push(&mut self) {
+        let current = *self.current();
+        self.stack().push(current);
+    }
+
+    // The bottom of the stack is never popped.
+    pub fn pop(&mut self) {
+        let stack = self.stack();
+        if stack.len() > 1 {
+            stack.pop();
+        }
+    }
+
+    pub fn model_view(&self) -> &Matrix {
+        self.model_view.last().unwrap()
+    }
+
+    pub fn model_view_projection(&self) -> Matrix {
+        mul(self.projection.last().unwrap(), self.model_view())
+    }
+}
+
+#[test]
+fn matrix_stacks_should_compose_like_opengl() {
+    let mut stacks = MatrixStacks::new();
+    stacks.matrix_mode(MatrixMode::Projection);
+    stacks.mult(&scaling(2., 2., 2.));
+    stacks.matrix_mode(MatrixMode::ModelView);
+    stacks.mult(&translation(1., 0., 0.));
+    stacks.push();
+    stacks.mult(&rotation(90., 0., 0., 1.));
+    let v = transform(&stacks.model_view_projection(), [1., 0., 0., 1.]);
+    assert!((v[0] - 2.).abs() < 1e-6 && (v[1] - 2.).abs() < 1e-6);
+    stacks.pop();
+    let v = transform(&stacks.model_view_projection(), [1., 0., 0., 1.]);
+    assert!((v[0] - 4.).abs() < 1e-6 && v[1].abs() < 1e-6);
+}
@@ -1,5 +1,11 @@
 pub mod color;
 pub mod display_list;
+pub mod hash;
+pub mod math;
+pub mod matrix;
 pub mod rand;
+pub mod raster;
+pub mod renderer;
+pub mod serde_array;
 pub mod texture;
 pub mod vector;
@@ -1,7 +1,9 @@
 use rand_core::{RngCore, SeedableRng};
 use rand_xorshift::XorShiftRng;
+use std::hash::Hasher;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+#[derive(Serialize, Deserialize)]
 pub struct Rand {
     pub rng: XorShiftRng,
     #[cfg(feature = "game_recorder")]
@@ -39,6 +41,12 @@ impl Rand {
             .unwrap_or_else(|_| 0)
     }
 
+    // The next number is enough to tell generators apart, it is drawn from a copy so that the
+    // sequence is left untouched.
+    pub fn hash_state<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.rng.clone().next_u64());
+    }
+
     pub fn set_seed(&mut self, seed: u64) {
         record_event_set_rand_seed!(self.rng_id, seed);
         self.rng = XorShiftRng::seed_from_u64(seed);
@@ -0,0 +1,660 @@
+use image::{Pixel, Rgba, RgbaImage};
+use std::collections::HashMap;
+
+use crate::util::matrix::{self, MatrixMode, MatrixStacks};
+use crate::util::renderer::{BlendFunc, Primitive};
+
+// A software implementation of the part of the fixed-function OpenGL pipeline the game draws
+// with: matrix stacks, immediate mode lines and polygons with smooth colors, display lists,
+// blending, back face culling and modulated textures. It draws into memory, without any GPU.
+
+type Rgb = [f32; 3];
+type Rgba32 = [f32; 4];
+
+fn lerp<A: AsRef<[f32]> + AsMut<[f32]> + Copy>(a: A, b: A, t: f32) -> A {
+    let mut r = a;
+    for (r, b) in r.as_mut().iter_mut().zip(b.as_ref()) {
+        *r += (b - *r) * t;
+    }
+    r
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Vertex {
+    clip: [f32; 4],
+    color: Rgba32,
+    tex_coord: [f32; 2],
+}
+
+impl Vertex {
+    fn lerp(self, other: Vertex, t: f32) -> Vertex {
+        Vertex {
+            clip: lerp(self.clip, other.clip, t),
+            color: lerp(self.color, other.color, t),
+            tex_coord: lerp(self.tex_coord, other.tex_coord, t),
+        }
+    }
+
+    // Distance to the near clipping plane, positive on the visible side.
+    fn near_distance(&self) -> f32 {
+        self.clip[2] + self.clip[3]
+    }
+}
+
+// A vertex once projected on the window, y going upwards.
+#[derive(Clone, Copy, Debug)]
+struct WindowVertex {
+    x: f32,
+    y: f32,
+    color: Rgba32,
+    tex_coord: [f32; 2],
+}
+
+// What display lists record.
+#[derive(Clone, Copy, Debug)]
+enum Command {
+    Color(Rgba32),
+    TexCoord([f32; 2]),
+    Begin(Primitive),
+    Vertex([f32; 3]),
+    End,
+    PushMatrix,
+    PopMatrix,
+    Translate([f32; 3]),
+    Rotate(f32, [f32; 3]),
+    Scale([f32; 3]),
+}
+
+// Textures are sampled with GL_LINEAR filtering and clamped, their first row being at the
+// bottom as with glTexImage2D.
+struct Texture {
+    width: usize,
+    height: usize,
+    texels: Vec<Rgba32>,
+}
+
+impl Texture {
+    fn sample(&self, tex_coord: [f32; 2]) -> Rgba32 {
+        let (width, height) = (self.width as f32, self.height as f32);
+        let x = (tex_coord[0] * width - 0.5).max(0.).min(width - 1.);
+        let y = (tex_coord[1] * height - 0.5).max(0.).min(height - 1.);
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+        let texel = |x: usize, y: usize| self.texels[y * self.width + x];
+        lerp(
+            lerp(texel(x0, y0), texel(x1, y0), fx),
+            lerp(texel(x0, y1), texel(x1, y1), fx),
+            fy,
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Viewport {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+pub struct Rasterizer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+    clear_color: Rgb,
+    viewport: Viewport,
+    transforms: MatrixStacks,
+    color: Rgba32,
+    tex_coord: [f32; 2],
+    blend: bool,
+    blend_func: BlendFunc,
+    cull_face: bool,
+    line_width: f32,
+    primitive: Option<Primitive>,
+    vertices: Vec<Vertex>,
+    lists: HashMap<u32, Vec<Command>>,
+    next_list: u32,
+    compiling: Option<(u32, Vec<Command>)>,
+    textures: HashMap<u32, Texture>,
+    next_texture: u32,
+    texture: Option<u32>,
+}
+
+impl Rasterizer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Rasterizer {
+            width,
+            height,
+            pixels: vec![[0.; 3]; width * height],
+            clear_color: [0.; 3],
+            viewport: Viewport {
+                x: 0,
+                y: 0,
+                width: width as i32,
+                height: height as i32,
+            },
+            transforms: MatrixStacks::new(),
+            color: [1.; 4],
+            tex_coord: [0.; 2],
+            blend: false,
+            blend_func: BlendFunc::Additive,
+            cull_face: false,
+            line_width: 1.,
+            primitive: None,
+            vertices: Vec::new(),
+            lists: HashMap::new(),
+            next_list: 1,
+            compiling: None,
+            textures: HashMap::new(),
+            next_texture: 1,
+            texture: None,
+        }
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.pixels = vec![self.clear_color; width * height];
+    }
+
+    // State
+
+    pub fn set_clear_color(&mut self, r: f32, g: f32, b: f32) {
+        self.clear_color = [r, g, b];
+    }
+
+    pub fn clear(&mut self) {
+        let clear_color = self.clear_color;
+        for pixel in &mut self.pixels {
+            *pixel = clear_color;
+        }
+    }
+
+    pub fn viewport(&mut self, x: i32, y: i32, width: i32, height: i32) {
+        self.viewport = Viewport {
+            x,
+            y,
+            width,
+            height,
+        };
+    }
+
+    pub fn set_blend(&mut self, blend: bool) {
+        self.blend = blend;
+    }
+
+    pub fn set_blend_func(&mut self, blend_func: BlendFunc) {
+        self.blend_func = blend_func;
+    }
+
+    pub fn set_cull_face(&mut self, cull_face: bool) {
+        self.cull_face = cull_face;
+    }
+
+    pub fn set_line_width(&mut self, line_width: f32) {
+        self.line_width = line_width;
+    }
+
+    pub fn set_color(&mut self, r: f32, g: f32, b: f32, a: f32) {
+        self.command(Command::Color([r, g, b, a]));
+    }
+
+    // Matrices
+
+    pub fn matrix_mode(&mut self, matrix_mode: MatrixMode) {
+        self.transforms.matrix_mode(matrix_mode);
+    }
+
+    pub fn load_identity(&mut self) {
+        self.transforms.load_identity();
+    }
+
+    pub fn mult_matrix(&mut self, m: &[f32; 16]) {
+        self.transforms.mult(m);
+    }
+
+    pub fn push_matrix(&mut self) {
+        self.command(Command::PushMatrix);
+    }
+
+    pub fn pop_matrix(&mut self) {
+        self.command(Command::PopMatrix);
+    }
+
+    pub fn translate(&mut self, x: f32, y: f32, z: f32) {
+        self.command(Command::Translate([x, y, z]));
+    }
+
+    pub fn rotate(&mut self, deg: f32, x: f32, y: f32, z: f32) {
+        self.command(Command::Rotate(deg, [x, y, z]));
+    }
+
+    pub fn scale(&mut self, x: f32, y: f32, z: f32) {
+        self.command(Command::Scale([x, y, z]));
+    }
+
+    pub fn frustum(&mut self, left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) {
+        self.mult_matrix(&matrix::frustum(left, right, bottom, top, near, far));
+    }
+
+    pub fn ortho(&mut self, left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) {
+        self.mult_matrix(&matrix::ortho(left, right, bottom, top, near, far));
+    }
+
+    // Immediate mode
+
+    pub fn begin(&mut self, primitive: Primitive) {
+        self.command(Command::Begin(primitive));
+    }
+
+    pub fn tex_coord(&mut self, u: f32, v: f32) {
+        self.command(Command::TexCoord([u, v]));
+    }
+
+    pub fn vertex(&mut self, x: f32, y: f32, z: f32) {
+        self.command(Command::Vertex([x, y, z]));
+    }
+
+    pub fn end(&mut self) {
+        self.command(Command::End);
+    }
+
+    // Display lists
+
+    pub fn gen_lists(&mut self, num: u32) -> u32 {
+        let idx = self.next_list;
+        self.next_list += num;
+        idx
+    }
+
+    pub fn new_list(&mut self, idx: u32) {
+        self.compiling = Some((idx, Vec::new()));
+    }
+
+    pub fn end_list(&mut self) {
+        if let Some((idx, commands)) = self.compiling.take() {
+            self.lists.insert(idx, commands);
+        }
+    }
+
+    pub fn call_list(&mut self, idx: u32) {
+        if let Some(commands) = self.lists.remove(&idx) {
+            for command in &commands {
+                self.command(*command);
+            }
+            self.lists.insert(idx, commands);
+        }
+    }
+
+    pub fn delete_lists(&mut self, idx: u32, num: u32) {
+        for i in idx..idx + num {
+            self.lists.remove(&i);
+        }
+    }
+
+    // Textures
+
+    pub fn create_texture(&mut self, width: usize, height: usize, rgba: &[u8]) -> u32 {
+        let idx = self.next_texture;
+        self.next_texture += 1;
+        let texels = rgba
+            .chunks_exact(4)
+            .map(|texel| {
+                let mut color = [0.; 4];
+                for (c, t) in color.iter_mut().zip(texel) {
+                    *c = f32::from(*t) / 255.;
+                }
+                color
+            })
+            .collect();
+        self.textures.insert(
+            idx,
+            Texture {
+                width,
+                height,
+                texels,
+            },
+        );
+        idx
+    }
+
+    // Replaces the texture with the bottom left corner of the image, as glCopyTexImage2D does.
+    pub fn copy_to_texture(&mut self, idx: u32, width: usize, height: usize) {
+        let mut texels = Vec::with_capacity(width * height);
+        for ty in 0..height {
+            for tx in 0..width {
+                texels.push(if tx < self.width && ty < self.height {
+                    let rgb = self.pixels[(self.height - 1 - ty) * self.width + tx];
+                    [rgb[0], rgb[1], rgb[2], 1.]
+                } else {
+                    [0., 0., 0., 1.]
+                });
+            }
+        }
+        self.textures.insert(
+            idx,
+            Texture {
+                width,
+                height,
+                texels,
+            },
+        );
+    }
+
+    // Polygons are textured as long as a texture is bound, lines never are.
+    pub fn bind_texture(&mut self, idx: Option<u32>) {
+        self.texture = idx;
+    }
+
+    pub fn delete_texture(&mut self, idx: u32) {
+        self.textures.remove(&idx);
+        if self.texture == Some(idx) {
+            self.texture = None;
+        }
+    }
+
+    // Commands are recorded while compiling a display list, as with GL_COMPILE.
+    fn command(&mut self, command: Command) {
+        if let Some((_, commands)) = &mut self.compiling {
+            commands.push(command);
+            return;
+        }
+        match command {
+            Command::Color(color) => self.color = color,
+            Command::TexCoord(tex_coord) => self.tex_coord = tex_coord,
+            Command::Begin(primitive) => {
+                self.primitive = Some(primitive);
+                self.vertices.clear();
+            }
+            Command::Vertex([x, y, z]) => {
+                let m = self.transforms.model_view_projection();
+                self.vertices.push(Vertex {
+                    clip: matrix::transform(&m, [x, y, z, 1.]),
+                    color: self.color,
+                    tex_coord: self.tex_coord,
+                });
+            }
+            Command::End => {
+                if let Some(primitive) = self.primitive.take() {
+                    let vertices = std::mem::replace(&mut self.vertices, Vec::new());
+                    self.draw_primitive(primitive, &vertices);
+                }
+            }
+            Command::PushMatrix => self.transforms.push(),
+            Command::PopMatrix => self.transforms.pop(),
+            Command::Translate([x, y, z]) => self.mult_matrix(&matrix::translation(x, y, z)),
+            Command::Rotate(deg, [x, y, z]) => self.mult_matrix(&matrix::rotation(deg, x, y, z)),
+            Command::Scale([x, y, z]) => self.mult_matrix(&matrix::scaling(x, y, z)),
+        }
+    }
+
+    // Primitives
+
+    fn draw_primitive(&mut self, primitive: Primitive, vertices: &[Vertex]) {
+        match primitive {
+            Primitive::Lines => {
+                for pair in vertices.chunks_exact(2) {
+                    self.draw_line(pair[0], pair[1]);
+                }
+            }
+            Primitive::LineStrip | Primitive::LineLoop => {
+                for pair in vertices.windows(2) {
+                    self.draw_line(pair[0], pair[1]);
+                }
+                if primitive == Primitive::LineLoop && vertices.len() > 2 {
+                    self.draw_line(vertices[vertices.len() - 1], vertices[0]);
+                }
+            }
+            Primitive::Triangles => {
+                for triangle in vertices.chunks_exact(3) {
+                    self.draw_polygon(triangle);
+                }
+            }
+            Primitive::TriangleFan => {
+                if vertices.len() >= 3 {
+                    self.draw_polygon(vertices);
+                }
+            }
+            Primitive::Quads => {
+                for quad in vertices.chunks_exact(4) {
+                    self.draw_polygon(quad);
+                }
+            }
+        }
+    }
+
+    fn to_window(&self, v: Vertex) -> WindowVertex {
+        let w = v.clip[3];
+        let viewport = self.viewport;
+        WindowVertex {
+            x: viewport.x as f32 + (v.clip[0] / w + 1.) / 2. * viewport.width as f32,
+            y: viewport.y as f32 + (v.clip[1] / w + 1.) / 2. * viewport.height as f32,
+            color: v.color,
+            tex_coord: v.tex_coord,
+        }
+    }
+
+    fn draw_line(&mut self, mut v1: Vertex, mut v2: Vertex) {
+        let (d1, d2) = (v1.near_distance(), v2.near_distance());
+        if d1 < 0. && d2 < 0. {
+            return;
+        }
+        if d1 < 0. {
+            v1 = v1.lerp(v2, d1 / (d1 - d2));
+        } else if d2 < 0. {
+            v2 = v2.lerp(v1, d2 / (d2 - d1));
+        }
+        let (w1, w2) = (self.to_window(v1), self.to_window(v2));
+        let steps = f32::max(f32::abs(w2.x - w1.x), f32::abs(w2.y - w1.y)).ceil();
+        let steps = if steps.is_finite() {
+            steps.min(16384.) as usize
+        } else {
+            return;
+        };
+        // Wide lines are drawn as squares of pixels along the line.
+        let width = self.line_width.round().max(1.) as i32;
+        let ofs = (width - 1) / 2;
+        for i in 0..=steps {
+            let t = if steps == 0 {
+                0.
+            } else {
+                i as f32 / steps as f32
+            };
+            let color = lerp(w1.color, w2.color, t);
+            let x = (w1.x + (w2.x - w1.x) * t).floor() as i32 - ofs;
+            let y = (w1.y + (w2.y - w1.y) * t).floor() as i32 - ofs;
+            for dy in 0..width {
+                for dx in 0..width {
+                    self.plot(x + dx, y + dy, color);
+                }
+            }
+        }
+    }
+
+    // Convex polygons, as all the polygons the game draws are.
+    fn draw_polygon(&mut self, vertices: &[Vertex]) {
+        let mut clipped = Vec::with_capacity(vertices.len() + 1);
+        for (i, &v) in vertices.iter().enumerate() {
+            let next = vertices[(i + 1) % vertices.len()];
+            let (d, next_d) = (v.near_distance(), next.near_distance());
+            if d >= 0. {
+                clipped.push(v);
+            }
+            if (d >= 0.) != (next_d >= 0.) {
+                clipped.push(v.lerp(next, d / (d - next_d)));
+            }
+        }
+        if clipped.len() < 3 {
+            return;
+        }
+        let window = clipped
+            .into_iter()
+            .map(|v| self.to_window(v))
+            .collect::<Vec<WindowVertex>>();
+        let area: f32 = (0..window.len())
+            .map(|i| {
+                let (a, b) = (window[i], window[(i + 1) % window.len()]);
+                a.x * b.y - b.x * a.y
+            })
+            .sum();
+        // Counter-clockwise polygons are front facing.
+        if area == 0. || (self.cull_face && area < 0.) {
+            return;
+        }
+        // The bound texture is taken out of the map while filling, the same as display lists
+        // while they are called.
+        let texture = self
+            .texture
+            .and_then(|idx| self.textures.remove(&idx).map(|texture| (idx, texture)));
+        for i in 1..window.len() - 1 {
+            self.fill_triangle(
+                window[0],
+                window[i],
+                window[i + 1],
+                texture.as_ref().map(|(_, texture)| texture),
+            );
+        }
+        if let Some((idx, texture)) = texture {
+            self.textures.insert(idx, texture);
+        }
+    }
+
+    fn fill_triangle(
+        &mut self,
+        a: WindowVertex,
+        b: WindowVertex,
+        c: WindowVertex,
+        texture: Option<&Texture>,
+    ) {
+        let edge = |p: WindowVertex, q: WindowVertex, x: f32, y: f32| {
+            (q.x - p.x) * (y - p.y) - (q.y - p.y) * (x - p.x)
+        };
+        let area = edge(a, b, c.x, c.y);
+        if area == 0. || !area.is_finite() {
+            return;
+        }
+        let viewport = self.viewport;
+        let min_x = f32::max(a.x.min(b.x).min(c.x).floor(), viewport.x as f32) as i32;
+        let max_x = f32::min(
+            a.x.max(b.x).max(c.x).ceil(),
+            (viewport.x + viewport.width) as f32,
+        ) as i32;
+        let min_y = f32::max(a.y.min(b.y).min(c.y).floor(), viewport.y as f32) as i32;
+        let max_y = f32::min(
+            a.y.max(b.y).max(c.y).ceil(),
+            (viewport.y + viewport.height) as f32,
+        ) as i32;
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let wa = edge(b, c, px, py) / area;
+                let wb = edge(c, a, px, py) / area;
+                let wc = edge(a, b, px, py) / area;
+                if wa < 0. || wb < 0. || wc < 0. {
+                    continue;
+                }
+                let mut color = [0.; 4];
+                for (k, col) in color.iter_mut().enumerate() {
+                    *col = a.color[k] * wa + b.color[k] * wb + c.color[k] * wc;
+                }
+                if let Some(texture) = texture {
+                    let tex_coord = [
+                        a.tex_coord[0] * wa + b.tex_coord[0] * wb + c.tex_coord[0] * wc,
+                        a.tex_coord[1] * wa + b.tex_coord[1] * wb + c.tex_coord[1] * wc,
+                    ];
+                    // GL_MODULATE
+                    for (col, t) in color.iter_mut().zip(&texture.sample(tex_coord)) {
+                        *col *= t;
+                    }
+                }
+                self.plot(x, y, color);
+            }
+        }
+    }
+
+    // Window coordinates have their origin at the bottom left corner.
+    fn plot(&mut self, x: i32, y: i32, color: Rgba32) {
+        let viewport = self.viewport;
+        if x < viewport.x
+            || y < viewport.y
+            || x >= viewport.x + viewport.width
+            || y >= viewport.y + viewport.height
+            || x < 0
+            || y < 0
+            || x as usize >= self.width
+            || y as usize >= self.height
+        {
+            return;
+        }
+        let idx = (self.height - 1 - y as usize) * self.width + x as usize;
+        let dst = self.pixels[idx];
+        let (src_factor, dst_factor) = if self.blend {
+            match self.blend_func {
+                BlendFunc::Additive => (color[3], 1.),
+                BlendFunc::Alpha => (color[3], 1. - color[3]),
+                BlendFunc::ConstantAdditive(alpha) => (alpha, 1.),
+            }
+        } else {
+            (1., 0.)
+        };
+        let mut blended = [0.; 3];
+        for (k, blended) in blended.iter_mut().enumerate() {
+            *blended = (color[k] * src_factor + dst[k] * dst_factor)
+                .max(0.)
+                .min(1.);
+        }
+        self.pixels[idx] = blended;
+    }
+
+    // Output
+
+    pub fn to_image(&self) -> RgbaImage {
+        let mut image = RgbaImage::new(self.width as u32, self.height as u32);
+        for (pixel, rgb) in image.pixels_mut().zip(&self.pixels) {
+            *pixel = Rgba::from_channels(
+                (rgb[0] * 255.).round() as u8,
+                (rgb[1] * 255.).round() as u8,
+                (rgb[2] * 255.).round() as u8,
+                255,
+            );
+        }
+        image
+    }
+}
+
+#[test]
+fn rasterizer_should_fill_polygons_and_blend_lines() {
+    let mut rasterizer = Rasterizer::new(16, 16);
+    rasterizer.matrix_mode(MatrixMode::Projection);
+    rasterizer.ortho(0., 16., 16., 0., -1., 1.);
+    rasterizer.matrix_mode(MatrixMode::ModelView);
+    rasterizer.set_color(1., 0., 0., 1.);
+    rasterizer.begin(Primitive::Quads);
+    rasterizer.vertex(2., 2., 0.);
+    rasterizer.vertex(2., 8., 0.);
+    rasterizer.vertex(8., 8., 0.);
+    rasterizer.vertex(8., 2., 0.);
+    rasterizer.end();
+    rasterizer.set_blend(true);
+    rasterizer.set_blend_func(BlendFunc::Additive);
+    let list = rasterizer.gen_lists(1);
+    rasterizer.new_list(list);
+    rasterizer.set_color(0., 1., 0., 0.5);
+    rasterizer.begin(Primitive::Lines);
+    rasterizer.vertex(0., 4.5, 0.);
+    rasterizer.vertex(16., 4.5, 0.);
+    rasterizer.end();
+    rasterizer.end_list();
+    rasterizer.call_list(list);
+
+    let image = rasterizer.to_image();
+    assert_eq!(image.get_pixel(5, 6).channels(), &[255, 0, 0, 255]);
+    assert_eq!(image.get_pixel(12, 12).channels(), &[0, 0, 0, 255]);
+    assert_eq!(image.get_pixel(5, 4).channels(), &[255, 128, 0, 255]);
+    assert_eq!(image.get_pixel(12, 4).channels(), &[0, 128, 0, 255]);
+}
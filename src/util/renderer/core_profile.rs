@@ -0,0 +1,777 @@
+use image::RgbaImage;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{c_void, CString};
+use std::mem;
+use std::ptr;
+
+use crate::gl;
+use crate::tt::errors::GameError;
+use crate::util::matrix::{self, MatrixStacks};
+
+use super::fixed_function::read_gl_pixels;
+use super::{BlendFunc, MatrixMode, Primitive, Renderer};
+
+const VERTEX_SHADER: &str = r#"
+#version 330 core
+
+uniform mat4 mvp;
+uniform vec4 color;
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec4 vertex_color;
+layout(location = 2) in vec2 vertex_tex_coord;
+layout(location = 3) in float current_color;
+
+out vec4 frag_color;
+out vec2 frag_tex_coord;
+
+void main() {
+    gl_Position = mvp * vec4(position, 1.0);
+    frag_color = mix(vertex_color, color, current_color);
+    frag_tex_coord = vertex_tex_coord;
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+#version 330 core
+
+uniform sampler2D tex;
+uniform bool textured;
+
+in vec4 frag_color;
+in vec2 frag_tex_coord;
+
+out vec4 out_color;
+
+void main() {
+    out_color = textured ? frag_color * texture(tex, frag_tex_coord) : frag_color;
+}
+"#;
+
+// Core contexts have no wide lines, nor smooth ones: every line is expanded into a quad facing the
+// screen, as wide as the line plus the pixel across which it fades out at its edges.
+const LINE_GEOMETRY_SHADER: &str = r#"
+#version 330 core
+
+uniform vec2 viewport_size;
+uniform float line_width;
+
+layout(lines) in;
+layout(triangle_strip, max_vertices = 4) out;
+
+in vec4 frag_color[];
+
+out vec4 line_color;
+out float line_offset;
+
+void emit(vec4 position, vec4 color, vec2 offset, float side) {
+    gl_Position = position + vec4(offset * side * position.w, 0.0, 0.0);
+    line_color = color;
+    line_offset = side * (line_width / 2.0 + 0.5);
+    EmitVertex();
+}
+
+void main() {
+    vec4 p0 = gl_in[0].gl_Position;
+    vec4 p1 = gl_in[1].gl_Position;
+    vec4 c0 = frag_color[0];
+    vec4 c1 = frag_color[1];
+    // Clipped against the near plane first, so that both ends can be projected.
+    float d0 = p0.z + p0.w;
+    float d1 = p1.z + p1.w;
+    if (d0 < 0.0 && d1 < 0.0) {
+        return;
+    }
+    if (d0 < 0.0) {
+        float t = d0 / (d0 - d1);
+        p0 = mix(p0, p1, t);
+        c0 = mix(c0, c1, t);
+    } else if (d1 < 0.0) {
+        float t = d1 / (d1 - d0);
+        p1 = mix(p1, p0, t);
+        c1 = mix(c1, c0, t);
+    }
+    vec2 dir = (p1.xy / p1.w - p0.xy / p0.w) * viewport_size;
+    if (dot(dir, dir) < 1e-12) {
+        dir = vec2(1.0, 0.0);
+    }
+    vec2 normal = normalize(vec2(-dir.y, dir.x));
+    vec2 offset = normal * (line_width + 1.0) / viewport_size;
+    emit(p0, c0, offset, 1.0);
+    emit(p0, c0, offset, -1.0);
+    emit(p1, c1, offset, 1.0);
+    emit(p1, c1, offset, -1.0);
+    EndPrimitive();
+}
+"#;
+
+const LINE_FRAGMENT_SHADER: &str = r#"
+#version 330 core
+
+uniform float line_width;
+
+in vec4 line_color;
+in float line_offset;
+
+out vec4 out_color;
+
+void main() {
+    float coverage = clamp(line_width / 2.0 + 0.5 - abs(line_offset), 0.0, 1.0);
+    out_color = vec4(line_color.rgb, line_color.a * coverage);
+}
+"#;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct Vertex {
+    position: [f32; 3],
+    color: [f32; 4],
+    tex_coord: [f32; 2],
+    // 1 when the vertex takes the color current when it is drawn, as when a display list does
+    // not set the color itself.
+    current_color: f32,
+}
+
+// Location, size and offset in floats of the attributes of the shader.
+const VERTEX_ATTRIBUTES: [(u32, i32, usize); 4] = [(0, 3, 0), (1, 4, 3), (2, 2, 7), (3, 1, 9)];
+
+struct VertexArray {
+    vao: u32,
+    vbo: u32,
+}
+
+impl VertexArray {
+    fn new() -> Self {
+        let (mut vao, mut vbo) = (0, 0);
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindVertexArray(vao);
+            gl::BindBuffer(gl::GL_ARRAY_BUFFER, vbo);
+            let stride = mem::size_of::<Vertex>() as i32;
+            for (location, size, offset) in &VERTEX_ATTRIBUTES {
+                gl::EnableVertexAttribArray(*location);
+                gl::VertexAttribPointer(
+                    *location,
+                    *size,
+                    gl::GL_FLOAT,
+                    gl::GL_FALSE,
+                    stride,
+                    (offset * mem::size_of::<f32>()) as *const c_void,
+                );
+            }
+        }
+        VertexArray { vao, vbo }
+    }
+
+    fn upload(&self, vertices: &[Vertex], usage: u32) {
+        unsafe {
+            gl::BindBuffer(gl::GL_ARRAY_BUFFER, self.vbo);
+            gl::BufferData(
+                gl::GL_ARRAY_BUFFER,
+                (vertices.len() * mem::size_of::<Vertex>()) as isize,
+                vertices.as_ptr() as *const c_void,
+                usage,
+            );
+        }
+    }
+
+    fn delete(&self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
+// A range of vertices drawn as one primitive.
+#[derive(Clone, Copy, Debug)]
+struct Draw {
+    mode: u32,
+    first: i32,
+    count: i32,
+}
+
+struct Mesh {
+    vertex_array: VertexArray,
+    draws: Vec<Draw>,
+    // The color the mesh leaves current after having been drawn.
+    color: Option<[f32; 4]>,
+}
+
+// Display lists are baked into meshes: the vertices are transformed by the matrices set while
+// compiling and take the colors set while compiling.
+struct MeshBuilder {
+    idx: u32,
+    transforms: MatrixStacks,
+    color: Option<[f32; 4]>,
+    vertices: Vec<Vertex>,
+    draws: Vec<Draw>,
+}
+
+struct Program {
+    program: u32,
+    mvp: i32,
+    color: i32,
+    textured: i32,
+    viewport_size: i32,
+    line_width: i32,
+}
+
+// What can only be created once there is an OpenGL context.
+struct Objects {
+    program: Program,
+    line_program: Program,
+    stream: VertexArray,
+    framebuffer: u32,
+}
+
+struct State {
+    objects: Option<Objects>,
+    transforms: MatrixStacks,
+    color: [f32; 4],
+    tex_coord: [f32; 2],
+    primitive: Option<Primitive>,
+    vertices: Vec<Vertex>,
+    meshes: HashMap<u32, Mesh>,
+    next_mesh: u32,
+    compiling: Option<MeshBuilder>,
+    texture: Option<u32>,
+    line_width: f32,
+    viewport_size: [f32; 2],
+}
+
+impl State {
+    fn transforms(&mut self) -> &mut MatrixStacks {
+        match &mut self.compiling {
+            Some(builder) => &mut builder.transforms,
+            None => &mut self.transforms,
+        }
+    }
+
+    fn use_program(&self, program: &Program) {
+        unsafe {
+            gl::UseProgram(program.program);
+            gl::UniformMatrix4fv(
+                program.mvp,
+                1,
+                gl::GL_FALSE,
+                self.transforms.model_view_projection().as_ptr(),
+            );
+            gl::Uniform4fv(program.color, 1, self.color.as_ptr());
+            gl::Uniform1i(program.textured, self.texture.is_some() as i32);
+            gl::Uniform2f(
+                program.viewport_size,
+                self.viewport_size[0],
+                self.viewport_size[1],
+            );
+            gl::Uniform1f(program.line_width, self.line_width);
+        }
+    }
+
+    // Lines go through the program which makes quads of them.
+    fn draw(&self, objects: &Objects, vertex_array: &VertexArray, draws: &[Draw]) {
+        unsafe {
+            gl::BindVertexArray(vertex_array.vao);
+        }
+        for draw in draws {
+            match draw.mode {
+                gl::GL_LINES | gl::GL_LINE_LOOP | gl::GL_LINE_STRIP => {
+                    self.use_program(&objects.line_program)
+                }
+                _ => self.use_program(&objects.program),
+            }
+            unsafe {
+                gl::DrawArrays(draw.mode, draw.first, draw.count);
+            }
+        }
+    }
+}
+
+// The OpenGL 3.3 core profile renderer: meshes are uploaded into vertex buffers once, immediate
+// mode primitives are streamed, and everything is drawn with a shader emulating the little of
+// the fixed-function pipeline the game needs.
+pub struct CoreProfileRenderer {
+    state: RefCell<State>,
+}
+
+#[allow(clippy::new_without_default)]
+impl CoreProfileRenderer {
+    pub fn new() -> Self {
+        CoreProfileRenderer {
+            state: RefCell::new(State {
+                objects: None,
+                transforms: MatrixStacks::new(),
+                color: [1.; 4],
+                tex_coord: [0.; 2],
+                primitive: None,
+                vertices: Vec::new(),
+                meshes: HashMap::new(),
+                next_mesh: 1,
+                compiling: None,
+                texture: None,
+                line_width: 1.,
+                viewport_size: [1.; 2],
+            }),
+        }
+    }
+}
+
+fn gl_enable(cap: u32, enable: bool) {
+    unsafe {
+        if enable {
+            gl::Enable(cap);
+        } else {
+            gl::Disable(cap);
+        }
+    }
+}
+
+// Quads do not exist any more, they are split into triangles.
+fn gl_primitive(primitive: Primitive, vertices: Vec<Vertex>) -> (u32, Vec<Vertex>) {
+    match primitive {
+        Primitive::Lines => (gl::GL_LINES, vertices),
+        Primitive::LineLoop => (gl::GL_LINE_LOOP, vertices),
+        Primitive::LineStrip => (gl::GL_LINE_STRIP, vertices),
+        Primitive::Triangles => (gl::GL_TRIANGLES, vertices),
+        Primitive::TriangleFan => (gl::GL_TRIANGLE_FAN, vertices),
+        Primitive::Quads => (
+            gl::GL_TRIANGLES,
+            vertices
+                .chunks_exact(4)
+                .flat_map(|q| vec![q[0], q[1], q[2], q[0], q[2], q[3]])
+                .collect(),
+        ),
+    }
+}
+
+fn info_log(
+    object: u32,
+    get_iv: unsafe fn(u32, u32, *mut i32),
+    get_info_log: unsafe fn(u32, i32, *mut i32, *mut gl::types::GLchar),
+) -> String {
+    unsafe {
+        let mut len = 0;
+        get_iv(object, gl::GL_INFO_LOG_LENGTH, &mut len);
+        let mut log = vec![0u8; len.max(1) as usize];
+        get_info_log(
+            object,
+            len,
+            ptr::null_mut(),
+            log.as_mut_ptr() as *mut gl::types::GLchar,
+        );
+        String::from_utf8_lossy(&log)
+            .trim_end_matches('\0')
+            .to_string()
+    }
+}
+
+// The info log of the driver when the status is not ok.
+fn check_status(
+    object: u32,
+    status: u32,
+    get_iv: unsafe fn(u32, u32, *mut i32),
+    get_info_log: unsafe fn(u32, i32, *mut i32, *mut gl::types::GLchar),
+) -> Result<(), String> {
+    let mut ok = 0;
+    unsafe {
+        get_iv(object, status, &mut ok);
+    }
+    if ok == 0 {
+        Err(info_log(object, get_iv, get_info_log))
+    } else {
+        Ok(())
+    }
+}
+
+fn compile_shader(kind: u32, source: &str) -> Result<u32, GameError> {
+    let source = CString::new(source).unwrap();
+    unsafe {
+        let shader = gl::CreateShader(kind);
+        gl::ShaderSource(shader, 1, &source.as_ptr(), ptr::null());
+        gl::CompileShader(shader);
+        check_status(
+            shader,
+            gl::GL_COMPILE_STATUS,
+            gl::GetShaderiv,
+            gl::GetShaderInfoLog,
+        )
+        .map_err(|log| format!("shader compilation failed: {}", log))
+        .map_err(Box::from)
+        .map_err(GameError::new_shader)?;
+        Ok(shader)
+    }
+}
+
+fn link_program(sources: &[(u32, &str)]) -> Result<Program, GameError> {
+    let mut shaders = Vec::new();
+    for (kind, source) in sources {
+        shaders.push(compile_shader(*kind, source)?);
+    }
+    unsafe {
+        let program = gl::CreateProgram();
+        for shader in &shaders {
+            gl::AttachShader(program, *shader);
+        }
+        gl::LinkProgram(program);
+        for shader in &shaders {
+            gl::DeleteShader(*shader);
+        }
+        check_status(
+            program,
+            gl::GL_LINK_STATUS,
+            gl::GetProgramiv,
+            gl::GetProgramInfoLog,
+        )
+        .map_err(|log| format!("shader program link failed: {}", log))
+        .map_err(Box::from)
+        .map_err(GameError::new_shader)?;
+        let uniform = |name: &str| {
+            let name = CString::new(name).unwrap();
+            gl::GetUniformLocation(program, name.as_ptr())
+        };
+        Ok(Program {
+            program,
+            mvp: uniform("mvp"),
+            color: uniform("color"),
+            textured: uniform("textured"),
+            viewport_size: uniform("viewport_size"),
+            line_width: uniform("line_width"),
+        })
+    }
+}
+
+impl Renderer for CoreProfileRenderer {
+    fn init(&self) -> Result<(), GameError> {
+        let program = link_program(&[
+            (gl::GL_VERTEX_SHADER, VERTEX_SHADER),
+            (gl::GL_FRAGMENT_SHADER, FRAGMENT_SHADER),
+        ])?;
+        let line_program = link_program(&[
+            (gl::GL_VERTEX_SHADER, VERTEX_SHADER),
+            (gl::GL_GEOMETRY_SHADER, LINE_GEOMETRY_SHADER),
+            (gl::GL_FRAGMENT_SHADER, LINE_FRAGMENT_SHADER),
+        ])?;
+        let mut framebuffer = 0;
+        unsafe {
+            gl::GenFramebuffers(1, &mut framebuffer);
+        }
+        self.state.borrow_mut().objects = Some(Objects {
+            program,
+            line_program,
+            stream: VertexArray::new(),
+            framebuffer,
+        });
+        unsafe {
+            gl::LineWidth(1.);
+            gl::BlendFunc(gl::GL_SRC_ALPHA, gl::GL_ONE);
+            gl::Enable(gl::GL_LINE_SMOOTH);
+            gl::Enable(gl::GL_BLEND);
+            gl::Disable(gl::GL_CULL_FACE);
+            gl::Disable(gl::GL_DEPTH_TEST);
+        }
+        Ok(())
+    }
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.state.borrow_mut().viewport_size = [width as f32, height as f32];
+        unsafe {
+            gl::Viewport(x, y, width, height);
+        }
+    }
+
+    fn set_clear_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        unsafe {
+            gl::ClearColor(r, g, b, a);
+        }
+    }
+
+    fn clear(&self) {
+        unsafe {
+            gl::Clear(gl::GL_COLOR_BUFFER_BIT);
+        }
+    }
+
+    fn read_pixels(&self, width: u32, height: u32) -> RgbaImage {
+        read_gl_pixels(width, height)
+    }
+
+    fn set_blend(&self, blend: bool) {
+        gl_enable(gl::GL_BLEND, blend);
+    }
+
+    fn set_blend_func(&self, blend_func: BlendFunc) {
+        unsafe {
+            match blend_func {
+                BlendFunc::Additive => gl::BlendFunc(gl::GL_SRC_ALPHA, gl::GL_ONE),
+                BlendFunc::Alpha => gl::BlendFunc(gl::GL_SRC_ALPHA, gl::GL_ONE_MINUS_SRC_ALPHA),
+                BlendFunc::ConstantAdditive(alpha) => {
+                    gl::BlendColor(0., 0., 0., alpha);
+                    gl::BlendFunc(gl::GL_CONSTANT_ALPHA, gl::GL_ONE);
+                }
+            }
+        }
+    }
+
+    fn set_cull_face(&self, cull_face: bool) {
+        gl_enable(gl::GL_CULL_FACE, cull_face);
+    }
+
+    fn set_line_width(&self, line_width: f32) {
+        self.state.borrow_mut().line_width = line_width;
+    }
+
+    fn set_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        let mut state = self.state.borrow_mut();
+        let state = &mut *state;
+        match &mut state.compiling {
+            Some(builder) => builder.color = Some([r, g, b, a]),
+            None => state.color = [r, g, b, a],
+        }
+    }
+
+    fn matrix_mode(&self, matrix_mode: MatrixMode) {
+        self.state
+            .borrow_mut()
+            .transforms()
+            .matrix_mode(matrix_mode);
+    }
+
+    fn load_identity(&self) {
+        self.state.borrow_mut().transforms().load_identity();
+    }
+
+    fn mult_matrix(&self, m: &[f32; 16]) {
+        self.state.borrow_mut().transforms().mult(m);
+    }
+
+    fn push_matrix(&self) {
+        self.state.borrow_mut().transforms().push();
+    }
+
+    fn pop_matrix(&self) {
+        self.state.borrow_mut().transforms().pop();
+    }
+
+    fn translate(&self, x: f32, y: f32, z: f32) {
+        self.mult_matrix(&matrix::translation(x, y, z));
+    }
+
+    fn rotate(&self, deg: f32, x: f32, y: f32, z: f32) {
+        self.mult_matrix(&matrix::rotation(deg, x, y, z));
+    }
+
+    fn scale(&self, x: f32, y: f32, z: f32) {
+        self.mult_matrix(&matrix::scaling(x, y, z));
+    }
+
+    fn frustum(&self, left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) {
+        self.mult_matrix(&matrix::frustum(left, right, bottom, top, near, far));
+    }
+
+    fn ortho(&self, left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) {
+        self.mult_matrix(&matrix::ortho(left, right, bottom, top, near, far));
+    }
+
+    fn begin(&self, primitive: Primitive) {
+        let mut state = self.state.borrow_mut();
+        state.primitive = Some(primitive);
+        state.vertices.clear();
+    }
+
+    fn tex_coord(&self, u: f32, v: f32) {
+        self.state.borrow_mut().tex_coord = [u, v];
+    }
+
+    fn vertex(&self, x: f32, y: f32, z: f32) {
+        let mut state = self.state.borrow_mut();
+        let tex_coord = state.tex_coord;
+        let vertex = match &state.compiling {
+            Some(builder) => {
+                let p = matrix::transform(builder.transforms.model_view(), [x, y, z, 1.]);
+                Vertex {
+                    position: [p[0], p[1], p[2]],
+                    color: builder.color.unwrap_or([1.; 4]),
+                    tex_coord,
+                    current_color: if builder.color.is_some() { 0. } else { 1. },
+                }
+            }
+            None => Vertex {
+                position: [x, y, z],
+                color: state.color,
+                tex_coord,
+                current_color: 0.,
+            },
+        };
+        state.vertices.push(vertex);
+    }
+
+    fn end(&self) {
+        let mut state = self.state.borrow_mut();
+        let state = &mut *state;
+        let primitive = match state.primitive.take() {
+            Some(primitive) => primitive,
+            None => return,
+        };
+        let vertices = mem::replace(&mut state.vertices, Vec::new());
+        let (mode, vertices) = gl_primitive(primitive, vertices);
+        if let Some(builder) = &mut state.compiling {
+            builder.draws.push(Draw {
+                mode,
+                first: builder.vertices.len() as i32,
+                count: vertices.len() as i32,
+            });
+            builder.vertices.extend(vertices);
+        } else if let Some(objects) = &state.objects {
+            objects.stream.upload(&vertices, gl::GL_STREAM_DRAW);
+            state.draw(
+                objects,
+                &objects.stream,
+                &[Draw {
+                    mode,
+                    first: 0,
+                    count: vertices.len() as i32,
+                }],
+            );
+        }
+    }
+
+    fn gen_meshes(&self, num: u32) -> u32 {
+        let mut state = self.state.borrow_mut();
+        let idx = state.next_mesh;
+        state.next_mesh += num;
+        idx
+    }
+
+    fn new_mesh(&self, idx: u32) {
+        self.state.borrow_mut().compiling = Some(MeshBuilder {
+            idx,
+            transforms: MatrixStacks::new(),
+            color: None,
+            vertices: Vec::new(),
+            draws: Vec::new(),
+        });
+    }
+
+    fn end_mesh(&self) {
+        let mut state = self.state.borrow_mut();
+        if let Some(builder) = state.compiling.take() {
+            let vertex_array = VertexArray::new();
+            vertex_array.upload(&builder.vertices, gl::GL_STATIC_DRAW);
+            let mesh = Mesh {
+                vertex_array,
+                draws: builder.draws,
+                color: builder.color,
+            };
+            if let Some(old_mesh) = state.meshes.insert(builder.idx, mesh) {
+                old_mesh.vertex_array.delete();
+            }
+        }
+    }
+
+    // Meshes are not called while compiling another one.
+    fn call_mesh(&self, idx: u32) {
+        let mut state = self.state.borrow_mut();
+        let state = &mut *state;
+        let color = match state.meshes.get(&idx) {
+            Some(mesh) => {
+                if let Some(objects) = &state.objects {
+                    state.draw(objects, &mesh.vertex_array, &mesh.draws);
+                }
+                mesh.color
+            }
+            None => return,
+        };
+        if let Some(color) = color {
+            state.color = color;
+        }
+    }
+
+    fn delete_meshes(&self, idx: u32, num: u32) {
+        let mut state = self.state.borrow_mut();
+        for i in idx..idx + num {
+            if let Some(mesh) = state.meshes.remove(&i) {
+                mesh.vertex_array.delete();
+            }
+        }
+    }
+
+    fn create_texture(&self, width: u32, height: u32, rgba: &[u8]) -> u32 {
+        let mut texture: u32 = 0;
+        unsafe {
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::GL_TEXTURE_2D, texture);
+            gl::TexImage2D(
+                gl::GL_TEXTURE_2D,
+                0,
+                gl::GL_RGBA as i32,
+                width as i32,
+                height as i32,
+                0,
+                gl::GL_RGBA,
+                gl::GL_UNSIGNED_BYTE,
+                rgba.as_ptr() as *const c_void,
+            );
+            gl::TexParameteri(
+                gl::GL_TEXTURE_2D,
+                gl::GL_TEXTURE_MIN_FILTER,
+                gl::GL_LINEAR as i32,
+            );
+            gl::TexParameteri(
+                gl::GL_TEXTURE_2D,
+                gl::GL_TEXTURE_MAG_FILTER,
+                gl::GL_LINEAR as i32,
+            );
+        }
+        texture
+    }
+
+    // Renders into the texture through a framebuffer, alpha staying opaque as it does when the
+    // texture is copied from the screen.
+    fn start_render_to_texture(&self, texture: u32, width: u32, height: u32) {
+        let mut state = self.state.borrow_mut();
+        state.viewport_size = [width as f32, height as f32];
+        if let Some(objects) = &state.objects {
+            unsafe {
+                gl::BindFramebuffer(gl::GL_FRAMEBUFFER, objects.framebuffer);
+                gl::FramebufferTexture2D(
+                    gl::GL_FRAMEBUFFER,
+                    gl::GL_COLOR_ATTACHMENT0,
+                    gl::GL_TEXTURE_2D,
+                    texture,
+                    0,
+                );
+                gl::Viewport(0, 0, width as i32, height as i32);
+                gl::Clear(gl::GL_COLOR_BUFFER_BIT);
+                gl::ColorMask(gl::GL_TRUE, gl::GL_TRUE, gl::GL_TRUE, gl::GL_FALSE);
+            }
+        }
+    }
+
+    fn end_render_to_texture(&self, _texture: u32, _width: u32, _height: u32) {
+        unsafe {
+            gl::ColorMask(gl::GL_TRUE, gl::GL_TRUE, gl::GL_TRUE, gl::GL_TRUE);
+            gl::BindFramebuffer(gl::GL_FRAMEBUFFER, 0);
+        }
+    }
+
+    fn bind_texture(&self, texture: Option<u32>) {
+        self.state.borrow_mut().texture = texture;
+        if let Some(texture) = texture {
+            unsafe {
+                gl::BindTexture(gl::GL_TEXTURE_2D, texture);
+            }
+        }
+    }
+
+    fn delete_texture(&self, texture: u32) {
+        let mut state = self.state.borrow_mut();
+        if state.texture == Some(texture) {
+            state.texture = None;
+        }
+        unsafe {
+            gl::DeleteTextures(1, &texture);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use image::RgbaImage;
+use std::cell::RefCell;
+
+use crate::tt::errors::GameError;
+use crate::util::raster::Rasterizer;
+
+use super::{BlendFunc, MatrixMode, Primitive, Renderer};
+
+// Draws in memory with the software rasterizer, without any window nor GPU.
+pub struct CpuRenderer {
+    rasterizer: RefCell<Rasterizer>,
+}
+
+impl CpuRenderer {
+    pub fn new(width: usize, height: usize) -> Self {
+        CpuRenderer {
+            rasterizer: RefCell::new(Rasterizer::new(width, height)),
+        }
+    }
+
+    pub fn resize(&self, width: usize, height: usize) {
+        self.rasterizer.borrow_mut().resize(width, height);
+    }
+}
+
+impl Renderer for CpuRenderer {
+    fn init(&self) -> Result<(), GameError> {
+        let mut rasterizer = self.rasterizer.borrow_mut();
+        rasterizer.set_line_width(1.);
+        rasterizer.set_blend_func(BlendFunc::Additive);
+        rasterizer.set_blend(true);
+        rasterizer.set_cull_face(false);
+        rasterizer.bind_texture(None);
+        Ok(())
+    }
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.rasterizer.borrow_mut().viewport(x, y, width, height);
+    }
+
+    // There is no destination alpha to clear.
+    fn set_clear_color(&self, r: f32, g: f32, b: f32, _a: f32) {
+        self.rasterizer.borrow_mut().set_clear_color(r, g, b);
+    }
+
+    fn clear(&self) {
+        self.rasterizer.borrow_mut().clear();
+    }
+
+    fn read_pixels(&self, width: u32, height: u32) -> RgbaImage {
+        let image = self.rasterizer.borrow().to_image();
+        let (width, height) = (width.min(image.width()), height.min(image.height()));
+        let y0 = image.height() - height;
+        RgbaImage::from_fn(width, height, |x, y| *image.get_pixel(x, y0 + y))
+    }
+
+    fn set_blend(&self, blend: bool) {
+        self.rasterizer.borrow_mut().set_blend(blend);
+    }
+
+    fn set_blend_func(&self, blend_func: BlendFunc) {
+        self.rasterizer.borrow_mut().set_blend_func(blend_func);
+    }
+
+    fn set_cull_face(&self, cull_face: bool) {
+        self.rasterizer.borrow_mut().set_cull_face(cull_face);
+    }
+
+    fn set_line_width(&self, line_width: f32) {
+        self.rasterizer.borrow_mut().set_line_width(line_width);
+    }
+
+    fn set_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        self.rasterizer.borrow_mut().set_color(r, g, b, a);
+    }
+
+    fn matrix_mode(&self, matrix_mode: MatrixMode) {
+        self.rasterizer.borrow_mut().matrix_mode(matrix_mode);
+    }
+
+    fn load_identity(&self) {
+        self.rasterizer.borrow_mut().load_identity();
+    }
+
+    fn mult_matrix(&self, m: &[f32; 16]) {
+        self.rasterizer.borrow_mut().mult_matrix(m);
+    }
+
+    fn push_matrix(&self) {
+        self.rasterizer.borrow_mut().push_matrix();
+    }
+
+    fn pop_matrix(&self) {
+        self.rasterizer.borrow_mut().pop_matrix();
+    }
+
+    fn translate(&self, x: f32, y: f32, z: f32) {
+        self.rasterizer.borrow_mut().translate(x, y, z);
+    }
+
+    fn rotate(&self, deg: f32, x: f32, y: f32, z: f32) {
+        self.rasterizer.borrow_mut().rotate(deg, x, y, z);
+    }
+
+    fn scale(&self, x: f32, y: f32, z: f32) {
+        self.rasterizer.borrow_mut().scale(x, y, z);
+    }
+
+    fn frustum(&self, left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) {
+        self.rasterizer
+            .borrow_mut()
+            .frustum(left, right, bottom, top, near, far);
+    }
+
+    fn ortho(&self, left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) {
+        self.rasterizer
+            .borrow_mut()
+            .ortho(left, right, bottom, top, near, far);
+    }
+
+    fn begin(&self, primitive: Primitive) {
+        self.rasterizer.borrow_mut().begin(primitive);
+    }
+
+    fn tex_coord(&self, u: f32, v: f32) {
+        self.rasterizer.borrow_mut().tex_coord(u, v);
+    }
+
+    fn vertex(&self, x: f32, y: f32, z: f32) {
+        self.rasterizer.borrow_mut().vertex(x, y, z);
+    }
+
+    fn end(&self) {
+        self.rasterizer.borrow_mut().end();
+    }
+
+    fn gen_meshes(&self, num: u32) -> u32 {
+        self.rasterizer.borrow_mut().gen_lists(num)
+    }
+
+    fn new_mesh(&self, idx: u32) {
+        self.rasterizer.borrow_mut().new_list(idx);
+    }
+
+    fn end_mesh(&self) {
+        self.rasterizer.borrow_mut().end_list();
+    }
+
+    fn call_mesh(&self, idx: u32) {
+        self.rasterizer.borrow_mut().call_list(idx);
+    }
+
+    fn delete_meshes(&self, idx: u32, num: u32) {
+        self.rasterizer.borrow_mut().delete_lists(idx, num);
+    }
+
+    fn create_texture(&self, width: u32, height: u32, rgba: &[u8]) -> u32 {
+        self.rasterizer
+            .borrow_mut()
+            .create_texture(width as usize, height as usize, rgba)
+    }
+
+    fn start_render_to_texture(&self, _texture: u32, width: u32, height: u32) {
+        self.viewport(0, 0, width as i32, height as i32);
+    }
+
+    fn end_render_to_texture(&self, texture: u32, width: u32, height: u32) {
+        self.rasterizer
+            .borrow_mut()
+            .copy_to_texture(texture, width as usize, height as usize);
+    }
+
+    fn bind_texture(&self, texture: Option<u32>) {
+        self.rasterizer.borrow_mut().bind_texture(texture);
+    }
+
+    fn delete_texture(&self, texture: u32) {
+        self.rasterizer.borrow_mut().delete_texture(texture);
+    }
+}
@@ -0,0 +1,324 @@
+use image::RgbaImage;
+use std::ffi::c_void;
+
+use crate::gl;
+use crate::tt::errors::GameError;
+
+use super::{BlendFunc, MatrixMode, Primitive, Renderer};
+
+// The OpenGL 2.1 renderer, with display lists and immediate mode.
+pub struct FixedFunctionRenderer;
+
+fn gl_primitive(primitive: Primitive) -> u32 {
+    match primitive {
+        Primitive::Lines => gl::GL_LINES,
+        Primitive::LineLoop => gl::GL_LINE_LOOP,
+        Primitive::LineStrip => gl::GL_LINE_STRIP,
+        Primitive::Triangles => gl::GL_TRIANGLES,
+        Primitive::TriangleFan => gl::GL_TRIANGLE_FAN,
+        Primitive::Quads => gl::GL_QUADS,
+    }
+}
+
+fn gl_enable(cap: u32, enable: bool) {
+    unsafe {
+        if enable {
+            gl::Enable(cap);
+        } else {
+            gl::Disable(cap);
+        }
+    }
+}
+
+// Reads the pixels of the current framebuffer, the image starting with the top row.
+pub(super) fn read_gl_pixels(width: u32, height: u32) -> RgbaImage {
+    let row_len = width as usize * 4;
+    let mut pixels = vec![0u8; row_len * height as usize];
+    unsafe {
+        gl::ReadPixels(
+            0,
+            0,
+            width as i32,
+            height as i32,
+            gl::GL_RGBA,
+            gl::GL_UNSIGNED_BYTE,
+            pixels.as_mut_ptr() as *mut c_void,
+        );
+    }
+    let mut image = Vec::with_capacity(pixels.len());
+    for row in pixels.chunks_exact(row_len).rev() {
+        image.extend_from_slice(row);
+    }
+    for pixel in image.chunks_exact_mut(4) {
+        pixel[3] = 255;
+    }
+    RgbaImage::from_raw(width, height, image).unwrap()
+}
+
+impl Renderer for FixedFunctionRenderer {
+    fn init(&self) -> Result<(), GameError> {
+        unsafe {
+            gl::LineWidth(1.);
+            gl::BlendFunc(gl::GL_SRC_ALPHA, gl::GL_ONE);
+            gl::Enable(gl::GL_LINE_SMOOTH);
+            gl::Enable(gl::GL_BLEND);
+            gl::Disable(gl::GL_COLOR_MATERIAL);
+            gl::Disable(gl::GL_CULL_FACE);
+            gl::Disable(gl::GL_DEPTH_TEST);
+            gl::Disable(gl::GL_LIGHTING);
+            gl::Disable(gl::GL_TEXTURE_2D);
+        }
+        Ok(())
+    }
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        unsafe {
+            gl::Viewport(x, y, width, height);
+        }
+    }
+
+    fn set_clear_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        unsafe {
+            gl::ClearColor(r, g, b, a);
+        }
+    }
+
+    fn clear(&self) {
+        unsafe {
+            gl::Clear(gl::GL_COLOR_BUFFER_BIT);
+        }
+    }
+
+    fn read_pixels(&self, width: u32, height: u32) -> RgbaImage {
+        read_gl_pixels(width, height)
+    }
+
+    fn set_blend(&self, blend: bool) {
+        gl_enable(gl::GL_BLEND, blend);
+    }
+
+    fn set_blend_func(&self, blend_func: BlendFunc) {
+        unsafe {
+            match blend_func {
+                BlendFunc::Additive => gl::BlendFunc(gl::GL_SRC_ALPHA, gl::GL_ONE),
+                BlendFunc::Alpha => gl::BlendFunc(gl::GL_SRC_ALPHA, gl::GL_ONE_MINUS_SRC_ALPHA),
+                BlendFunc::ConstantAdditive(alpha) => {
+                    gl::BlendColor(0., 0., 0., alpha);
+                    gl::BlendFunc(gl::GL_CONSTANT_ALPHA, gl::GL_ONE);
+                }
+            }
+        }
+    }
+
+    fn set_cull_face(&self, cull_face: bool) {
+        gl_enable(gl::GL_CULL_FACE, cull_face);
+    }
+
+    fn set_line_width(&self, line_width: f32) {
+        unsafe {
+            gl::LineWidth(line_width);
+        }
+    }
+
+    fn set_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        unsafe {
+            gl::Color4f(r, g, b, a);
+        }
+    }
+
+    fn matrix_mode(&self, matrix_mode: MatrixMode) {
+        unsafe {
+            gl::MatrixMode(match matrix_mode {
+                MatrixMode::Projection => gl::GL_PROJECTION,
+                MatrixMode::ModelView => gl::GL_MODELVIEW,
+            });
+        }
+    }
+
+    fn load_identity(&self) {
+        unsafe {
+            gl::LoadIdentity();
+        }
+    }
+
+    fn mult_matrix(&self, m: &[f32; 16]) {
+        unsafe {
+            gl::MultMatrixf(m.as_ptr());
+        }
+    }
+
+    fn push_matrix(&self) {
+        unsafe {
+            gl::PushMatrix();
+        }
+    }
+
+    fn pop_matrix(&self) {
+        unsafe {
+            gl::PopMatrix();
+        }
+    }
+
+    fn translate(&self, x: f32, y: f32, z: f32) {
+        unsafe {
+            gl::Translatef(x, y, z);
+        }
+    }
+
+    fn rotate(&self, deg: f32, x: f32, y: f32, z: f32) {
+        unsafe {
+            gl::Rotatef(deg, x, y, z);
+        }
+    }
+
+    fn scale(&self, x: f32, y: f32, z: f32) {
+        unsafe {
+            gl::Scalef(x, y, z);
+        }
+    }
+
+    fn frustum(&self, left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) {
+        unsafe {
+            gl::Frustum(
+                f64::from(left),
+                f64::from(right),
+                f64::from(bottom),
+                f64::from(top),
+                f64::from(near),
+                f64::from(far),
+            );
+        }
+    }
+
+    fn ortho(&self, left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) {
+        unsafe {
+            gl::Ortho(
+                f64::from(left),
+                f64::from(right),
+                f64::from(bottom),
+                f64::from(top),
+                f64::from(near),
+                f64::from(far),
+            );
+        }
+    }
+
+    fn begin(&self, primitive: Primitive) {
+        unsafe {
+            gl::Begin(gl_primitive(primitive));
+        }
+    }
+
+    fn tex_coord(&self, u: f32, v: f32) {
+        unsafe {
+            gl::TexCoord2f(u, v);
+        }
+    }
+
+    fn vertex(&self, x: f32, y: f32, z: f32) {
+        unsafe {
+            gl::Vertex3f(x, y, z);
+        }
+    }
+
+    fn end(&self) {
+        unsafe {
+            gl::End();
+        }
+    }
+
+    fn gen_meshes(&self, num: u32) -> u32 {
+        unsafe { gl::GenLists(num as i32) }
+    }
+
+    fn new_mesh(&self, idx: u32) {
+        unsafe {
+            gl::NewList(idx, gl::GL_COMPILE);
+        }
+    }
+
+    fn end_mesh(&self) {
+        unsafe {
+            gl::EndList();
+        }
+    }
+
+    fn call_mesh(&self, idx: u32) {
+        unsafe {
+            gl::CallList(idx);
+        }
+    }
+
+    fn delete_meshes(&self, idx: u32, num: u32) {
+        unsafe {
+            gl::DeleteLists(idx, num as i32);
+        }
+    }
+
+    fn create_texture(&self, width: u32, height: u32, rgba: &[u8]) -> u32 {
+        let mut texture: u32 = 0;
+        unsafe {
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::GL_TEXTURE_2D, texture);
+            gl::TexImage2D(
+                gl::GL_TEXTURE_2D,
+                0,
+                4,
+                width as i32,
+                height as i32,
+                0,
+                gl::GL_RGBA,
+                gl::GL_UNSIGNED_BYTE,
+                rgba.as_ptr() as *const c_void,
+            );
+            gl::TexParameteri(
+                gl::GL_TEXTURE_2D,
+                gl::GL_TEXTURE_MIN_FILTER,
+                gl::GL_LINEAR as i32,
+            );
+            gl::TexParameteri(
+                gl::GL_TEXTURE_2D,
+                gl::GL_TEXTURE_MAG_FILTER,
+                gl::GL_LINEAR as i32,
+            );
+        }
+        texture
+    }
+
+    fn start_render_to_texture(&self, _texture: u32, width: u32, height: u32) {
+        self.viewport(0, 0, width as i32, height as i32);
+    }
+
+    fn end_render_to_texture(&self, texture: u32, width: u32, height: u32) {
+        unsafe {
+            gl::BindTexture(gl::GL_TEXTURE_2D, texture);
+            gl::CopyTexImage2D(
+                gl::GL_TEXTURE_2D,
+                0,
+                gl::GL_RGBA,
+                0,
+                0,
+                width as i32,
+                height as i32,
+                0,
+            );
+        }
+    }
+
+    fn bind_texture(&self, texture: Option<u32>) {
+        unsafe {
+            if let Some(texture) = texture {
+                gl::Enable(gl::GL_TEXTURE_2D);
+                gl::BindTexture(gl::GL_TEXTURE_2D, texture);
+            } else {
+                gl::Disable(gl::GL_TEXTURE_2D);
+            }
+        }
+    }
+
+    fn delete_texture(&self, texture: u32) {
+        unsafe {
+            gl::DeleteTextures(1, &texture);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+pub mod core_profile;
+pub mod cpu;
+pub mod fixed_function;
+
+use image::RgbaImage;
+
+use crate::tt::errors::GameError;
+
+pub use crate::util::matrix::MatrixMode;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Primitive {
+    Lines,
+    LineLoop,
+    LineStrip,
+    Triangles,
+    TriangleFan,
+    Quads,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BlendFunc {
+    // GL_SRC_ALPHA, GL_ONE
+    Additive,
+    // GL_SRC_ALPHA, GL_ONE_MINUS_SRC_ALPHA
+    Alpha,
+    // GL_CONSTANT_ALPHA, GL_ONE
+    ConstantAdditive(f32),
+}
+
+// What the game draws through, whatever draws the pixels in the end.
+//
+// It follows the fixed-function OpenGL pipeline the game was written against: matrix stacks,
+// immediate mode primitives, meshes recorded once and called many times (display lists),
+// and textures for the title logo and the luminous overlay. Methods take `&self` so that the
+// renderer can be shared by the resources which are compiled lazily.
+pub trait Renderer {
+    // Sets up the state the game expects: additive blending, no culling, no texture.
+    fn init(&self) -> Result<(), GameError>;
+
+    // Frame
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32);
+    fn set_clear_color(&self, r: f32, g: f32, b: f32, a: f32);
+    fn clear(&self);
+    // The bottom left corner of what has been drawn, opaque.
+    fn read_pixels(&self, width: u32, height: u32) -> RgbaImage;
+
+    // State
+
+    fn set_blend(&self, blend: bool);
+    fn set_blend_func(&self, blend_func: BlendFunc);
+    fn set_cull_face(&self, cull_face: bool);
+    fn set_line_width(&self, line_width: f32);
+    fn set_color(&self, r: f32, g: f32, b: f32, a: f32);
+
+    // Transforms
+
+    fn matrix_mode(&self, matrix_mode: MatrixMode);
+    fn load_identity(&self);
+    fn mult_matrix(&self, m: &[f32; 16]);
+    fn push_matrix(&self);
+    fn pop_matrix(&self);
+    fn translate(&self, x: f32, y: f32, z: f32);
+    fn rotate(&self, deg: f32, x: f32, y: f32, z: f32);
+    fn scale(&self, x: f32, y: f32, z: f32);
+    fn frustum(&self, left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32);
+    fn ortho(&self, left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32);
+
+    // Primitives
+
+    fn begin(&self, primitive: Primitive);
+    fn tex_coord(&self, u: f32, v: f32);
+    fn vertex(&self, x: f32, y: f32, z: f32);
+    fn end(&self);
+
+    // Meshes
+
+    fn gen_meshes(&self, num: u32) -> u32;
+    fn new_mesh(&self, idx: u32);
+    fn end_mesh(&self);
+    fn call_mesh(&self, idx: u32);
+    fn delete_meshes(&self, idx: u32, num: u32);
+
+    // Textures
+
+    fn create_texture(&self, width: u32, height: u32, rgba: &[u8]) -> u32;
+    // What is drawn until the end of the rendering ends up in the texture, in a viewport of its
+    // size. The texture is cleared at the start, or is the bottom left corner of what has been
+    // cleared before.
+    fn start_render_to_texture(&self, texture: u32, width: u32, height: u32);
+    fn end_render_to_texture(&self, texture: u32, width: u32, height: u32);
+    // Polygons are textured until the texture is unbound.
+    fn bind_texture(&self, texture: Option<u32>);
+    fn delete_texture(&self, texture: u32);
+}
@@ -1,46 +1,42 @@
-use crate::gl;
+use std::rc::Rc;
+
+use crate::util::renderer::Renderer;
 
 pub struct Texture {
+    renderer: Rc<dyn Renderer>,
     num: u32,
 }
 
 impl Texture {
-    pub fn create(name: &str) -> Result<Self, image::ImageError> {
+    pub fn new(renderer: &Rc<dyn Renderer>, width: u32, height: u32, rgba: &[u8]) -> Self {
+        Texture {
+            renderer: renderer.clone(),
+            num: renderer.create_texture(width, height, rgba),
+        }
+    }
+
+    pub fn create(renderer: &Rc<dyn Renderer>, name: &str) -> Result<Self, image::ImageError> {
         let img = image::open("images/".to_string() + name)?;
-        if let image::DynamicImage::ImageRgb8(rgb_img) = img {
-            let mut num: u32 = 0;
-            unsafe {
-                gl::GenTextures(1, &mut num);
-                gl::BindTexture(gl::GL_TEXTURE_2D, num);
-                gl::TexImage2D(
-                    gl::GL_TEXTURE_2D,
-                    0,
-                    3,
-                    rgb_img.dimensions().0 as i32,
-                    rgb_img.dimensions().1 as i32,
-                    0,
-                    gl::GL_RGB,
-                    gl::GL_UNSIGNED_BYTE,
-                    rgb_img.into_raw().as_ptr() as *const std::ffi::c_void,
-                );
-                gl::TexParameteri(
-                    gl::GL_TEXTURE_2D,
-                    gl::GL_TEXTURE_MIN_FILTER,
-                    gl::GL_LINEAR as i32,
-                );
-                gl::TexParameteri(
-                    gl::GL_TEXTURE_2D,
-                    gl::GL_TEXTURE_MAG_FILTER,
-                    gl::GL_LINEAR as i32,
-                );
-            }
-            Ok(Texture { num })
+        if let image::DynamicImage::ImageRgb8(_) = img {
+            let rgba_img = img.to_rgba();
+            let (width, height) = rgba_img.dimensions();
+            Ok(Texture::new(renderer, width, height, &rgba_img.into_raw()))
         } else {
             panic!("Image {} should be of type RGB8!", name);
         }
     }
 
+    pub fn num(&self) -> u32 {
+        self.num
+    }
+
     pub fn bind(&self) {
-        unsafe { gl::BindTexture(gl::GL_TEXTURE_2D, self.num) }
+        self.renderer.bind_texture(Some(self.num))
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        self.renderer.delete_texture(self.num);
     }
 }
@@ -1,6 +1,7 @@
-use crate::gl;
+use crate::util::math;
+use crate::util::renderer::Renderer;
 
-#[derive(PartialEq, Default, Clone, Copy, Debug)]
+#[derive(PartialEq, Default, Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Vector {
     pub x: f32,
     pub y: f32,
@@ -73,7 +74,7 @@ impl std::ops::MulAssign<f32> for Vector {
     }
 }
 
-#[derive(PartialEq, Default, Clone, Copy, Debug)]
+#[derive(PartialEq, Default, Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Vector3 {
     pub x: f32,
     pub y: f32,
@@ -94,36 +95,32 @@ impl Vector3 {
     }
 
     pub fn roll_x(&mut self, d: f32) -> &mut Self {
-        let ty = self.y * f32::cos(d) - self.z * f32::sin(d);
-        self.z = self.y * f32::sin(d) + self.z * f32::cos(d);
+        let ty = self.y * math::cos(d) - self.z * math::sin(d);
+        self.z = self.y * math::sin(d) + self.z * math::cos(d);
         self.y = ty;
         self
     }
 
     pub fn roll_y(&mut self, d: f32) -> &mut Self {
-        let tx = self.x * f32::cos(d) - self.z * f32::sin(d);
-        self.z = self.x * f32::sin(d) + self.z * f32::cos(d);
+        let tx = self.x * math::cos(d) - self.z * math::sin(d);
+        self.z = self.x * math::sin(d) + self.z * math::cos(d);
         self.x = tx;
         self
     }
 
     pub fn roll_z(&mut self, d: f32) -> &mut Self {
-        let tx = self.x * f32::cos(d) - self.y * f32::sin(d);
-        self.y = self.x * f32::sin(d) + self.y * f32::cos(d);
+        let tx = self.x * math::cos(d) - self.y * math::sin(d);
+        self.y = self.x * math::sin(d) + self.y * math::cos(d);
         self.x = tx;
         self
     }
 
-    pub fn gl_translate(&self) {
-        unsafe {
-            gl::Translatef(self.x, self.y, self.z);
-        }
+    pub fn translate(&self, renderer: &dyn Renderer) {
+        renderer.translate(self.x, self.y, self.z);
     }
 
-    pub fn gl_vertex(&self) {
-        unsafe {
-            gl::Vertex3f(self.x, self.y, self.z);
-        }
+    pub fn vertex(&self, renderer: &dyn Renderer) {
+        renderer.vertex(self.x, self.y, self.z);
     }
 
     pub fn blend(v1: Vector3, v2: Vector3, ratio: f32) -> Self {